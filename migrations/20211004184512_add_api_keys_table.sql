@@ -0,0 +1,11 @@
+-- Create API Keys Table
+CREATE TABLE ApiKeys (
+  id serial PRIMARY KEY,
+  name VARCHAR(100) NOT NULL,
+  key_prefix VARCHAR(20) NOT NULL,
+  key_hash VARCHAR(64) UNIQUE NOT NULL,
+  scopes TEXT[] NOT NULL,
+  created_on timestamptz NOT NULL,
+  revoked_on timestamptz
+);
+CREATE INDEX idx_api_key_name ON ApiKeys(name);
@@ -0,0 +1,2 @@
+-- Index properties by region to support regional exports
+CREATE INDEX idx_property_region ON Properties(state_or_region, admin_division);
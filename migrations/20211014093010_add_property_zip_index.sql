@@ -0,0 +1,2 @@
+-- Index properties by zip code to support searching scores by property zip code
+CREATE INDEX idx_property_zip ON Properties(zip_or_postal_code);
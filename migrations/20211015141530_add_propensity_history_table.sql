@@ -0,0 +1,18 @@
+-- Create Propensity History Table, keeping every version of a property's score. Propensities holds the
+-- version currently in effect for each property.
+CREATE TABLE PropensityHistory (
+  id serial PRIMARY KEY,
+  apn VARCHAR(50) NOT NULL,
+  score SMALLINT NOT NULL,
+  zip_or_postal_code VARCHAR(20),
+  effective_on DATE NOT NULL,
+  source VARCHAR(100) NOT NULL,
+  loaded_on timestamptz NOT NULL,
+  UNIQUE (apn, effective_on, source)
+);
+CREATE INDEX idx_propensity_history_apn ON PropensityHistory(apn, effective_on);
+
+-- Scores loaded before history was kept become the first version of each property's history.
+INSERT INTO PropensityHistory (apn, score, zip_or_postal_code, effective_on, source, loaded_on)
+SELECT apn, score, zip_or_postal_code, created_on::DATE, 'initial', created_on
+FROM Propensities;
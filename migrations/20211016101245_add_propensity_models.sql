@@ -0,0 +1,26 @@
+-- Create Propensity Models Table, registering each model whose scores are loaded.
+CREATE TABLE PropensityModels (
+  name VARCHAR(50) PRIMARY KEY,
+  min_score SMALLINT NOT NULL,
+  max_score SMALLINT NOT NULL,
+  description TEXT NOT NULL,
+  created_on timestamptz NOT NULL,
+  CHECK (min_score <= max_score)
+);
+
+-- Scores loaded before models were registered are HomeEquityIntelScore_LineofCredit scores.
+INSERT INTO PropensityModels (name, min_score, max_score, description, created_on)
+VALUES ('heloc', 1, 950, 'Home equity line of credit (HomeEquityIntelScore_LineofCredit)', NOW());
+
+-- Scores are keyed by property and model.
+ALTER TABLE Propensities ADD COLUMN model VARCHAR(50) NOT NULL DEFAULT 'heloc' REFERENCES PropensityModels(name);
+ALTER TABLE Propensities ALTER COLUMN model DROP DEFAULT;
+ALTER TABLE Propensities DROP CONSTRAINT propensities_apn_key;
+ALTER TABLE Propensities ADD CONSTRAINT propensities_apn_model_key UNIQUE (apn, model);
+CREATE INDEX idx_propensity_model_zip ON Propensities(model, zip_or_postal_code);
+
+ALTER TABLE PropensityHistory ADD COLUMN model VARCHAR(50) NOT NULL DEFAULT 'heloc' REFERENCES PropensityModels(name);
+ALTER TABLE PropensityHistory ALTER COLUMN model DROP DEFAULT;
+ALTER TABLE PropensityHistory DROP CONSTRAINT propensityhistory_apn_effective_on_source_key;
+ALTER TABLE PropensityHistory ADD CONSTRAINT propensityhistory_apn_model_effective_on_source_key
+  UNIQUE (apn, model, effective_on, source);
@@ -0,0 +1,13 @@
+-- Record whether higher or lower scores of each model indicate a greater propensity.
+ALTER TABLE PropensityModels ADD COLUMN direction VARCHAR(10) NOT NULL DEFAULT 'higher'
+  CHECK (direction IN ('higher', 'lower'));
+
+-- Create Propensity Tiers Table, naming bands of each model's scores.
+CREATE TABLE PropensityTiers (
+  model VARCHAR(50) NOT NULL REFERENCES PropensityModels(name) ON DELETE CASCADE,
+  name VARCHAR(50) NOT NULL,
+  min_score SMALLINT NOT NULL,
+  max_score SMALLINT NOT NULL,
+  PRIMARY KEY (model, name),
+  CHECK (min_score <= max_score)
+);
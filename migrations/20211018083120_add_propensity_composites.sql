@@ -0,0 +1,16 @@
+-- Create Propensity Composites Tables, defining registered models whose scores are materialized as
+-- the weighted mean of other models' normalized scores.
+CREATE TABLE PropensityComposites (
+  model VARCHAR(50) PRIMARY KEY REFERENCES PropensityModels(name) ON DELETE CASCADE,
+  normalization VARCHAR(20) NOT NULL CHECK (normalization IN ('percentile', 'z-score')),
+  region VARCHAR(20) NOT NULL CHECK (region IN ('state', 'county', 'zip_code')),
+  materialized_on timestamptz
+);
+
+CREATE TABLE PropensityCompositeComponents (
+  composite VARCHAR(50) NOT NULL REFERENCES PropensityComposites(model) ON DELETE CASCADE,
+  model VARCHAR(50) NOT NULL REFERENCES PropensityModels(name),
+  weight NUMERIC(10, 4) NOT NULL CHECK (weight > 0),
+  PRIMARY KEY (composite, model),
+  CHECK (composite <> model)
+);
@@ -0,0 +1,3 @@
+-- Add the raw land use of a property's vendor record, kept so properties whose land use matched no
+-- land use of the taxonomy can be reclassified once the taxonomy covers it.
+ALTER TABLE Properties ADD COLUMN raw_land_use_type VARCHAR(100);
@@ -0,0 +1,4 @@
+-- Index properties and propensity scores by their last update so the readiness check reads their
+-- latest update without scanning either table
+CREATE INDEX idx_property_last_updated_on ON Properties(last_updated_on);
+CREATE INDEX idx_propensity_last_updated_on ON Propensities(last_updated_on);
@@ -77,7 +77,11 @@ impl AddressLine {
 
 impl Display for AddressLine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let street_name = format!("{} {}", self.street_name, self.street_suffix);
+        let street_name = if self.street_suffix.is_empty() {
+            self.street_name.clone()
+        } else {
+            format!("{} {}", self.street_name, self.street_suffix)
+        };
         let street_name = self.street_direction.decorate_street_name(street_name.as_str());
         write!(f, "{} {}", self.street_number, street_name)
     }
@@ -243,6 +247,954 @@ impl TryFrom<String> for ZipOrPostalCode {
     }
 }
 
+lazy_static::lazy_static! {
+    static ref RE_ZIP_PLUS_4: Regex = Regex::new(r##"^(\d{5})(-\d{4})?$"##).unwrap();
+    static ref RE_STATE_CODE: Regex = Regex::new(r##"^[A-Z]{2}$"##).unwrap();
+}
+
+const DIRECTIONALS: &[(&str, &str)] = &[
+    ("N", "N"),
+    ("NORTH", "N"),
+    ("S", "S"),
+    ("SOUTH", "S"),
+    ("E", "E"),
+    ("EAST", "E"),
+    ("W", "W"),
+    ("WEST", "W"),
+    ("NE", "NE"),
+    ("NORTHEAST", "NE"),
+    ("NW", "NW"),
+    ("NORTHWEST", "NW"),
+    ("SE", "SE"),
+    ("SOUTHEAST", "SE"),
+    ("SW", "SW"),
+    ("SOUTHWEST", "SW"),
+];
+
+/// Standard abbreviations of the street suffixes recognized as ending the street line of a free-text
+/// address without commas; any variant of these in `USPS_STREET_SUFFIXES` is recognized. Suffixes
+/// that double as common words of city names, such as LAKE or PARK, are left out.
+const FREE_TEXT_STREET_SUFFIXES: &[&str] = &[
+    "AVE", "BLVD", "CIR", "CRES", "CRST", "CT", "DR", "HWY", "KY", "LN", "LOOP", "PKWY", "PL", "RD", "ST", "TER",
+    "TRL", "WAY",
+];
+
+/// Standard abbreviations of the secondary unit designators recognized in a free-text address; any
+/// variant of these in `USPS_SECONDARY_DESIGNATORS` is recognized. Designators that double as common
+/// words of street names, such as PIER or FRONT, are left out.
+const FREE_TEXT_SECONDARY_DESIGNATORS: &[&str] = &["#", "APT", "BLDG", "LOT", "SLIP", "SPC", "STE", "TRLR", "UNIT"];
+
+fn lookup(table: &[(&str, &'static str)], token: &str) -> Option<&'static str> {
+    table.iter().find(|(rep, _)| *rep == token).map(|(_, standard)| *standard)
+}
+
+/// Looks up a token as `lookup` does, recognizing only the tokens standardized to one of `recognized`.
+fn lookup_recognized(table: &[(&str, &'static str)], recognized: &[&str], token: &str) -> Option<&'static str> {
+    lookup(table, token).filter(|standard| recognized.contains(standard))
+}
+
+/// Street suffixes, their common variants and abbreviations, and the USPS standard abbreviation of
+/// each, from USPS Publication 28, Appendix C1.
+const USPS_STREET_SUFFIXES: &[(&str, &str)] = &[
+    ("ALLEE", "ALY"),
+    ("ALLEY", "ALY"),
+    ("ALLY", "ALY"),
+    ("ALY", "ALY"),
+    ("ANEX", "ANX"),
+    ("ANNEX", "ANX"),
+    ("ANNX", "ANX"),
+    ("ANX", "ANX"),
+    ("ARC", "ARC"),
+    ("ARCADE", "ARC"),
+    ("AV", "AVE"),
+    ("AVE", "AVE"),
+    ("AVEN", "AVE"),
+    ("AVENU", "AVE"),
+    ("AVENUE", "AVE"),
+    ("AVN", "AVE"),
+    ("AVNUE", "AVE"),
+    ("BAYOO", "BYU"),
+    ("BAYOU", "BYU"),
+    ("BCH", "BCH"),
+    ("BEACH", "BCH"),
+    ("BEND", "BND"),
+    ("BG", "BG"),
+    ("BLF", "BLF"),
+    ("BLUF", "BLF"),
+    ("BLUFF", "BLF"),
+    ("BLVD", "BLVD"),
+    ("BND", "BND"),
+    ("BOT", "BTM"),
+    ("BOTTM", "BTM"),
+    ("BOTTOM", "BTM"),
+    ("BOUL", "BLVD"),
+    ("BOULEVARD", "BLVD"),
+    ("BOULV", "BLVD"),
+    ("BR", "BR"),
+    ("BRANCH", "BR"),
+    ("BRDGE", "BRG"),
+    ("BRG", "BRG"),
+    ("BRIDGE", "BRG"),
+    ("BRK", "BRK"),
+    ("BRNCH", "BR"),
+    ("BROOK", "BRK"),
+    ("BTM", "BTM"),
+    ("BURG", "BG"),
+    ("BYP", "BYP"),
+    ("BYPA", "BYP"),
+    ("BYPAS", "BYP"),
+    ("BYPASS", "BYP"),
+    ("BYPS", "BYP"),
+    ("BYU", "BYU"),
+    ("CAMP", "CP"),
+    ("CANYN", "CYN"),
+    ("CANYON", "CYN"),
+    ("CAPE", "CPE"),
+    ("CAUSEWAY", "CSWY"),
+    ("CAUSWA", "CSWY"),
+    ("CEN", "CTR"),
+    ("CENT", "CTR"),
+    ("CENTER", "CTR"),
+    ("CENTR", "CTR"),
+    ("CENTRE", "CTR"),
+    ("CIR", "CIR"),
+    ("CIRC", "CIR"),
+    ("CIRCL", "CIR"),
+    ("CIRCLE", "CIR"),
+    ("CLB", "CLB"),
+    ("CLF", "CLF"),
+    ("CLIFF", "CLF"),
+    ("CLUB", "CLB"),
+    ("CMN", "CMN"),
+    ("CMP", "CP"),
+    ("CNTER", "CTR"),
+    ("CNTR", "CTR"),
+    ("CNYN", "CYN"),
+    ("COMMON", "CMN"),
+    ("COR", "COR"),
+    ("CORNER", "COR"),
+    ("CORNERS", "CORS"),
+    ("CORS", "CORS"),
+    ("COURSE", "CRSE"),
+    ("COURT", "CT"),
+    ("COURTS", "CTS"),
+    ("COVE", "CV"),
+    ("CP", "CP"),
+    ("CPE", "CPE"),
+    ("CRCL", "CIR"),
+    ("CRCLE", "CIR"),
+    ("CREEK", "CRK"),
+    ("CRES", "CRES"),
+    ("CRESCENT", "CRES"),
+    ("CREST", "CRST"),
+    ("CRK", "CRK"),
+    ("CROSSING", "XING"),
+    ("CRSE", "CRSE"),
+    ("CRSENT", "CRES"),
+    ("CRSNT", "CRES"),
+    ("CRSSNG", "XING"),
+    ("CRST", "CRST"),
+    ("CSWY", "CSWY"),
+    ("CT", "CT"),
+    ("CTR", "CTR"),
+    ("CTS", "CTS"),
+    ("CURV", "CURV"),
+    ("CURVE", "CURV"),
+    ("CV", "CV"),
+    ("CYN", "CYN"),
+    ("DALE", "DL"),
+    ("DAM", "DM"),
+    ("DIV", "DV"),
+    ("DIVIDE", "DV"),
+    ("DL", "DL"),
+    ("DM", "DM"),
+    ("DR", "DR"),
+    ("DRIV", "DR"),
+    ("DRIVE", "DR"),
+    ("DRV", "DR"),
+    ("DV", "DV"),
+    ("DVD", "DV"),
+    ("EST", "EST"),
+    ("ESTATE", "EST"),
+    ("ESTATES", "ESTS"),
+    ("ESTS", "ESTS"),
+    ("EXP", "EXPY"),
+    ("EXPR", "EXPY"),
+    ("EXPRESS", "EXPY"),
+    ("EXPRESSWAY", "EXPY"),
+    ("EXPW", "EXPY"),
+    ("EXPY", "EXPY"),
+    ("EXT", "EXT"),
+    ("EXTENSION", "EXT"),
+    ("EXTN", "EXT"),
+    ("EXTNSN", "EXT"),
+    ("FALLS", "FLS"),
+    ("FERRY", "FRY"),
+    ("FIELD", "FLD"),
+    ("FIELDS", "FLDS"),
+    ("FLAT", "FLT"),
+    ("FLD", "FLD"),
+    ("FLDS", "FLDS"),
+    ("FLS", "FLS"),
+    ("FLT", "FLT"),
+    ("FORD", "FRD"),
+    ("FOREST", "FRST"),
+    ("FORESTS", "FRST"),
+    ("FORK", "FRK"),
+    ("FORT", "FT"),
+    ("FRD", "FRD"),
+    ("FREEWAY", "FWY"),
+    ("FREEWY", "FWY"),
+    ("FRK", "FRK"),
+    ("FRRY", "FRY"),
+    ("FRST", "FRST"),
+    ("FRT", "FT"),
+    ("FRWAY", "FWY"),
+    ("FRWY", "FWY"),
+    ("FRY", "FRY"),
+    ("FT", "FT"),
+    ("FWY", "FWY"),
+    ("GARDEN", "GDN"),
+    ("GARDENS", "GDNS"),
+    ("GARDN", "GDN"),
+    ("GATEWAY", "GTWY"),
+    ("GATEWY", "GTWY"),
+    ("GATWAY", "GTWY"),
+    ("GDN", "GDN"),
+    ("GDNS", "GDNS"),
+    ("GLEN", "GLN"),
+    ("GLN", "GLN"),
+    ("GRDEN", "GDN"),
+    ("GRDN", "GDN"),
+    ("GREEN", "GRN"),
+    ("GRN", "GRN"),
+    ("GROV", "GRV"),
+    ("GROVE", "GRV"),
+    ("GRV", "GRV"),
+    ("GTWAY", "GTWY"),
+    ("GTWY", "GTWY"),
+    ("HARB", "HBR"),
+    ("HARBOR", "HBR"),
+    ("HARBR", "HBR"),
+    ("HAVEN", "HVN"),
+    ("HBR", "HBR"),
+    ("HEIGHTS", "HTS"),
+    ("HIGHWAY", "HWY"),
+    ("HIGHWY", "HWY"),
+    ("HILL", "HL"),
+    ("HILLS", "HLS"),
+    ("HIWAY", "HWY"),
+    ("HIWY", "HWY"),
+    ("HL", "HL"),
+    ("HLLW", "HOLW"),
+    ("HLS", "HLS"),
+    ("HOLLOW", "HOLW"),
+    ("HOLLOWS", "HOLW"),
+    ("HOLW", "HOLW"),
+    ("HOLWS", "HOLW"),
+    ("HRBOR", "HBR"),
+    ("HT", "HTS"),
+    ("HTS", "HTS"),
+    ("HVN", "HVN"),
+    ("HWAY", "HWY"),
+    ("HWY", "HWY"),
+    ("IS", "IS"),
+    ("ISLAND", "IS"),
+    ("ISLND", "IS"),
+    ("JCT", "JCT"),
+    ("JCTION", "JCT"),
+    ("JCTN", "JCT"),
+    ("JUNCTION", "JCT"),
+    ("JUNCTN", "JCT"),
+    ("JUNCTON", "JCT"),
+    ("KEY", "KY"),
+    ("KNL", "KNL"),
+    ("KNOL", "KNL"),
+    ("KNOLL", "KNL"),
+    ("KY", "KY"),
+    ("LAKE", "LK"),
+    ("LAKES", "LKS"),
+    ("LANDING", "LNDG"),
+    ("LANE", "LN"),
+    ("LGT", "LGT"),
+    ("LIGHT", "LGT"),
+    ("LK", "LK"),
+    ("LKS", "LKS"),
+    ("LN", "LN"),
+    ("LNDG", "LNDG"),
+    ("LNDNG", "LNDG"),
+    ("LOOP", "LOOP"),
+    ("LOOPS", "LOOP"),
+    ("MALL", "MALL"),
+    ("MANOR", "MNR"),
+    ("MDW", "MDW"),
+    ("MDWS", "MDWS"),
+    ("MEADOW", "MDW"),
+    ("MEADOWS", "MDWS"),
+    ("MEDOWS", "MDWS"),
+    ("MILL", "ML"),
+    ("MILLS", "MLS"),
+    ("MISSION", "MSN"),
+    ("MISSN", "MSN"),
+    ("ML", "ML"),
+    ("MLS", "MLS"),
+    ("MNR", "MNR"),
+    ("MNT", "MT"),
+    ("MNTAIN", "MTN"),
+    ("MNTN", "MTN"),
+    ("MOTORWAY", "MTWY"),
+    ("MOUNT", "MT"),
+    ("MOUNTAIN", "MTN"),
+    ("MOUNTIN", "MTN"),
+    ("MSN", "MSN"),
+    ("MSSN", "MSN"),
+    ("MT", "MT"),
+    ("MTIN", "MTN"),
+    ("MTN", "MTN"),
+    ("MTWY", "MTWY"),
+    ("OPAS", "OPAS"),
+    ("OVAL", "OVAL"),
+    ("OVERPASS", "OPAS"),
+    ("OVL", "OVAL"),
+    ("PARK", "PARK"),
+    ("PARKWAY", "PKWY"),
+    ("PARKWY", "PKWY"),
+    ("PASS", "PASS"),
+    ("PATH", "PATH"),
+    ("PATHS", "PATH"),
+    ("PIKE", "PIKE"),
+    ("PIKES", "PIKE"),
+    ("PINE", "PNE"),
+    ("PINES", "PNES"),
+    ("PKWAY", "PKWY"),
+    ("PKWY", "PKWY"),
+    ("PKY", "PKWY"),
+    ("PL", "PL"),
+    ("PLACE", "PL"),
+    ("PLAIN", "PLN"),
+    ("PLAINS", "PLNS"),
+    ("PLAZA", "PLZ"),
+    ("PLN", "PLN"),
+    ("PLNS", "PLNS"),
+    ("PLZ", "PLZ"),
+    ("PLZA", "PLZ"),
+    ("PNE", "PNE"),
+    ("PNES", "PNES"),
+    ("POINT", "PT"),
+    ("POINTS", "PTS"),
+    ("PORT", "PRT"),
+    ("PR", "PR"),
+    ("PRAIRIE", "PR"),
+    ("PRK", "PARK"),
+    ("PRR", "PR"),
+    ("PRT", "PRT"),
+    ("PT", "PT"),
+    ("PTS", "PTS"),
+    ("RANCH", "RNCH"),
+    ("RANCHES", "RNCH"),
+    ("RD", "RD"),
+    ("RDG", "RDG"),
+    ("RDGE", "RDG"),
+    ("RIDGE", "RDG"),
+    ("RIV", "RIV"),
+    ("RIVER", "RIV"),
+    ("RIVR", "RIV"),
+    ("RNCH", "RNCH"),
+    ("RNCHS", "RNCH"),
+    ("ROAD", "RD"),
+    ("ROUTE", "RTE"),
+    ("ROW", "ROW"),
+    ("RTE", "RTE"),
+    ("RUN", "RUN"),
+    ("RVR", "RIV"),
+    ("SHOAR", "SHR"),
+    ("SHOARS", "SHRS"),
+    ("SHORE", "SHR"),
+    ("SHORES", "SHRS"),
+    ("SHR", "SHR"),
+    ("SHRS", "SHRS"),
+    ("SKWY", "SKWY"),
+    ("SKYWAY", "SKWY"),
+    ("SMT", "SMT"),
+    ("SPG", "SPG"),
+    ("SPGS", "SPGS"),
+    ("SPNG", "SPG"),
+    ("SPNGS", "SPGS"),
+    ("SPRING", "SPG"),
+    ("SPRINGS", "SPGS"),
+    ("SPRNG", "SPG"),
+    ("SPRNGS", "SPGS"),
+    ("SQ", "SQ"),
+    ("SQR", "SQ"),
+    ("SQRE", "SQ"),
+    ("SQU", "SQ"),
+    ("SQUARE", "SQ"),
+    ("ST", "ST"),
+    ("STA", "STA"),
+    ("STATION", "STA"),
+    ("STATN", "STA"),
+    ("STN", "STA"),
+    ("STR", "ST"),
+    ("STREAM", "STRM"),
+    ("STREET", "ST"),
+    ("STREME", "STRM"),
+    ("STRM", "STRM"),
+    ("STRT", "ST"),
+    ("SUMIT", "SMT"),
+    ("SUMITT", "SMT"),
+    ("SUMMIT", "SMT"),
+    ("TER", "TER"),
+    ("TERR", "TER"),
+    ("TERRACE", "TER"),
+    ("TPKE", "TPKE"),
+    ("TRACE", "TRCE"),
+    ("TRACES", "TRCE"),
+    ("TRACK", "TRAK"),
+    ("TRACKS", "TRAK"),
+    ("TRAIL", "TRL"),
+    ("TRAILS", "TRL"),
+    ("TRAK", "TRAK"),
+    ("TRCE", "TRCE"),
+    ("TRK", "TRAK"),
+    ("TRKS", "TRAK"),
+    ("TRL", "TRL"),
+    ("TRLS", "TRL"),
+    ("TRNPK", "TPKE"),
+    ("TUNEL", "TUNL"),
+    ("TUNL", "TUNL"),
+    ("TUNLS", "TUNL"),
+    ("TUNNEL", "TUNL"),
+    ("TUNNELS", "TUNL"),
+    ("TUNNL", "TUNL"),
+    ("TURNPIKE", "TPKE"),
+    ("TURNPK", "TPKE"),
+    ("UN", "UN"),
+    ("UNION", "UN"),
+    ("VALLEY", "VLY"),
+    ("VALLY", "VLY"),
+    ("VIEW", "VW"),
+    ("VILL", "VLG"),
+    ("VILLAG", "VLG"),
+    ("VILLAGE", "VLG"),
+    ("VILLE", "VL"),
+    ("VILLG", "VLG"),
+    ("VIS", "VIS"),
+    ("VIST", "VIS"),
+    ("VISTA", "VIS"),
+    ("VL", "VL"),
+    ("VLG", "VLG"),
+    ("VLLY", "VLY"),
+    ("VLY", "VLY"),
+    ("VST", "VIS"),
+    ("VSTA", "VIS"),
+    ("VW", "VW"),
+    ("WALK", "WALK"),
+    ("WALKS", "WALK"),
+    ("WALL", "WALL"),
+    ("WAY", "WAY"),
+    ("WELL", "WL"),
+    ("WELLS", "WLS"),
+    ("WL", "WL"),
+    ("WLS", "WLS"),
+    ("WY", "WAY"),
+    ("XING", "XING"),
+];
+
+/// Secondary unit designators and the USPS standard abbreviation of each, from USPS Publication 28,
+/// Appendix C2.
+const USPS_SECONDARY_DESIGNATORS: &[(&str, &str)] = &[
+    ("#", "#"),
+    ("APARTMENT", "APT"),
+    ("APT", "APT"),
+    ("BASEMENT", "BSMT"),
+    ("BLDG", "BLDG"),
+    ("BSMT", "BSMT"),
+    ("BUILDING", "BLDG"),
+    ("DEPARTMENT", "DEPT"),
+    ("DEPT", "DEPT"),
+    ("FL", "FL"),
+    ("FLOOR", "FL"),
+    ("FRNT", "FRNT"),
+    ("FRONT", "FRNT"),
+    ("HANGAR", "HNGR"),
+    ("HNGR", "HNGR"),
+    ("KEY", "KEY"),
+    ("LBBY", "LBBY"),
+    ("LOBBY", "LBBY"),
+    ("LOT", "LOT"),
+    ("LOWER", "LOWR"),
+    ("LOWR", "LOWR"),
+    ("OFC", "OFC"),
+    ("OFFICE", "OFC"),
+    ("PENTHOUSE", "PH"),
+    ("PH", "PH"),
+    ("PIER", "PIER"),
+    ("REAR", "REAR"),
+    ("RM", "RM"),
+    ("ROOM", "RM"),
+    ("SIDE", "SIDE"),
+    ("SLIP", "SLIP"),
+    ("SPACE", "SPC"),
+    ("SPC", "SPC"),
+    ("STE", "STE"),
+    ("STOP", "STOP"),
+    ("SUITE", "STE"),
+    ("TRAILER", "TRLR"),
+    ("TRLR", "TRLR"),
+    ("UNIT", "UNIT"),
+    ("UPPER", "UPPR"),
+    ("UPPR", "UPPR"),
+];
+
+lazy_static::lazy_static! {
+    static ref RE_NUMBERED_STREET: Regex = Regex::new(r##"^0*(\d+)(ST|ND|RD|TH)?$"##).unwrap();
+}
+
+/// Standardizes the components of US addresses to the forms of USPS Publication 28, so the same
+/// address is stored and matched alike whichever source it came from; e.g., AVENUE, AVE and AV are
+/// all standardized to AVE. Components without a standard form are uppercased, with periods and
+/// repeated whitespace removed.
+pub struct AddressStandardizer;
+
+impl AddressStandardizer {
+    pub fn address(address: &Address) -> Address {
+        Address {
+            address_line: Self::address_line(&address.address_line),
+            secondary_address_line: address.secondary_address_line.as_ref().map(Self::secondary_address_line),
+            city: City::new(Self::clean(address.city.as_ref())),
+            state_or_region: StateOrRegion::new(Self::clean(address.state_or_region.as_ref())),
+            ..address.clone()
+        }
+    }
+
+    pub fn address_line(line: &AddressLine) -> AddressLine {
+        AddressLine {
+            street_number: Self::clean(&line.street_number),
+            street_name: Self::street_name(&line.street_name),
+            street_suffix: Self::street_suffix(&line.street_suffix),
+            street_direction: Self::street_direction(&line.street_direction),
+        }
+    }
+
+    pub fn secondary_address_line(line: &SecondaryAddressLine) -> SecondaryAddressLine {
+        SecondaryAddressLine {
+            designator: Self::secondary_designator(&line.designator),
+            number: Self::clean(&line.number).trim_start_matches('#').to_string(),
+        }
+    }
+
+    pub fn street_direction(direction: &StreetDirection) -> StreetDirection {
+        match direction {
+            StreetDirection::None => StreetDirection::None,
+            StreetDirection::Prefix(prefix) => StreetDirection::Prefix(Self::directional(prefix)),
+            StreetDirection::Suffix(suffix) => StreetDirection::Suffix(Self::directional(suffix)),
+            StreetDirection::Both(prefix, suffix) => {
+                StreetDirection::Both(Self::directional(prefix), Self::directional(suffix))
+            }
+        }
+    }
+
+    /// Standardizes a street name, writing a numbered street as an ordinal; e.g., 2 as 2ND.
+    pub fn street_name(name: &str) -> String {
+        let name = Self::clean(name);
+        match RE_NUMBERED_STREET.captures(&name) {
+            Some(captures) => Self::ordinal(&captures[1]),
+            None => name,
+        }
+    }
+
+    pub fn street_suffix(suffix: &str) -> String {
+        let suffix = Self::clean(suffix);
+        lookup(USPS_STREET_SUFFIXES, &suffix)
+            .map(|s| s.to_string())
+            .unwrap_or(suffix)
+    }
+
+    /// Standardizes a directional, which may be spelled out in one or two words; e.g., NORTH EAST
+    /// and N.E. are both standardized to NE.
+    pub fn directional(direction: &str) -> String {
+        let direction = Self::clean(direction);
+        let compact = direction.replace(' ', "");
+        lookup(DIRECTIONALS, &compact).map(|d| d.to_string()).unwrap_or(direction)
+    }
+
+    pub fn secondary_designator(designator: &str) -> String {
+        let designator = Self::clean(designator);
+        lookup(USPS_SECONDARY_DESIGNATORS, &designator)
+            .map(|d| d.to_string())
+            .unwrap_or(designator)
+    }
+
+    /// Standardizes the words of a free-text address without parsing it, such as the beginning of an
+    /// address typed so far. The leading street number is kept and a directional or numbered street
+    /// name following it is standardized. Only the trailing words are abbreviated, as they are for an
+    /// address: a secondary unit designator (with or without its number), a post directional and
+    /// a street suffix, each as long as a street name word precedes it. The street name itself is
+    /// kept, so "123 Lake Forest Drive" is standardized to "123 LAKE FOREST DR" and
+    /// "123 North 2 Avenue" to "123 N 2ND AVE".
+    pub fn address_words(text: &str) -> String {
+        let cleaned = Self::clean(text);
+        let mut words: Vec<String> = cleaned.split(' ').map(|word| word.to_string()).collect();
+        if words.len() < 2 {
+            return cleaned;
+        }
+
+        let mut name_start = 1;
+        if let Some(direction) = lookup(DIRECTIONALS, &words[1]) {
+            words[1] = direction.to_string();
+            name_start = 2;
+        }
+
+        let mut end = words.len();
+        let designator_at = if name_start + 2 < end && lookup(USPS_SECONDARY_DESIGNATORS, &words[end - 2]).is_some() {
+            Some(end - 2)
+        } else if name_start + 1 < end && lookup(USPS_SECONDARY_DESIGNATORS, &words[end - 1]).is_some() {
+            Some(end - 1)
+        } else {
+            None
+        };
+        if let Some(pos) = designator_at {
+            words[pos] = Self::secondary_designator(&words[pos]);
+            end = pos;
+        }
+        if name_start + 1 < end {
+            if let Some(direction) = lookup(DIRECTIONALS, &words[end - 1]) {
+                words[end - 1] = direction.to_string();
+                end -= 1;
+            }
+        }
+        if name_start + 1 < end {
+            if let Some(suffix) = lookup(USPS_STREET_SUFFIXES, &words[end - 1]) {
+                words[end - 1] = suffix.to_string();
+            }
+        }
+        if name_start < words.len() {
+            words[name_start] = Self::street_name(&words[name_start]);
+        }
+
+        words.join(" ")
+    }
+
+    fn ordinal(number: &str) -> String {
+        let last_two = number.len().saturating_sub(2);
+        let indicator = match (&number[last_two..], number.chars().last()) {
+            ("11", _) | ("12", _) | ("13", _) => "TH",
+            (_, Some('1')) => "ST",
+            (_, Some('2')) => "ND",
+            (_, Some('3')) => "RD",
+            _ => "TH",
+        };
+        format!("{}{}", number, indicator)
+    }
+
+    fn clean(component: &str) -> String {
+        component
+            .to_uppercase()
+            .replace('.', "")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Address components recognized in a single-line, free-text address such as
+/// "2318 Martin Luther King Jr Way, Tacoma WA 98405". Only the street line is required; the city,
+/// state and zip code are recognized when present. The street line is standardized by the
+/// `AddressStandardizer`, as the addresses of loaded properties are, so the two can be compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedAddress {
+    pub address_line: AddressLine,
+    pub secondary_address_line: Option<SecondaryAddressLine>,
+    pub city: Option<City>,
+    pub state_or_region: Option<StateOrRegion>,
+    pub zip_or_postal_code: Option<ZipOrPostalCode>,
+}
+
+impl ParsedAddress {
+    pub fn parse(single_line: &str) -> Result<Self, DomainError> {
+        let normalized = single_line.to_uppercase().replace('.', "");
+        let mut segments: Vec<Vec<String>> = normalized
+            .split(',')
+            .map(|segment| segment.split_whitespace().map(|t| t.to_string()).collect::<Vec<_>>())
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        if segments.is_empty() {
+            return Err(DomainError::UnparseableAddress("address is empty".to_string()));
+        }
+
+        let zip_or_postal_code = Self::pop_trailing(&mut segments, |t| RE_ZIP_PLUS_4.is_match(t))
+            .and_then(|zip| RE_ZIP_PLUS_4.captures(&zip).map(|c| c[1].to_string()))
+            .map(ZipOrPostalCode::new)
+            .transpose()?;
+
+        // a trailing two-letter token is only taken as the state when the address evidently continues
+        // past the street line; otherwise "123 Main Ct" would lose its street suffix.
+        let state_or_region = if zip_or_postal_code.is_some() || 1 < segments.len() {
+            Self::pop_trailing(&mut segments, |t| RE_STATE_CODE.is_match(t)).map(StateOrRegion::new)
+        } else {
+            None
+        };
+
+        let (street_tokens, city_tokens) = Self::split_street_and_city(segments);
+        let city = if city_tokens.is_empty() {
+            None
+        } else {
+            Some(City::new(city_tokens.join(" ")))
+        };
+        let (address_line, secondary_address_line) = Self::parse_street_tokens(street_tokens)?;
+
+        Ok(Self {
+            address_line,
+            secondary_address_line,
+            city,
+            state_or_region,
+            zip_or_postal_code,
+        })
+    }
+
+    /// Whether the candidate address agrees with every component recognized in this address. The
+    /// candidate is standardized first, since properties loaded before standardization are stored in
+    /// their original forms.
+    pub fn is_exact_match(&self, candidate: &Address) -> bool {
+        let candidate = &AddressStandardizer::address(candidate);
+        let line = &self.address_line;
+        let other = &candidate.address_line;
+        let secondary_matches = match (&self.secondary_address_line, &candidate.secondary_address_line) {
+            (None, _) => true,
+            (Some(secondary), Some(other)) => secondary.number == other.number,
+            (Some(_), None) => false,
+        };
+
+        line.street_number == other.street_number
+            && line.street_name == other.street_name
+            && (line.street_suffix.is_empty() || line.street_suffix == other.street_suffix)
+            && (line.street_direction == StreetDirection::None || line.street_direction == other.street_direction)
+            && secondary_matches
+            && self.city.as_ref().map(|c| c == &candidate.city).unwrap_or(true)
+            && self
+                .state_or_region
+                .as_ref()
+                .map(|s| s == &candidate.state_or_region)
+                .unwrap_or(true)
+            && self
+                .zip_or_postal_code
+                .as_ref()
+                .map(|z| z == &candidate.zip_or_postal_code)
+                .unwrap_or(true)
+    }
+
+    /// Heuristic confidence, between 0 and 1, that the candidate is the address that was intended.
+    /// The street name carries the most weight and is compared by edit distance to tolerate typos;
+    /// components that were not recognized in this address earn partial credit.
+    pub fn match_confidence(&self, candidate: &Address) -> f64 {
+        let candidate = &AddressStandardizer::address(candidate);
+        if self.is_exact_match(candidate) {
+            return 1.0;
+        }
+
+        let line = &self.address_line;
+        let other = &candidate.address_line;
+        if line.street_number != other.street_number {
+            return 0.0;
+        }
+
+        let name = strsim::normalized_levenshtein(&line.street_name, &other.street_name);
+
+        let suffix = match line.street_suffix.as_str() {
+            "" => 0.5,
+            suffix if suffix == other.street_suffix => 1.0,
+            _ => 0.0,
+        };
+
+        let direction = match &line.street_direction {
+            StreetDirection::None => 0.5,
+            direction if direction == &other.street_direction => 1.0,
+            _ => 0.0,
+        };
+
+        let zip_matches = self
+            .zip_or_postal_code
+            .as_ref()
+            .map(|z| z == &candidate.zip_or_postal_code);
+        let city_matches = self.city.as_ref().map(|c| c == &candidate.city);
+        let locality = match (zip_matches, city_matches) {
+            (Some(true), _) => 1.0,
+            (_, Some(true)) => 0.75,
+            (None, None) => 0.5,
+            _ => 0.0,
+        };
+
+        let secondary = match (&self.secondary_address_line, &candidate.secondary_address_line) {
+            (None, None) => 1.0,
+            (None, Some(_)) => 0.5,
+            (Some(secondary), Some(other)) if secondary.number == other.number => 1.0,
+            _ => 0.0,
+        };
+
+        0.6 * name + 0.1 * suffix + 0.1 * direction + 0.15 * locality + 0.05 * secondary
+    }
+
+    fn pop_trailing(segments: &mut Vec<Vec<String>>, matches: impl Fn(&str) -> bool) -> Option<String> {
+        let last_segment = segments.last_mut()?;
+        if !last_segment.last().map(|t| matches(t)).unwrap_or(false) {
+            return None;
+        }
+
+        let token = last_segment.pop();
+        if last_segment.is_empty() {
+            segments.pop();
+        }
+        token
+    }
+
+    fn split_street_and_city(mut segments: Vec<Vec<String>>) -> (Vec<String>, Vec<String>) {
+        if segments.is_empty() {
+            return (vec![], vec![]);
+        }
+
+        let mut street_tokens = segments.remove(0);
+        if !segments.is_empty() {
+            // secondary address lines are sometimes separated from the street line by a comma.
+            while segments
+                .first()
+                .and_then(|segment| segment.first())
+                .map(|t| Self::is_secondary_designator(t))
+                .unwrap_or(false)
+            {
+                street_tokens.extend(segments.remove(0));
+            }
+            return (street_tokens, segments.into_iter().flatten().collect());
+        }
+
+        // without commas, the street line is taken to end at the last street suffix (and any following
+        // directional and secondary address) with any remaining words naming the city.
+        let suffix_position = street_tokens
+            .iter()
+            .enumerate()
+            .skip(2)
+            .rev()
+            .find(|(_, t)| lookup_recognized(USPS_STREET_SUFFIXES, FREE_TEXT_STREET_SUFFIXES, t).is_some())
+            .map(|(pos, _)| pos);
+
+        let mut street_end = match suffix_position {
+            Some(pos) => pos + 1,
+            None => return (street_tokens, vec![]),
+        };
+        if street_tokens
+            .get(street_end)
+            .and_then(|t| lookup(DIRECTIONALS, t))
+            .is_some()
+        {
+            street_end += 1;
+        }
+        if let Some(token) = street_tokens.get(street_end) {
+            if token.starts_with('#') && 1 < token.len() {
+                street_end += 1;
+            } else if Self::is_secondary_designator(token) {
+                street_end = (street_end + 2).min(street_tokens.len());
+            }
+        }
+
+        let city_tokens = street_tokens.split_off(street_end);
+        (street_tokens, city_tokens)
+    }
+
+    fn is_secondary_designator(token: &str) -> bool {
+        token.starts_with('#')
+            || lookup_recognized(USPS_SECONDARY_DESIGNATORS, FREE_TEXT_SECONDARY_DESIGNATORS, token).is_some()
+    }
+
+    fn parse_street_tokens(
+        mut tokens: Vec<String>,
+    ) -> Result<(AddressLine, Option<SecondaryAddressLine>), DomainError> {
+        let street_number = match tokens.first() {
+            Some(number) if number.starts_with(|c: char| c.is_ascii_digit()) => tokens.remove(0),
+            _ => return Err(DomainError::UnparseableAddress("missing street number".to_string())),
+        };
+
+        let mut secondary_address_line = None;
+        if let Some(pos) = tokens.iter().position(|t| Self::is_secondary_designator(t)) {
+            let mut secondary = tokens.split_off(pos);
+            let designator = secondary.remove(0);
+            secondary_address_line = match designator.strip_prefix('#') {
+                Some(number) if !number.is_empty() => Some(SecondaryAddressLine::new("#", number)),
+                _ => {
+                    let designator = lookup(USPS_SECONDARY_DESIGNATORS, &designator).unwrap_or("#");
+                    let number = secondary.join(" ").trim_start_matches('#').to_string();
+                    Some(SecondaryAddressLine::new(designator, number))
+                }
+            };
+        }
+
+        let pre_direction = match tokens.first().and_then(|t| lookup(DIRECTIONALS, t)) {
+            Some(direction) if 1 < tokens.len() => {
+                tokens.remove(0);
+                Some(direction)
+            }
+            _ => None,
+        };
+
+        let post_direction = match tokens.last().and_then(|t| lookup(DIRECTIONALS, t)) {
+            Some(direction) if 1 < tokens.len() => {
+                tokens.pop();
+                Some(direction)
+            }
+            _ => None,
+        };
+
+        let street_suffix = match tokens.last().and_then(|t| lookup(USPS_STREET_SUFFIXES, t)) {
+            Some(suffix) if 1 < tokens.len() => {
+                tokens.pop();
+                suffix
+            }
+            _ => "",
+        };
+
+        if tokens.is_empty() {
+            return Err(DomainError::UnparseableAddress("missing street name".to_string()));
+        }
+
+        let address_line = AddressLine::new(
+            street_number,
+            tokens.join(" "),
+            street_suffix,
+            StreetDirection::new(pre_direction, post_direction),
+        );
+        Ok((
+            AddressStandardizer::address_line(&address_line),
+            secondary_address_line.map(|line| AddressStandardizer::secondary_address_line(&line)),
+        ))
+    }
+}
+
+impl Display for ParsedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.address_line)?;
+        if let Some(ref secondary) = self.secondary_address_line {
+            write!(f, " {}", secondary)?;
+        }
+
+        let locality: Vec<String> = vec![
+            self.city.as_ref().map(|c| c.to_string()),
+            self.state_or_region
+                .as_ref()
+                .map(|s| s.to_string())
+                .into_iter()
+                .chain(self.zip_or_postal_code.as_ref().map(|z| z.to_string()))
+                .reduce(|state, zip| format!("{} {}", state, zip)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if !locality.is_empty() {
+            write!(f, ", {}", locality.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 //todo: good use for a declarative macro to initialize const.
 lazy_static::lazy_static! {
     pub static ref USA: CountryCode = CountryCode::new("USA", "United States of America").unwrap();
@@ -270,7 +1222,7 @@ impl CountryCode {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use claim::assert_ok;
+    use claim::{assert_err, assert_ok};
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -314,4 +1266,165 @@ mod tests {
         assert_eq!(format!("{}", address), "3112 Bonnie Brook Ln SW".to_string());
         Ok(())
     }
+
+    #[test]
+    fn test_parse_single_line_address() -> anyhow::Result<()> {
+        let actual = assert_ok!(ParsedAddress::parse("2318 Martin Luther King Jr. Way, Tacoma WA 98405"));
+        assert_eq!(
+            actual,
+            ParsedAddress {
+                address_line: AddressLine::new("2318", "MARTIN LUTHER KING JR", "WAY", StreetDirection::None),
+                secondary_address_line: None,
+                city: Some(City::new("TACOMA")),
+                state_or_region: Some(StateOrRegion::new("WA")),
+                zip_or_postal_code: Some(assert_ok!(ZipOrPostalCode::new("98405"))),
+            }
+        );
+
+        let actual = assert_ok!(ParsedAddress::parse(
+            "8426 75th Street Northeast Apt 3 Marysville WA 98270-1234"
+        ));
+        assert_eq!(
+            actual,
+            ParsedAddress {
+                address_line: AddressLine::new("8426", "75TH", "ST", StreetDirection::for_suffix("NE")),
+                secondary_address_line: Some(SecondaryAddressLine::new("APT", "3")),
+                city: Some(City::new("MARYSVILLE")),
+                state_or_region: Some(StateOrRegion::new("WA")),
+                zip_or_postal_code: Some(assert_ok!(ZipOrPostalCode::new("98270"))),
+            }
+        );
+        assert_eq!(
+            actual.to_string(),
+            "8426 75TH ST NE APT 3, MARYSVILLE, WA 98270".to_string()
+        );
+
+        let actual = assert_ok!(ParsedAddress::parse("583 Battery Ct #510N"));
+        assert_eq!(
+            actual,
+            ParsedAddress {
+                address_line: AddressLine::new("583", "BATTERY", "CT", StreetDirection::None),
+                secondary_address_line: Some(SecondaryAddressLine::new("#", "510N")),
+                city: None,
+                state_or_region: None,
+                zip_or_postal_code: None,
+            }
+        );
+
+        assert_err!(ParsedAddress::parse("Martin Luther King Jr Way, Tacoma WA"));
+        assert_err!(ParsedAddress::parse(" , "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_standardizer() -> anyhow::Result<()> {
+        for suffix in &["Avenue", "AVE", "av.", " Aven "] {
+            assert_eq!(AddressStandardizer::street_suffix(suffix), "AVE".to_string());
+        }
+        assert_eq!(AddressStandardizer::street_suffix("Crossing"), "XING".to_string());
+        assert_eq!(AddressStandardizer::street_suffix("Esplanade"), "ESPLANADE".to_string());
+
+        for direction in &["Northeast", "north east", "N.E.", "ne"] {
+            assert_eq!(AddressStandardizer::directional(direction), "NE".to_string());
+        }
+        assert_eq!(
+            AddressStandardizer::secondary_designator("Apartment"),
+            "APT".to_string()
+        );
+        assert_eq!(AddressStandardizer::secondary_designator("Penthouse"), "PH".to_string());
+
+        assert_eq!(AddressStandardizer::street_name("2"), "2ND".to_string());
+        assert_eq!(AddressStandardizer::street_name("2nd"), "2ND".to_string());
+        assert_eq!(AddressStandardizer::street_name("21"), "21ST".to_string());
+        assert_eq!(AddressStandardizer::street_name("112"), "112TH".to_string());
+        assert_eq!(AddressStandardizer::street_name("3rd"), "3RD".to_string());
+        assert_eq!(AddressStandardizer::street_name("Highway  2"), "HIGHWAY 2".to_string());
+
+        let address = Address::new_in_usa(
+            AddressLine::new(
+                "100",
+                "2",
+                "Avenue",
+                StreetDirection::new(Some("North East"), None::<String>),
+            ),
+            Some(SecondaryAddressLine::new("Suite", "#200")),
+            City::new("Seattle"),
+            StateOrRegion::new("wa"),
+            assert_ok!(ZipOrPostalCode::new("98101")),
+        );
+        let standardized = AddressStandardizer::address(&address);
+        assert_eq!(
+            standardized.to_string(),
+            "100 NE 2ND AVE STE 200, SEATTLE, WA 98101, USA".to_string()
+        );
+        assert_eq!(AddressStandardizer::address(&standardized), standardized);
+
+        let parsed = assert_ok!(ParsedAddress::parse("100 Northeast 2 Av Suite 200, Seattle WA 98101"));
+        assert!(parsed.is_exact_match(&standardized));
+
+        assert_eq!(
+            AddressStandardizer::address_words("123 North 2 Avenue"),
+            "123 N 2ND AVE"
+        );
+        assert_eq!(
+            AddressStandardizer::address_words("583 battery street suite"),
+            "583 BATTERY ST STE"
+        );
+        assert_eq!(AddressStandardizer::address_words("76 Cedar"), "76 CEDAR");
+        assert_eq!(
+            AddressStandardizer::address_words("123 Lake Forest Drive"),
+            "123 LAKE FOREST DR"
+        );
+        assert_eq!(
+            AddressStandardizer::address_words("456 Green Valley Road Apartment 2"),
+            "456 GREEN VALLEY RD APT 2"
+        );
+        assert_eq!(
+            AddressStandardizer::address_words("789 Mountain View Court Northeast"),
+            "789 MOUNTAIN VIEW CT NE"
+        );
+        assert_eq!(AddressStandardizer::address_words(""), "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_match_confidence() -> anyhow::Result<()> {
+        let candidate = Address::new_in_usa(
+            AddressLine::new("2318", "MARTIN LUTHER KING JR", "WAY", StreetDirection::None),
+            None,
+            City::new("TACOMA"),
+            StateOrRegion::new("WA"),
+            assert_ok!(ZipOrPostalCode::new("98405")),
+        );
+
+        let exact = assert_ok!(ParsedAddress::parse("2318 Martin Luther King Jr Way, Tacoma WA 98405"));
+        assert!(exact.is_exact_match(&candidate));
+        assert_eq!(exact.match_confidence(&candidate), 1.0);
+
+        let typo = assert_ok!(ParsedAddress::parse("2318 Martin Luther Kng Way, Tacoma WA 98405"));
+        assert!(!typo.is_exact_match(&candidate));
+        let confidence = typo.match_confidence(&candidate);
+        assert!(0.8 < confidence && confidence < 1.0, "confidence: {}", confidence);
+
+        let other_number = assert_ok!(ParsedAddress::parse("2320 Martin Luther King Jr Way, Tacoma WA 98405"));
+        assert_eq!(other_number.match_confidence(&candidate), 0.0);
+
+        // properties stored before standardization still match
+        let unstandardized = Address::new_in_usa(
+            AddressLine::new(
+                "100",
+                "2",
+                "Avenue",
+                StreetDirection::new(Some("North East"), None::<String>),
+            ),
+            Some(SecondaryAddressLine::new("Suite", "200")),
+            City::new("Seattle"),
+            StateOrRegion::new("WA"),
+            assert_ok!(ZipOrPostalCode::new("98101")),
+        );
+        let parsed = assert_ok!(ParsedAddress::parse("100 NE 2nd Ave Ste 200, Seattle WA 98101"));
+        assert!(parsed.is_exact_match(&unstandardized));
+        assert_eq!(parsed.match_confidence(&unstandardized), 1.0);
+        Ok(())
+    }
 }
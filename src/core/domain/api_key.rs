@@ -0,0 +1,245 @@
+use crate::core::domain::DomainError;
+use crate::core::CoreError;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+const API_KEY_TAG: &str = "ap_";
+const API_KEY_SECRET_LENGTH: usize = 40;
+const API_KEY_PREFIX_LENGTH: usize = 11;
+
+/// Access granted to an API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    /// Read propensity scores by APN.
+    #[serde(rename = "scores:read")]
+    ScoresRead,
+
+    /// Read the owner addresses associated with propensity scores.
+    #[serde(rename = "addresses:read")]
+    AddressesRead,
+
+    /// Perform server administration, such as flushing the response cache.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::ScoresRead, Scope::AddressesRead, Scope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ScoresRead => "scores:read",
+            Self::AddressesRead => "addresses:read",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = DomainError;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|s| s.as_str() == scope.trim())
+            .copied()
+            .ok_or_else(|| DomainError::UnrecognizedScope(scope.to_string()))
+    }
+}
+
+/// A registered API key. Only a hash of the key is stored, so the key itself is available only when
+/// it is created; the short key prefix is kept so operators can recognize keys in listings and logs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_on: DateTime<Utc>,
+    pub revoked_on: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_on.is_some()
+    }
+
+    /// Generates a new random API key; e.g., "ap_3kTMd9...".
+    pub fn generate_key() -> String {
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(API_KEY_SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        format!("{}{}", API_KEY_TAG, secret)
+    }
+
+    /// Hex encoded SHA-256 digest under which a key is stored. Keys are long and random, so a fast
+    /// digest is sufficient, and it lets a presented key be found by an indexed lookup.
+    pub fn hash_key(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    fn prefix_of(key: &str) -> String {
+        key.chars().take(API_KEY_PREFIX_LENGTH).collect()
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct ApiKeyRow {
+    id: i32,
+    name: String,
+    key_prefix: String,
+    scopes: Vec<String>,
+    created_on: DateTime<Utc>,
+    revoked_on: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ApiKeyRow> for ApiKey {
+    type Error = CoreError;
+
+    fn try_from(row: ApiKeyRow) -> Result<Self, Self::Error> {
+        let scopes = row
+            .scopes
+            .iter()
+            .map(|s| s.parse())
+            .collect::<Result<Vec<Scope>, DomainError>>()
+            .map_err(|err| CoreError::CoreError(err.into()))?;
+
+        Ok(Self {
+            id: row.id,
+            name: row.name,
+            key_prefix: row.key_prefix,
+            scopes,
+            created_on: row.created_on,
+            revoked_on: row.revoked_on,
+        })
+    }
+}
+
+pub struct ApiKeyRepository;
+
+impl ApiKeyRepository {
+    /// Registers a new API key with the given scopes, returning the record along with the key, which
+    /// cannot be recovered later.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn create(name: &str, scopes: &[Scope], pool: &PgPool) -> Result<(ApiKey, String), CoreError> {
+        let key = ApiKey::generate_key();
+        let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+
+        let row: ApiKeyRow = sqlx::query_as(
+            r##"
+            INSERT INTO ApiKeys (name, key_prefix, key_hash, scopes, created_on)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, name, key_prefix, scopes, created_on, revoked_on
+            "##,
+        )
+        .bind(name)
+        .bind(ApiKey::prefix_of(&key))
+        .bind(ApiKey::hash_key(&key))
+        .bind(scopes)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await
+        .context("Failed to perform a query to register an API key.")?;
+
+        Ok((ApiKey::try_from(row)?, key))
+    }
+
+    /// Finds the unrevoked API key matching a key presented by a client.
+    #[tracing::instrument(level = "debug", skip(key, pool))]
+    pub async fn find_active(key: &str, pool: &PgPool) -> Result<Option<ApiKey>, CoreError> {
+        let row: Option<ApiKeyRow> = sqlx::query_as(
+            r##"
+            SELECT id, name, key_prefix, scopes, created_on, revoked_on
+            FROM ApiKeys
+            WHERE key_hash = $1 AND revoked_on IS NULL
+            "##,
+        )
+        .bind(ApiKey::hash_key(key))
+        .fetch_optional(pool)
+        .await
+        .context("Failed to perform a query to authenticate an API key.")?;
+
+        row.map(ApiKey::try_from).transpose()
+    }
+
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<ApiKey>, CoreError> {
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(
+            r##"
+            SELECT id, name, key_prefix, scopes, created_on, revoked_on
+            FROM ApiKeys
+            ORDER BY created_on
+            "##,
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve API keys.")?;
+
+        rows.into_iter().map(ApiKey::try_from).collect()
+    }
+
+    /// Revokes the API keys registered under the name, returning the number of keys revoked.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn revoke(name: &str, pool: &PgPool) -> Result<u64, CoreError> {
+        let result = sqlx::query("UPDATE ApiKeys SET revoked_on = $1 WHERE name = $2 AND revoked_on IS NULL")
+            .bind(Utc::now())
+            .bind(name)
+            .execute(pool)
+            .await
+            .context("Failed to perform a query to revoke API keys.")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_scope_parsing() {
+        assert_eq!(assert_ok!("scores:read".parse::<Scope>()), Scope::ScoresRead);
+        assert_eq!(assert_ok!(" addresses:read".parse::<Scope>()), Scope::AddressesRead);
+        assert_eq!(assert_ok!("admin".parse::<Scope>()), Scope::Admin);
+        assert_err!("scores:write".parse::<Scope>());
+    }
+
+    #[test]
+    fn test_api_key_generation_and_hashing() {
+        let key = ApiKey::generate_key();
+        assert!(key.starts_with(API_KEY_TAG));
+        assert_eq!(key.len(), API_KEY_TAG.len() + API_KEY_SECRET_LENGTH);
+        assert_ne!(key, ApiKey::generate_key());
+
+        let hash = ApiKey::hash_key(&key);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, ApiKey::hash_key(&key));
+        assert_eq!(
+            ApiKey::hash_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(ApiKey::prefix_of(&key), &key[..API_KEY_PREFIX_LENGTH]);
+    }
+}
@@ -0,0 +1,531 @@
+use crate::core::domain::DomainError;
+use crate::core::CoreError;
+use anyhow::Context;
+use config::{Config, FileFormat};
+use regex::{RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Taxonomy built into the loader, used unless another is configured.
+const DEFAULT_TAXONOMY: &str = include_str!("../../../resources/land_use_taxonomy.yaml");
+
+/// Code stored for a property whose raw land use matched no land use of the taxonomy.
+const UNKNOWN_CODE: &str = "Unknown";
+
+/// Land use of a property, stored by its canonical code. Land uses of the taxonomy beyond the
+/// residential types known to the service are `Other`, holding their code; a property whose raw land
+/// use matched nothing is `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", from = "String")]
+pub enum LandUseType {
+    CondominiumUnit,
+    Duplex,
+    MobileOrManufacturedHome,
+    MultiFamilyDwellings,
+    PlannedUnitDevelopment,
+    Quadruplex,
+    RuralOrAgriculturalResidence,
+    SingleFamilyResidential,
+    Townhouse,
+    Triplex,
+    VacationResidence,
+    Other(String),
+    Unknown,
+}
+
+impl LandUseType {
+    /// The land use stored under the canonical code.
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "CondominiumUnit" => Self::CondominiumUnit,
+            "Duplex" => Self::Duplex,
+            "MobileOrManufacturedHome" => Self::MobileOrManufacturedHome,
+            "MultiFamilyDwellings" => Self::MultiFamilyDwellings,
+            "PlannedUnitDevelopment" => Self::PlannedUnitDevelopment,
+            "Quadruplex" => Self::Quadruplex,
+            "RuralOrAgriculturalResidence" => Self::RuralOrAgriculturalResidence,
+            "SingleFamilyResidential" => Self::SingleFamilyResidential,
+            "Townhouse" => Self::Townhouse,
+            "Triplex" => Self::Triplex,
+            "VacationResidence" => Self::VacationResidence,
+            UNKNOWN_CODE => Self::Unknown,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        match self {
+            Self::CondominiumUnit => "CondominiumUnit",
+            Self::Duplex => "Duplex",
+            Self::MobileOrManufacturedHome => "MobileOrManufacturedHome",
+            Self::MultiFamilyDwellings => "MultiFamilyDwellings",
+            Self::PlannedUnitDevelopment => "PlannedUnitDevelopment",
+            Self::Quadruplex => "Quadruplex",
+            Self::RuralOrAgriculturalResidence => "RuralOrAgriculturalResidence",
+            Self::SingleFamilyResidential => "SingleFamilyResidential",
+            Self::Townhouse => "Townhouse",
+            Self::Triplex => "Triplex",
+            Self::VacationResidence => "VacationResidence",
+            Self::Other(code) => code.as_str(),
+            Self::Unknown => UNKNOWN_CODE,
+        }
+    }
+}
+
+impl fmt::Display for LandUseType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl Into<String> for LandUseType {
+    fn into(self) -> String {
+        format!("{}", self)
+    }
+}
+
+impl From<String> for LandUseType {
+    fn from(code: String) -> Self {
+        Self::from_code(&code)
+    }
+}
+
+/// Broad category of land uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LandUseCategory {
+    Residential,
+    Commercial,
+    Industrial,
+    Land,
+}
+
+impl LandUseCategory {
+    pub const ALL: [LandUseCategory; 4] = [
+        LandUseCategory::Residential,
+        LandUseCategory::Commercial,
+        LandUseCategory::Industrial,
+        LandUseCategory::Land,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Residential => "residential",
+            Self::Commercial => "commercial",
+            Self::Industrial => "industrial",
+            Self::Land => "land",
+        }
+    }
+}
+
+impl fmt::Display for LandUseCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LandUseCategory {
+    type Err = DomainError;
+
+    fn from_str(category: &str) -> Result<Self, Self::Err> {
+        let category = category.trim().to_lowercase();
+        Self::ALL.iter().find(|c| c.as_str() == category).copied().ok_or_else(|| {
+            DomainError::InvalidLandUseTaxonomy(format!(
+                "{} is not one of residential, commercial, industrial or land",
+                category
+            ))
+        })
+    }
+}
+
+/// A land use of the taxonomy and the patterns matching its raw values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LandUseDefinition {
+    pub code: String,
+    pub name: String,
+    pub category: LandUseCategory,
+
+    /// Case-insensitive regular expressions matching raw land use values.
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TaxonomyFile {
+    land_uses: Vec<LandUseDefinition>,
+}
+
+/// Land uses, in order of precedence, and one matcher compiled from the patterns of every land use
+/// so a raw land use is matched against the whole taxonomy in a single pass.
+#[derive(Debug, Clone)]
+pub struct LandUseTaxonomy {
+    definitions: Vec<LandUseDefinition>,
+    land_uses: Vec<LandUseType>,
+    matcher: RegexSet,
+
+    /// Position of the land use of each pattern of the matcher.
+    pattern_land_uses: Vec<usize>,
+}
+
+impl LandUseTaxonomy {
+    pub fn new(definitions: Vec<LandUseDefinition>) -> Result<Self, DomainError> {
+        if definitions.is_empty() {
+            return Err(DomainError::InvalidLandUseTaxonomy(
+                "no land uses are defined".to_string(),
+            ));
+        }
+
+        let mut codes = HashSet::with_capacity(definitions.len());
+        let mut patterns = vec![];
+        let mut pattern_land_uses = vec![];
+        for (pos, definition) in definitions.iter().enumerate() {
+            if definition.code.trim().is_empty() || definition.code == UNKNOWN_CODE {
+                return Err(DomainError::InvalidLandUseTaxonomy(format!(
+                    "{:?} is not a valid land use code",
+                    definition.code
+                )));
+            }
+            if !codes.insert(definition.code.as_str()) {
+                return Err(DomainError::InvalidLandUseTaxonomy(format!(
+                    "land use {} is defined more than once",
+                    definition.code
+                )));
+            }
+            if definition.patterns.is_empty() {
+                return Err(DomainError::InvalidLandUseTaxonomy(format!(
+                    "land use {} has no patterns",
+                    definition.code
+                )));
+            }
+
+            for pattern in definition.patterns.iter() {
+                patterns.push(pattern.as_str());
+                pattern_land_uses.push(pos);
+            }
+        }
+
+        let matcher = RegexSetBuilder::new(&patterns)
+            .case_insensitive(true)
+            .build()
+            .map_err(|err| DomainError::InvalidLandUseTaxonomy(format!("invalid land use pattern: {}", err)))?;
+        let land_uses = definitions.iter().map(|d| LandUseType::from_code(&d.code)).collect();
+
+        Ok(Self { definitions, land_uses, matcher, pattern_land_uses })
+    }
+
+    /// Loads the taxonomy from a configuration file, such as `resources/land_use_taxonomy.yaml`.
+    pub fn from_path(path: &Path) -> Result<Self, CoreError> {
+        let config = Config::builder()
+            .add_source(config::File::from(path))
+            .build()
+            .with_context(|| format!("Failed to read land use taxonomy from {:?}", path))?;
+        Self::from_config(config)
+    }
+
+    fn from_config(config: Config) -> Result<Self, CoreError> {
+        let file: TaxonomyFile = config.try_into().context("Failed to parse land use taxonomy")?;
+        Self::new(file.land_uses).map_err(|err| CoreError::CoreError(err.into()))
+    }
+
+    /// Classifies a raw land use as the land use of highest precedence whose patterns match it,
+    /// noting the other land uses it also matches.
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn classify(&self, raw: &str) -> LandUseClassification {
+        let mut matched: Vec<usize> = self
+            .matcher
+            .matches(raw)
+            .into_iter()
+            .map(|pattern| self.pattern_land_uses[pattern])
+            .collect();
+        matched.sort_unstable();
+        matched.dedup();
+
+        let mut matches = matched.into_iter().map(|pos| self.land_uses[pos].clone());
+        match matches.next() {
+            Some(land_use) => LandUseClassification { land_use, also_matched: matches.collect() },
+            None => LandUseClassification {
+                land_use: LandUseType::Unknown,
+                also_matched: vec![],
+            },
+        }
+    }
+
+    /// The taxonomy's definition of the land use, if any.
+    pub fn definition(&self, land_use: &LandUseType) -> Option<&LandUseDefinition> {
+        self.land_uses
+            .iter()
+            .position(|lu| lu == land_use)
+            .map(|pos| &self.definitions[pos])
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &LandUseDefinition> {
+        self.definitions.iter()
+    }
+}
+
+impl Default for LandUseTaxonomy {
+    fn default() -> Self {
+        let config = Config::builder()
+            .add_source(config::File::from_str(DEFAULT_TAXONOMY, FileFormat::Yaml))
+            .build()
+            .expect("built-in land use taxonomy is readable");
+        Self::from_config(config).expect("built-in land use taxonomy is valid")
+    }
+}
+
+/// Land use a raw value is classified as, along with the other land uses of lower precedence whose
+/// patterns also match it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LandUseClassification {
+    pub land_use: LandUseType,
+    pub also_matched: Vec<LandUseType>,
+}
+
+impl LandUseClassification {
+    /// Whether the raw value matches more than one land use, so its classification rests on the
+    /// taxonomy's order.
+    pub fn is_ambiguous(&self) -> bool {
+        !self.also_matched.is_empty()
+    }
+}
+
+/// Classifies the raw land uses of a load by a taxonomy. Vendor files reuse a small vocabulary of
+/// raw values, so each distinct value is matched once and its classification cached, along with the
+/// number of times it was classified.
+#[derive(Debug)]
+pub struct LandUseClassifier {
+    taxonomy: LandUseTaxonomy,
+    classified: HashMap<String, (LandUseClassification, usize)>,
+}
+
+impl LandUseClassifier {
+    pub fn new(taxonomy: LandUseTaxonomy) -> Self {
+        Self { taxonomy, classified: HashMap::new() }
+    }
+
+    pub fn taxonomy(&self) -> &LandUseTaxonomy {
+        &self.taxonomy
+    }
+
+    pub fn classify(&mut self, raw: &str) -> LandUseType {
+        if let Some((classification, count)) = self.classified.get_mut(raw) {
+            *count += 1;
+            return classification.land_use.clone();
+        }
+
+        let classification = self.taxonomy.classify(raw);
+        if classification.is_ambiguous() {
+            tracing::debug!(%raw, ?classification, "raw land use matches more than one land use");
+        }
+        let land_use = classification.land_use.clone();
+        self.classified.insert(raw.to_string(), (classification, 1));
+        land_use
+    }
+
+    /// Raw values that matched no land use, with the number of times each was classified, most
+    /// common first.
+    pub fn unmatched(&self) -> Vec<(&str, usize)> {
+        let mut unmatched: Vec<(&str, usize)> = self
+            .classified
+            .iter()
+            .filter(|(_, (classification, _))| classification.land_use == LandUseType::Unknown)
+            .map(|(raw, (_, count))| (raw.as_str(), *count))
+            .collect();
+        unmatched.sort_by(|(a_raw, a_count), (b_raw, b_count)| b_count.cmp(a_count).then_with(|| a_raw.cmp(b_raw)));
+        unmatched
+    }
+
+    /// Raw values that matched more than one land use, with their classification and the number of
+    /// times each was classified, most common first.
+    pub fn ambiguous(&self) -> Vec<(&str, &LandUseClassification, usize)> {
+        let mut ambiguous: Vec<(&str, &LandUseClassification, usize)> = self
+            .classified
+            .iter()
+            .filter(|(_, (classification, _))| classification.is_ambiguous())
+            .map(|(raw, (classification, count))| (raw.as_str(), classification, *count))
+            .collect();
+        ambiguous
+            .sort_by(|(a_raw, _, a_count), (b_raw, _, b_count)| b_count.cmp(a_count).then_with(|| a_raw.cmp(b_raw)));
+        ambiguous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    fn definition(code: &str, category: LandUseCategory, patterns: &[&str]) -> LandUseDefinition {
+        LandUseDefinition {
+            code: code.to_string(),
+            name: code.to_string(),
+            category,
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_land_use_type_codes() {
+        assert_eq!(LandUseType::from_code("Duplex"), LandUseType::Duplex);
+        assert_eq!(LandUseType::from_code("Unknown"), LandUseType::Unknown);
+        assert_eq!(
+            LandUseType::from_code("VacantLand"),
+            LandUseType::Other("VacantLand".to_string())
+        );
+
+        for land_use in vec![
+            LandUseType::SingleFamilyResidential,
+            LandUseType::Other("RetailStore".to_string()),
+            LandUseType::Unknown,
+        ] {
+            let code: String = land_use.clone().into();
+            assert_eq!(LandUseType::from(code), land_use);
+        }
+
+        let json = assert_ok!(serde_json::to_string(&LandUseType::Other("RetailStore".to_string())));
+        assert_eq!(json, r#""RetailStore""#);
+        assert_eq!(
+            assert_ok!(serde_json::from_str::<LandUseType>(r#""Townhouse""#)),
+            LandUseType::Townhouse
+        );
+    }
+
+    #[test]
+    fn test_default_taxonomy_classification() {
+        let taxonomy = LandUseTaxonomy::default();
+        assert_eq!(
+            taxonomy.classify("Single Family Residential").land_use,
+            LandUseType::SingleFamilyResidential
+        );
+        assert_eq!(
+            taxonomy.classify("CONDOMINIUM UNIT").land_use,
+            LandUseType::CondominiumUnit
+        );
+        assert_eq!(
+            taxonomy.classify("Multi-Family Dwellings").land_use,
+            LandUseType::MultiFamilyDwellings
+        );
+        assert_eq!(
+            taxonomy.classify("Rural or Agricultural Residence").land_use,
+            LandUseType::RuralOrAgriculturalResidence
+        );
+        assert_eq!(
+            taxonomy.classify("Vacant Land (NEC)").land_use,
+            LandUseType::Other("VacantLand".to_string())
+        );
+        assert_eq!(taxonomy.classify("Bowling Alley").land_use, LandUseType::Unknown);
+
+        let vacant = assert_ok!(taxonomy
+            .definition(&LandUseType::Other("VacantLand".to_string()))
+            .ok_or("no definition"));
+        assert_eq!(vacant.category, LandUseCategory::Land);
+        assert_eq!(
+            taxonomy.definition(&LandUseType::Townhouse).map(|d| d.category),
+            Some(LandUseCategory::Residential)
+        );
+        assert_eq!(taxonomy.definition(&LandUseType::Unknown), None);
+    }
+
+    #[test]
+    fn test_taxonomy_validation() {
+        assert_err!(LandUseTaxonomy::new(vec![]));
+        assert_err!(LandUseTaxonomy::new(vec![definition(
+            "Unknown",
+            LandUseCategory::Land,
+            &["unknown"]
+        )]));
+        assert_err!(LandUseTaxonomy::new(vec![definition(
+            "Retail",
+            LandUseCategory::Commercial,
+            &[]
+        )]));
+        assert_err!(LandUseTaxonomy::new(vec![definition(
+            "Retail",
+            LandUseCategory::Commercial,
+            &["retail("]
+        )]));
+        assert_err!(LandUseTaxonomy::new(vec![
+            definition("Retail", LandUseCategory::Commercial, &["retail"]),
+            definition("Retail", LandUseCategory::Commercial, &["store"]),
+        ]));
+
+        let taxonomy = assert_ok!(LandUseTaxonomy::new(vec![
+            definition("Marina", LandUseCategory::Commercial, &[r"marina|boat\s*slip"]),
+            definition("Duplex", LandUseCategory::Residential, &["duplex"]),
+        ]));
+        assert_eq!(
+            taxonomy.classify("Boat Slip").land_use,
+            LandUseType::Other("Marina".to_string())
+        );
+        assert_eq!(taxonomy.classify("duplex").land_use, LandUseType::Duplex);
+        assert_eq!(taxonomy.classify("townhouse").land_use, LandUseType::Unknown);
+
+        assert_eq!(
+            assert_ok!(" Commercial".parse::<LandUseCategory>()),
+            LandUseCategory::Commercial
+        );
+        assert_err!("institutional".parse::<LandUseCategory>());
+    }
+
+    #[test]
+    fn test_ambiguous_classification() {
+        let taxonomy = assert_ok!(LandUseTaxonomy::new(vec![
+            definition("Duplex", LandUseCategory::Residential, &["duplex"]),
+            definition(
+                "Quadruplex",
+                LandUseCategory::Residential,
+                &["quadruplex", r"four\s*plex"]
+            ),
+            definition("Triplex", LandUseCategory::Residential, &["triplex", "plex"]),
+        ]));
+
+        assert_eq!(
+            taxonomy.classify("Duplex or Quadruplex"),
+            LandUseClassification {
+                land_use: LandUseType::Duplex,
+                also_matched: vec![LandUseType::Quadruplex, LandUseType::Triplex],
+            }
+        );
+        let fourplex = taxonomy.classify("Four Plex");
+        assert_eq!(fourplex.land_use, LandUseType::Quadruplex);
+        assert!(fourplex.is_ambiguous());
+        assert!(!taxonomy.classify("Triplex").is_ambiguous());
+    }
+
+    #[test]
+    fn test_classifier_caches_and_reports() {
+        let mut classifier = LandUseClassifier::new(LandUseTaxonomy::default());
+        for raw in &[
+            "Single Family Residential",
+            "Bowling Alley",
+            "Single Family Residential",
+            "Mini Golf",
+        ] {
+            classifier.classify(raw);
+        }
+        assert_eq!(classifier.classify("Bowling Alley"), LandUseType::Unknown);
+        assert_eq!(
+            classifier.classify("Single Family Residential"),
+            LandUseType::SingleFamilyResidential
+        );
+
+        assert_eq!(classifier.unmatched(), vec![("Bowling Alley", 2), ("Mini Golf", 1)]);
+        assert!(classifier.ambiguous().is_empty());
+
+        assert_eq!(
+            classifier.classify("Retail Store / Office"),
+            LandUseType::Other("RetailStore".to_string())
+        );
+        let ambiguous = classifier.ambiguous();
+        assert_eq!(ambiguous.len(), 1);
+        assert_eq!(ambiguous[0].0, "Retail Store / Office");
+        assert_eq!(
+            ambiguous[0].1.also_matched,
+            vec![LandUseType::Other("OfficeBuilding".to_string())]
+        );
+        assert_eq!(ambiguous[0].2, 1);
+    }
+}
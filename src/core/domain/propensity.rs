@@ -1,18 +1,21 @@
-use crate::core::domain::property::Property;
-use crate::core::domain::{
-    Address, AddressLine, AssessorParcelNumber, City, SecondaryAddressLine, StateOrRegion, StreetDirection,
-    ZipOrPostalCode,
-};
-use crate::core::CoreError;
+use crate::core::domain::propensity_model::ScoreDirection;
+use crate::core::domain::property::{Property, PropertyAttributes, PropertyFields, StoredAddress};
+use crate::core::domain::{Address, AssessorParcelNumber, DomainError, ParsedAddress, ZipOrPostalCode};
+use crate::core::{CoreError, TableFreshness};
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
+use std::str::FromStr;
 use validator::Validate;
 
+/// A score of any registered model. Scores are stored as `SMALLINT`; the range valid for a given
+/// model is its `ScoreScale`, enforced when scores are loaded.
 #[derive(Debug, Validate, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PropensityScore {
-    #[validate(range(min = 0))]
+    #[validate(range(max = 32767))]
     pub score: u16,
 }
 
@@ -31,6 +34,9 @@ pub struct PropertyPropensityScore {
 
     pub apn: AssessorParcelNumber,
 
+    /// Name of the registered model that produced the score.
+    pub model: String,
+
     #[validate]
     pub zip_or_postal_code: Option<ZipOrPostalCode>,
 
@@ -40,11 +46,12 @@ pub struct PropertyPropensityScore {
 
 impl PropertyPropensityScore {
     pub fn new(
-        score: PropensityScore, apn: &AssessorParcelNumber, zip_or_postal_code: &Option<ZipOrPostalCode>,
+        score: PropensityScore, model: &str, apn: &AssessorParcelNumber, zip_or_postal_code: &Option<ZipOrPostalCode>,
     ) -> Result<Self, CoreError> {
         let property_score = Self {
             id: None,
             apn: apn.clone(),
+            model: model.to_string(),
             zip_or_postal_code: zip_or_postal_code.clone(),
             score,
         };
@@ -52,10 +59,11 @@ impl PropertyPropensityScore {
         Ok(property_score)
     }
 
-    pub fn for_property(property: &Property, score: PropensityScore) -> Result<Self, CoreError> {
+    pub fn for_property(property: &Property, model: &str, score: PropensityScore) -> Result<Self, CoreError> {
         let property_score = Self {
             id: None,
             apn: property.apn.clone(),
+            model: model.to_string(),
             zip_or_postal_code: Some(property.address.zip_or_postal_code.clone()),
             score,
         };
@@ -64,23 +72,302 @@ impl PropertyPropensityScore {
     }
 }
 
+/// A version of a property's propensity score, in effect from a date until superseded by a version
+/// with a later effective date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropensityScoreVersion {
+    pub apn: AssessorParcelNumber,
+    pub model: String,
+    pub score: PropensityScore,
+    pub zip_or_postal_code: Option<ZipOrPostalCode>,
+    pub effective_on: NaiveDate,
+
+    /// Source or model that produced the score.
+    pub source: String,
+
+    pub loaded_on: DateTime<Utc>,
+}
+
+impl PropensityScoreVersion {
+    pub fn new(
+        record: &PropertyPropensityScore, effective_on: NaiveDate, source: impl Into<String>, loaded_on: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            apn: record.apn.clone(),
+            model: record.model.clone(),
+            score: record.score,
+            zip_or_postal_code: record.zip_or_postal_code.clone(),
+            effective_on,
+            source: source.into(),
+            loaded_on,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PropensityScoreVersionRow {
+    apn: String,
+    model: String,
+    score: i16,
+    zip_or_postal_code: Option<String>,
+    effective_on: NaiveDate,
+    source: String,
+    loaded_on: DateTime<Utc>,
+}
+
+impl PropensityScoreVersionRow {
+    fn into_version(self) -> Result<PropensityScoreVersion, CoreError> {
+        Ok(PropensityScoreVersion {
+            apn: AssessorParcelNumber::new(self.apn)?,
+            model: self.model,
+            score: PropensityScore::new(self.score as u16)?,
+            zip_or_postal_code: self.zip_or_postal_code.map(ZipOrPostalCode::new).transpose()?,
+            effective_on: self.effective_on,
+            source: self.source,
+            loaded_on: self.loaded_on,
+        })
+    }
+}
+
 pub struct PropertyPropensityScoreRepository;
 
-type ScoreAddress = (PropertyPropensityScore, Option<Address>);
+pub type ScoreAddress = (PropertyPropensityScore, Option<Address>);
+
+/// A property whose address may match an address lookup, along with its propensity score if one has
+/// been loaded.
+pub type AddressCandidate = (AssessorParcelNumber, Address, Option<PropensityScore>);
+
+#[derive(Debug)]
+struct AddressCandidateRow {
+    apn: String,
+    score: Option<i16>,
+    address: StoredAddress,
+}
+
+impl<'r> FromRow<'r, PgRow> for AddressCandidateRow {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            apn: row.try_get("apn")?,
+            score: row.try_get("score")?,
+            address: StoredAddress::from_row(row)?,
+        })
+    }
+}
+
+impl AddressCandidateRow {
+    fn into_candidate(self) -> Result<AddressCandidate, CoreError> {
+        let score = self.score.map(|s| PropensityScore::new(s as u16)).transpose()?;
+        Ok((
+            AssessorParcelNumber::new(self.apn)?,
+            self.address.into_address()?,
+            score,
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct ScoreAddressRow {
+    id: i32,
+    apn: String,
+    model: String,
+    score: i16,
+    p_zip_or_postal_code: Option<String>,
+    address: Option<StoredAddress>,
+}
+
+impl<'r> FromRow<'r, PgRow> for ScoreAddressRow {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            apn: row.try_get("apn")?,
+            model: row.try_get("model")?,
+            score: row.try_get("score")?,
+            p_zip_or_postal_code: row.try_get("p_zip_or_postal_code")?,
+            // every property has a street number, so a null one means a left join found no property
+            address: match row.try_get::<Option<String>, _>("street_number")? {
+                Some(_) => Some(StoredAddress::from_row(row)?),
+                None => None,
+            },
+        })
+    }
+}
+
+impl ScoreAddressRow {
+    fn into_score_address(self) -> Result<ScoreAddress, CoreError> {
+        let p_zip_or_postal_code = self.p_zip_or_postal_code.map(ZipOrPostalCode::new).transpose()?;
+        let score = PropertyPropensityScore {
+            id: Some(self.id),
+            apn: AssessorParcelNumber::new(self.apn)?,
+            model: self.model,
+            zip_or_postal_code: p_zip_or_postal_code,
+            score: PropensityScore::new(self.score as u16)?,
+        };
+        Ok((score, self.address.map(StoredAddress::into_address).transpose()?))
+    }
+}
+
+/// A scored property with its address and the property attributes selected for it.
+pub type ScoredProperty = (PropertyPropensityScore, Option<Address>, PropertyAttributes);
+
+fn decode_scored_property(row: &PgRow, fields: &PropertyFields) -> Result<ScoredProperty, CoreError> {
+    let (score, address) = ScoreAddressRow::from_row(row)?.into_score_address()?;
+    let attributes = match address {
+        Some(_) => PropertyAttributes::from_row(row, fields)?,
+        None => PropertyAttributes::default(),
+    };
+    Ok((score, address, attributes))
+}
+
+/// Standing of a scored property among the scored properties in its zip code.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ZipRanking {
+    /// Position by descending score, where properties with equal scores share a rank.
+    pub zip_rank: u32,
+
+    /// Percentage of the other scored properties in the zip code with lower scores.
+    pub zip_percentile: f64,
+}
+
+/// A scored property with its address and ranking within its zip code.
+pub type RankedScoreAddress = (PropertyPropensityScore, Option<Address>, ZipRanking);
+
+#[derive(Debug)]
+struct RankedScoreAddressRow {
+    score_address: ScoreAddressRow,
+    zip_rank: i64,
+    zip_percentile: f64,
+}
+
+impl<'r> FromRow<'r, PgRow> for RankedScoreAddressRow {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            score_address: ScoreAddressRow::from_row(row)?,
+            zip_rank: row.try_get("zip_rank")?,
+            zip_percentile: row.try_get("zip_percentile")?,
+        })
+    }
+}
+
+impl RankedScoreAddressRow {
+    fn into_ranked_score_address(self) -> Result<RankedScoreAddress, CoreError> {
+        let ranking = ZipRanking {
+            zip_rank: self.zip_rank as u32,
+            zip_percentile: self.zip_percentile,
+        };
+        let (score, address) = self.score_address.into_score_address()?;
+        Ok((score, address, ranking))
+    }
+}
+
+/// Zip code by which a zip code search selects propensity scores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ZipCodeSource {
+    /// The zip code supplied by the vendor with the propensity score.
+    Propensity,
+
+    /// The zip code of the scored property, so scores whose property is not loaded never match.
+    Property,
+}
+
+impl Default for ZipCodeSource {
+    fn default() -> Self {
+        Self::Propensity
+    }
+}
+
+impl ZipCodeSource {
+    pub const ALL: [ZipCodeSource; 2] = [ZipCodeSource::Propensity, ZipCodeSource::Property];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Propensity => "propensity",
+            Self::Property => "property",
+        }
+    }
+
+    fn column(&self) -> &'static str {
+        match self {
+            Self::Propensity => "Propensities.zip_or_postal_code",
+            Self::Property => "Properties.zip_or_postal_code",
+        }
+    }
+}
+
+impl FromStr for ZipCodeSource {
+    type Err = DomainError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let source = source.trim().to_lowercase();
+        Self::ALL
+            .iter()
+            .find(|s| s.as_str() == source)
+            .copied()
+            .ok_or_else(|| DomainError::UnrecognizedZipCodeSource(source))
+    }
+}
+
+/// How a zip code search selects propensity scores and joins them to their properties.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ZipSearchOptions {
+    pub zip_source: ZipCodeSource,
+
+    /// Whether scores whose property is not loaded are included, without an address or property
+    /// fields. The loader keeps such scores in case their property is loaded later.
+    pub include_unmatched: bool,
+
+    /// Whether the model's higher or lower scores are listed first.
+    pub direction: ScoreDirection,
+
+    /// Inclusive range of scores searched, such as a tier band of the model; every score if `None`.
+    pub score_band: Option<(u16, u16)>,
+}
+
+/// Query for the scored properties in a state, and optionally a county within it, selecting the
+/// requested property attributes. The SQL is built once so the rows streamed from it may borrow it.
+#[derive(Debug, Clone)]
+pub struct RegionScoresQuery {
+    fields: PropertyFields,
+    sql: String,
+}
+
+impl RegionScoresQuery {
+    pub fn new(fields: PropertyFields) -> Self {
+        let sql = format!(
+            r##"
+            SELECT Propensities.id, Propensities.apn, Propensities.model, Propensities.score, Propensities.zip_or_postal_code as p_zip_or_postal_code,
+                Properties.street_number, Properties.street_pre_direction, Properties.street_name,
+                Properties.street_suffix, Properties.street_post_direction, Properties.secondary_designator,
+                Properties.secondary_number, Properties.city, Properties.state_or_region, Properties.zip_or_postal_code
+                {}
+            FROM Propensities
+            INNER JOIN Properties
+            ON Propensities.apn = Properties.apn
+            WHERE Properties.state_or_region = $1
+                AND ($2::TEXT IS NULL OR Properties.admin_division = $2)
+                AND Propensities.model = $3
+            ORDER BY Propensities.apn
+            LIMIT $4
+            "##,
+            fields.select_list()
+        );
+        Self { fields, sql }
+    }
+}
 
 impl PropertyPropensityScoreRepository {
     #[tracing::instrument(level = "info", skip(pool))]
     pub async fn find_for_apn(
-        apn: &AssessorParcelNumber, pool: &PgPool,
+        apn: &AssessorParcelNumber, model: &str, pool: &PgPool,
     ) -> Result<Option<PropertyPropensityScore>, CoreError> {
         sqlx::query!(
             r##"
-            SELECT id, apn, score, zip_or_postal_code
+            SELECT id, apn, model, score, zip_or_postal_code
             FROM propensities
-            WHERE apn = $1
+            WHERE apn = $1 AND model = $2
             LIMIT 1
             "##,
-            apn.as_ref()
+            apn.as_ref(),
+            model
         )
         .fetch_optional(pool)
         .await
@@ -90,6 +377,7 @@ impl PropertyPropensityScoreRepository {
             Ok(PropertyPropensityScore {
                 id: Some(row.id),
                 apn: AssessorParcelNumber::new(row.apn)?,
+                model: row.model,
                 zip_or_postal_code,
                 score: PropensityScore::new(row.score as u16)?,
             })
@@ -97,63 +385,255 @@ impl PropertyPropensityScoreRepository {
         .transpose()
     }
 
+    /// Finds the properties with the top propensity scores in a zip code, selecting only the
+    /// requested property attributes. Scores whose property is not loaded are found, without an
+    /// address or attributes, only if the options include them.
     #[tracing::instrument(level = "info", skip(pool))]
     pub async fn find_address_scores_for_zip_code(
-        zip_code: &ZipOrPostalCode, limit: u16, pool: &PgPool,
+        zip_code: &ZipOrPostalCode, model: &str, limit: u16, fields: &PropertyFields, options: ZipSearchOptions,
+        pool: &PgPool,
+    ) -> Result<Vec<ScoredProperty>, CoreError> {
+        let join = if options.include_unmatched { "LEFT JOIN" } else { "INNER JOIN" };
+        let sql = format!(
+            r##"
+            SELECT Propensities.id, Propensities.apn, Propensities.model, Propensities.score, Propensities.zip_or_postal_code as p_zip_or_postal_code,
+                Properties.street_number, Properties.street_pre_direction, Properties.street_name,
+                Properties.street_suffix, Properties.street_post_direction, Properties.secondary_designator,
+                Properties.secondary_number, Properties.city, Properties.state_or_region, Properties.zip_or_postal_code
+                {}
+            FROM Propensities
+            {} Properties
+            ON Propensities.apn = Properties.apn
+            WHERE {} = $1
+                AND Propensities.model = $2
+                AND ($4::SMALLINT IS NULL OR Propensities.score BETWEEN $4 AND $5)
+            ORDER BY Propensities.score {}
+            LIMIT $3
+            "##,
+            fields.select_list(),
+            join,
+            options.zip_source.column(),
+            options.direction.best_first(),
+        );
+        let (band_min, band_max) = options
+            .score_band
+            .map(|(min, max)| (Some(min as i16), Some(max as i16)))
+            .unwrap_or((None, None));
+        let records = sqlx::query(&sql)
+            .bind(zip_code.as_ref())
+            .bind(model)
+            .bind(limit as i64)
+            .bind(band_min)
+            .bind(band_max)
+            .fetch_all(pool)
+            .await
+            .context("Failed to perform a query to retrieve top propensity scores for a zip code.")?;
+
+        records
+            .iter()
+            .map(|record| decode_scored_property(record, fields))
+            .collect()
+    }
+
+    /// Counts the properties in a state, and optionally a county within it, scored by a model.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn count_for_region(
+        state: &str, county: Option<&str>, model: &str, pool: &PgPool,
+    ) -> Result<i64, CoreError> {
+        let (nr_scores,): (i64,) = sqlx::query_as(
+            r##"
+            SELECT COUNT(*)
+            FROM Propensities
+            INNER JOIN Properties
+            ON Propensities.apn = Properties.apn
+            WHERE Properties.state_or_region = $1
+                AND ($2::TEXT IS NULL OR Properties.admin_division = $2)
+                AND Propensities.model = $3
+            "##,
+        )
+        .bind(state)
+        .bind(county)
+        .bind(model)
+        .fetch_one(pool)
+        .await
+        .context("Failed to perform a query to count propensity scores in a region.")?;
+
+        Ok(nr_scores)
+    }
+
+    /// Streams up to `limit` properties in a state, and optionally a county within it, scored by a
+    /// model in APN order. Rows are decoded as they arrive from the database, so callers can process regions of any
+    /// size in constant memory.
+    pub fn stream_for_region<'a>(
+        query: &'a RegionScoresQuery, state: &'a str, county: Option<&'a str>, model: &'a str, limit: i64,
+        pool: &'a PgPool,
+    ) -> impl Stream<Item = Result<ScoredProperty, CoreError>> + 'a {
+        sqlx::query(&query.sql)
+            .bind(state)
+            .bind(county)
+            .bind(model)
+            .bind(limit)
+            .fetch(pool)
+            .map(move |record| decode_scored_property(&record?, &query.fields))
+    }
+
+    /// Finds the `n` properties in a state scored best by a model, optionally narrowed to a county
+    /// and city, each ranked within its zip code. Rankings are computed over every scored property in the zip
+    /// code within the state, including those outside the county or city, but only the zip codes
+    /// intersecting the county or city are ranked.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_top_scores_for_region(
+        state: &str, county: Option<&str>, city: Option<&str>, model: &str, direction: ScoreDirection, n: u16,
+        pool: &PgPool,
+    ) -> Result<Vec<RankedScoreAddress>, CoreError> {
+        let sql = format!(
+            r##"
+            WITH Ranked AS (
+                SELECT Propensities.id, Propensities.apn, Propensities.model, Propensities.score, Propensities.zip_or_postal_code as p_zip_or_postal_code,
+                    Properties.street_number, Properties.street_pre_direction, Properties.street_name,
+                    Properties.street_suffix, Properties.street_post_direction, Properties.secondary_designator,
+                    Properties.secondary_number, Properties.city, Properties.state_or_region, Properties.zip_or_postal_code,
+                    Properties.admin_division,
+                    RANK() OVER (
+                        PARTITION BY Properties.zip_or_postal_code ORDER BY Propensities.score {best}
+                    ) AS zip_rank,
+                    100 * PERCENT_RANK() OVER (
+                        PARTITION BY Properties.zip_or_postal_code ORDER BY Propensities.score {worst}
+                    ) AS zip_percentile
+                FROM Propensities
+                INNER JOIN Properties
+                ON Propensities.apn = Properties.apn
+                WHERE Properties.state_or_region = $1
+                    AND Propensities.model = $4
+                    AND (
+                        ($2::TEXT IS NULL AND $3::TEXT IS NULL)
+                        OR Properties.zip_or_postal_code IN (
+                            SELECT Regional.zip_or_postal_code
+                            FROM Properties AS Regional
+                            WHERE Regional.state_or_region = $1
+                                AND ($2::TEXT IS NULL OR Regional.admin_division = $2)
+                                AND ($3::TEXT IS NULL OR Regional.city = $3)
+                        )
+                    )
+            )
+            SELECT *
+            FROM Ranked
+            WHERE ($2::TEXT IS NULL OR admin_division = $2)
+                AND ($3::TEXT IS NULL OR city = $3)
+            ORDER BY score {best}, apn
+            LIMIT $5
+            "##,
+            best = direction.best_first(),
+            worst = direction.worst_first(),
+        );
+        let records = sqlx::query_as::<_, RankedScoreAddressRow>(&sql)
+            .bind(state)
+            .bind(county)
+            .bind(city)
+            .bind(model)
+            .bind(n as i64)
+            .fetch_all(pool)
+            .await
+            .context("Failed to perform a query to retrieve top propensity scores for a region.")?;
+
+        records
+            .into_iter()
+            .map(|record| record.into_ranked_score_address())
+            .collect()
+    }
+
+    /// Finds the propensity scores and property addresses for a set of APNs in a single query. APNs
+    /// without a score or a matching property are simply absent from the result, so callers are
+    /// responsible for identifying which of the requested APNs were not found.
+    #[tracing::instrument(level = "info", skip(pool, apns), fields(nr_apns = apns.len()))]
+    pub async fn find_address_scores_for_apns(
+        apns: &[AssessorParcelNumber], model: &str, pool: &PgPool,
     ) -> Result<Vec<ScoreAddress>, CoreError> {
-        let records = sqlx::query!(
+        if apns.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let apns: Vec<String> = apns.iter().map(|apn| apn.to_string()).collect();
+        let records = sqlx::query_as::<_, ScoreAddressRow>(
             r##"
-            SELECT Propensities.id, Propensities.apn, Propensities.score, Propensities.zip_or_postal_code as p_zip_or_postal_code,
+            SELECT Propensities.id, Propensities.apn, Propensities.model, Propensities.score, Propensities.zip_or_postal_code as p_zip_or_postal_code,
                 Properties.street_number, Properties.street_pre_direction, Properties.street_name,
                 Properties.street_suffix, Properties.street_post_direction, Properties.secondary_designator,
-                Properties.secondary_number, Properties.city, Properties.state_or_region, Properties.zip_or_postal_code as a_zip_or_postal_code
+                Properties.secondary_number, Properties.city, Properties.state_or_region, Properties.zip_or_postal_code
             FROM Propensities
             INNER JOIN Properties
             ON Propensities.apn = Properties.apn
-            WHERE Propensities.zip_or_postal_code = $1
-            ORDER BY Propensities.score DESC
-            LIMIT $2
+            WHERE Propensities.apn = ANY($1)
+                AND Propensities.model = $2
             "##,
-            zip_code.as_ref(),
-            limit as i64,
         )
+        .bind(&apns)
+        .bind(model)
         .fetch_all(pool)
         .await
-        .context("Failed to perform a query to retrieve top propensity scores for a zip code.")?;
+        .context("Failed to perform a query to retrieve propensity scores for a batch of APNs.")?;
 
-        let result: Vec<Result<ScoreAddress, CoreError>> = records
-            .into_iter()
-            .map(|record| {
-                let p_zip_or_postal_code = record.p_zip_or_postal_code.map(|z| ZipOrPostalCode::new(z)).transpose()?;
-                let score = PropertyPropensityScore {
-                    id: Some(record.id),
-                    apn: AssessorParcelNumber::new(record.apn)?,
-                    zip_or_postal_code: p_zip_or_postal_code,
-                    score: PropensityScore::new(record.score as u16)?,
-                };
-                let secondary: Option<SecondaryAddressLine> = record
-                    .secondary_designator
-                    .zip(record.secondary_number)
-                    .map(|(d, n)| SecondaryAddressLine::new(d, n));
-
-                let address = Address::new_in_usa(
-                    AddressLine::new(
-                        record.street_number,
-                        record.street_name,
-                        record.street_suffix,
-                        StreetDirection::new(record.street_pre_direction, record.street_post_direction),
-                    ),
-                    secondary,
-                    City::new(record.city),
-                    StateOrRegion::new(record.state_or_region),
-                    ZipOrPostalCode::new(record.a_zip_or_postal_code)?,
-                );
-                Ok((score, Some(address)))
-            })
-            .collect();
+        records.into_iter().map(|record| record.into_score_address()).collect()
+    }
 
-        let result: Result<Vec<ScoreAddress>, CoreError> = result.into_iter().collect();
-        result
+    /// Finds properties that may be the given address, with their propensity scores. Candidates
+    /// share the street number and, when known, the state and either the zip code or city of the
+    /// address. Since a locality may have many more properties with the street number than the limit,
+    /// candidates with the address's street name come first, followed by those whose street name
+    /// begins alike; ranking the candidates by how well they match is left to the caller.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_address_score_candidates(
+        address: &ParsedAddress, model: &str, limit: u16, pool: &PgPool,
+    ) -> Result<Vec<AddressCandidate>, CoreError> {
+        let records = sqlx::query_as::<_, AddressCandidateRow>(
+            r##"
+            SELECT Properties.apn, Propensities.score,
+                Properties.street_number, Properties.street_pre_direction, Properties.street_name,
+                Properties.street_suffix, Properties.street_post_direction, Properties.secondary_designator,
+                Properties.secondary_number, Properties.city, Properties.state_or_region, Properties.zip_or_postal_code
+            FROM Properties
+            LEFT JOIN Propensities
+            ON Propensities.apn = Properties.apn AND Propensities.model = $6
+            WHERE Properties.street_number = $1
+                AND ($2::TEXT IS NULL OR Properties.state_or_region = $2)
+                AND (($3::TEXT IS NULL AND $4::TEXT IS NULL)
+                    OR Properties.zip_or_postal_code = $3
+                    OR Properties.city = $4)
+            ORDER BY Properties.street_name = $7 DESC,
+                LEFT(Properties.street_name, 1) = LEFT($7, 1) DESC,
+                Properties.apn
+            LIMIT $5
+            "##,
+        )
+        .bind(&address.address_line.street_number)
+        .bind(address.state_or_region.as_ref().map(|s| s.as_ref()))
+        .bind(address.zip_or_postal_code.as_ref().map(|z| z.as_ref()))
+        .bind(address.city.as_ref().map(|c| c.as_ref()))
+        .bind(limit as i64)
+        .bind(model)
+        .bind(&address.address_line.street_name)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve candidate properties for an address.")?;
+
+        records.into_iter().map(|record| record.into_candidate()).collect()
+    }
+
+    /// Freshness of propensity scores cheap enough to assess on every readiness probe: the row count
+    /// is the planner's estimate rather than an exact count.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn estimate_freshness(pool: &PgPool) -> Result<TableFreshness, CoreError> {
+        let (nr_rows, last_updated_on): (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+            r##"
+            SELECT GREATEST(reltuples, 0)::BIGINT, (SELECT MAX(last_updated_on) FROM Propensities)
+            FROM pg_class
+            WHERE oid = 'propensities'::regclass
+            "##,
+        )
+        .fetch_one(pool)
+        .await
+        .context("Failed to perform a query to estimate freshness of propensity scores.")?;
+        Ok(TableFreshness { nr_rows, last_updated_on })
     }
 
     #[tracing::instrument(level = "info", skip(transaction))]
@@ -164,21 +644,174 @@ impl PropertyPropensityScoreRepository {
 
         let result = sqlx::query!(
             r##"
-            INSERT INTO Propensities (id, apn, zip_or_postal_code, score, created_on, last_updated_on)
-            VALUES(DEFAULT, $1, $2, $3, $4, $5)
+            INSERT INTO Propensities (id, apn, model, zip_or_postal_code, score, created_on, last_updated_on)
+            VALUES(DEFAULT, $1, $2, $3, $4, $5, $5)
             RETURNING id
             "##,
             record.apn.as_ref(),
+            &record.model,
             record.zip_or_postal_code.as_ref().map(|z| z.as_ref()),
             record.score.score as i16,
-            now.into(),
-            now.into()
+            now
         )
         .fetch_one(transaction)
         .await?;
 
-        Ok(PropertyPropensityScore { id: Some(result.id as i32), ..record.clone() })
+        Ok(PropertyPropensityScore { id: Some(result.id), ..record.clone() })
     }
+
+    /// Records a version of a property's score, then brings the property's current score in
+    /// Propensities up to date with its latest effective version; a version effective before the
+    /// current one is kept only as history. Returns the property's current score, or `None` if
+    /// the version was previously recorded (with the same effective date and source).
+    #[tracing::instrument(level = "info", skip(transaction))]
+    pub async fn record_version(
+        transaction: &mut Transaction<'_, Postgres>, version: &PropensityScoreVersion,
+    ) -> Result<Option<PropertyPropensityScore>, CoreError> {
+        let recorded = sqlx::query!(
+            r##"
+            INSERT INTO PropensityHistory (apn, model, score, zip_or_postal_code, effective_on, source, loaded_on)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (apn, model, effective_on, source) DO NOTHING
+            "##,
+            version.apn.as_ref(),
+            &version.model,
+            version.score.score as i16,
+            version.zip_or_postal_code.as_ref().map(|z| z.as_ref()),
+            version.effective_on,
+            &version.source,
+            version.loaded_on
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to record a propensity score version.")?;
+
+        if recorded.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let current = sqlx::query!(
+            r##"
+            INSERT INTO Propensities (apn, model, zip_or_postal_code, score, created_on, last_updated_on)
+            SELECT apn, model, zip_or_postal_code, score, $3::TIMESTAMPTZ, $3::TIMESTAMPTZ
+            FROM (
+                SELECT apn, model, zip_or_postal_code, score
+                FROM PropensityHistory
+                WHERE apn = $1 AND model = $2
+                ORDER BY effective_on DESC, loaded_on DESC, id DESC
+                LIMIT 1
+            ) AS Latest
+            ON CONFLICT (apn, model) DO UPDATE
+            SET score = EXCLUDED.score,
+                zip_or_postal_code = EXCLUDED.zip_or_postal_code,
+                last_updated_on = EXCLUDED.last_updated_on
+            RETURNING id, score, zip_or_postal_code
+            "##,
+            version.apn.as_ref(),
+            &version.model,
+            version.loaded_on
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Failed to update the current propensity score to its latest version.")?;
+
+        Ok(Some(PropertyPropensityScore {
+            id: Some(current.id),
+            apn: version.apn.clone(),
+            model: version.model.clone(),
+            zip_or_postal_code: current.zip_or_postal_code.map(ZipOrPostalCode::new).transpose()?,
+            score: PropensityScore::new(current.score as u16)?,
+        }))
+    }
+
+    /// Finds the version of a property's score by a model in effect on a date, or currently if no
+    /// date is given. Of versions effective on the same date, the most recently loaded is in effect.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_version(
+        apn: &AssessorParcelNumber, model: &str, as_of: Option<NaiveDate>, pool: &PgPool,
+    ) -> Result<Option<PropensityScoreVersion>, CoreError> {
+        sqlx::query_as::<_, PropensityScoreVersionRow>(
+            r##"
+            SELECT apn, model, score, zip_or_postal_code, effective_on, source, loaded_on
+            FROM PropensityHistory
+            WHERE apn = $1 AND model = $2
+                AND ($3::DATE IS NULL OR effective_on <= $3)
+            ORDER BY effective_on DESC, loaded_on DESC, id DESC
+            LIMIT 1
+            "##,
+        )
+        .bind(apn.as_ref())
+        .bind(model)
+        .bind(as_of)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to perform a query to retrieve a propensity score version.")?
+        .map(|row| row.into_version())
+        .transpose()
+    }
+
+    /// Finds every version of a property's score by a model, in the order they took effect.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_history(
+        apn: &AssessorParcelNumber, model: &str, pool: &PgPool,
+    ) -> Result<Vec<PropensityScoreVersion>, CoreError> {
+        let records = sqlx::query_as::<_, PropensityScoreVersionRow>(
+            r##"
+            SELECT apn, model, score, zip_or_postal_code, effective_on, source, loaded_on
+            FROM PropensityHistory
+            WHERE apn = $1 AND model = $2
+            ORDER BY effective_on, loaded_on, id
+            "##,
+        )
+        .bind(apn.as_ref())
+        .bind(model)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve propensity score history.")?;
+
+        records.into_iter().map(|record| record.into_version()).collect()
+    }
+
+    /// Finds the current score of every property scored by a model, with the zip code supplied with
+    /// the score and the county of its property, if loaded.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_located_scores(model: &str, pool: &PgPool) -> Result<Vec<LocatedScore>, CoreError> {
+        let records: Vec<(String, i16, Option<String>, Option<String>)> = sqlx::query_as(
+            r##"
+            SELECT Propensities.apn, Propensities.score, Propensities.zip_or_postal_code, Properties.admin_division
+            FROM Propensities
+            LEFT JOIN Properties
+            ON Propensities.apn = Properties.apn
+            WHERE Propensities.model = $1
+            "##,
+        )
+        .bind(model)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve the propensity scores of a model.")?;
+
+        records
+            .into_iter()
+            .map(|(apn, score, zip_code, county)| -> Result<LocatedScore, CoreError> {
+                Ok(LocatedScore {
+                    apn: AssessorParcelNumber::new(apn)?,
+                    score: PropensityScore::new(score as u16)?,
+                    zip_code,
+                    county,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A property's current score by a model, with the zip code supplied with the score and the county of
+/// the property, if known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocatedScore {
+    pub apn: AssessorParcelNumber,
+    pub score: PropensityScore,
+    pub zip_code: Option<String>,
+    pub county: Option<String>,
 }
 
 #[cfg(test)]
@@ -189,15 +822,30 @@ mod tests {
 
     #[test]
     fn test_propensity_score_validation() -> anyhow::Result<()> {
-        let _errors = assert_err!(PropensityScore::new(0));
-
-        let actual = assert_ok!(PropensityScore::new(50));
-        assert_eq!(actual, PropensityScore { score: 50 });
+        let actual = assert_ok!(PropensityScore::new(0));
+        assert_eq!(actual, PropensityScore { score: 0 });
 
         let actual = assert_ok!(PropensityScore::new(950));
         assert_eq!(actual, PropensityScore { score: 950 });
 
-        let _errors = assert_err!(PropensityScore::new(951));
+        let actual = assert_ok!(PropensityScore::new(951));
+        assert_eq!(actual, PropensityScore { score: 951 });
+
+        let _errors = assert_err!(PropensityScore::new(32768));
         Ok(())
     }
+
+    #[test]
+    fn test_zip_code_source_parsing() {
+        assert_eq!(ZipCodeSource::default(), ZipCodeSource::Propensity);
+        assert_eq!(
+            assert_ok!("Property ".parse::<ZipCodeSource>()),
+            ZipCodeSource::Property
+        );
+        assert_eq!(
+            assert_ok!("propensity".parse::<ZipCodeSource>()),
+            ZipCodeSource::Propensity
+        );
+        assert_err!("mailing".parse::<ZipCodeSource>());
+    }
 }
@@ -0,0 +1,657 @@
+use crate::core::domain::propensity_model::{PropensityModel, ScoreDirection};
+use crate::core::domain::DomainError;
+use crate::core::CoreError;
+use anyhow::Context;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// Source recorded in the score history of materialized composite scores.
+pub const COMPOSITE_SOURCE: &str = "composite";
+
+/// How each component model's scores are normalized before they are weighted, so scores of models
+/// on different scales can be combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Normalization {
+    /// Percentile rank of the score among the model's scores in its region.
+    Percentile,
+
+    /// Standard score of the score among the model's scores in its region. Combined standard scores
+    /// are placed on the composite's scale between three standard deviations either side of the mean.
+    ZScore,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self::Percentile
+    }
+}
+
+impl Normalization {
+    pub const ALL: [Normalization; 2] = [Normalization::Percentile, Normalization::ZScore];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Percentile => "percentile",
+            Self::ZScore => "z-score",
+        }
+    }
+
+    /// SQL normalizing a component's `signed_score`, oriented so greater values indicate a greater
+    /// propensity, over the `within_region` window of the component model's scores.
+    fn normalized_sql(&self) -> &'static str {
+        match self {
+            Self::Percentile => "PERCENT_RANK() OVER (within_region ORDER BY signed_score)",
+            Self::ZScore => {
+                "COALESCE((signed_score - AVG(signed_score) OVER within_region) / NULLIF(STDDEV_POP(signed_score) OVER within_region, 0), 0)"
+            }
+        }
+    }
+
+    /// SQL placing the weighted `value` of the normalized component scores between 0 and 1.
+    fn unit_sql(&self) -> &'static str {
+        match self {
+            Self::Percentile => "value",
+            Self::ZScore => "LEAST(GREATEST((value + 3) / 6, 0), 1)",
+        }
+    }
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = DomainError;
+
+    fn from_str(normalization: &str) -> Result<Self, Self::Err> {
+        let normalization = normalization.trim().to_lowercase().replace('_', "-");
+        Self::ALL
+            .iter()
+            .find(|n| n.as_str() == normalization)
+            .copied()
+            .ok_or_else(|| {
+                DomainError::InvalidComposite(format!("{} is not one of percentile or z-score", normalization))
+            })
+    }
+}
+
+/// Region within which component scores are normalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationRegion {
+    State,
+    County,
+    ZipCode,
+}
+
+impl Default for NormalizationRegion {
+    fn default() -> Self {
+        Self::State
+    }
+}
+
+impl NormalizationRegion {
+    pub const ALL: [NormalizationRegion; 3] = [
+        NormalizationRegion::State,
+        NormalizationRegion::County,
+        NormalizationRegion::ZipCode,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::State => "state",
+            Self::County => "county",
+            Self::ZipCode => "zip_code",
+        }
+    }
+
+    /// SQL identifying the region of a score. States and counties are those of the scored property;
+    /// scores whose property is not loaded, or has no county, fall back to being normalized within
+    /// their zip code.
+    fn key_sql(&self) -> &'static str {
+        match self {
+            Self::State => "COALESCE(Properties.state_or_region, 'zip:' || Propensities.zip_or_postal_code)",
+            Self::County => {
+                "COALESCE(Properties.state_or_region || '/' || Properties.admin_division, 'zip:' || Propensities.zip_or_postal_code)"
+            }
+            Self::ZipCode => "Propensities.zip_or_postal_code",
+        }
+    }
+
+    fn join_sql(&self) -> &'static str {
+        match self {
+            Self::State | Self::County => "LEFT JOIN Properties ON Propensities.apn = Properties.apn",
+            Self::ZipCode => "",
+        }
+    }
+}
+
+impl fmt::Display for NormalizationRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NormalizationRegion {
+    type Err = DomainError;
+
+    fn from_str(region: &str) -> Result<Self, Self::Err> {
+        let region = region.trim().to_lowercase();
+        let region = match region.as_str() {
+            "zip" | "zipcode" => "zip_code".to_string(),
+            _ => region,
+        };
+        Self::ALL
+            .iter()
+            .find(|r| r.as_str() == region)
+            .copied()
+            .ok_or_else(|| DomainError::InvalidComposite(format!("{} is not one of state, county or zip_code", region)))
+    }
+}
+
+/// A registered model whose scores contribute to a composite with a relative weight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompositeComponent {
+    pub model: String,
+    pub weight: BigDecimal,
+}
+
+impl CompositeComponent {
+    pub fn new(model: impl Into<String>, weight: BigDecimal) -> Result<Self, DomainError> {
+        let model = model.into().trim().to_lowercase();
+        if model.is_empty() {
+            return Err(DomainError::InvalidComposite("component model is empty".to_string()));
+        }
+
+        if weight <= BigDecimal::zero() {
+            return Err(DomainError::InvalidComposite(format!(
+                "weight of component {} must be positive",
+                model
+            )));
+        }
+
+        Ok(Self { model, weight })
+    }
+}
+
+impl fmt::Display for CompositeComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.model, self.weight)
+    }
+}
+
+/// Parses a component given as `MODEL=WEIGHT`; e.g., `refinance=0.6`.
+impl FromStr for CompositeComponent {
+    type Err = DomainError;
+
+    fn from_str(component: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || DomainError::InvalidComposite(format!("component {} is not of the form MODEL=WEIGHT", component));
+        let mut parts = component.splitn(2, '=');
+        let model = parts.next().ok_or_else(invalid)?;
+        let weight = parts.next().ok_or_else(invalid)?;
+        let weight = BigDecimal::from_str(weight.trim()).map_err(|_| invalid())?;
+        Self::new(model, weight)
+    }
+}
+
+/// Definition of a composite model, whose scores are the weighted mean of its component models'
+/// normalized scores. A property is given a composite score only if every component model scores it.
+/// The composite is itself a registered model, whose scale its scores are placed on, so its scores
+/// are searched like those of any other model once materialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompositeModel {
+    /// Name of the registered model holding the composite scores.
+    pub model: String,
+    pub components: Vec<CompositeComponent>,
+    pub normalization: Normalization,
+    pub region: NormalizationRegion,
+}
+
+impl CompositeModel {
+    pub fn new(
+        model: impl Into<String>, components: Vec<CompositeComponent>, normalization: Normalization,
+        region: NormalizationRegion,
+    ) -> Result<Self, DomainError> {
+        let model = model.into().trim().to_lowercase();
+        if components.is_empty() {
+            return Err(DomainError::InvalidComposite(format!(
+                "composite {} has no components",
+                model
+            )));
+        }
+
+        let mut seen = HashSet::new();
+        for component in components.iter() {
+            if component.model == model {
+                return Err(DomainError::InvalidComposite(format!(
+                    "composite {} cannot be its own component",
+                    model
+                )));
+            }
+            if !seen.insert(component.model.as_str()) {
+                return Err(DomainError::InvalidComposite(format!(
+                    "component {} of composite {} is given more than once",
+                    component.model, model
+                )));
+            }
+        }
+
+        Ok(Self { model, components, normalization, region })
+    }
+
+    /// SQL computing the composite score of every property scored by all of the composite's components,
+    /// placed on the composite model's scale. Binds the composite's name as `$1` and the scale's bounds
+    /// as `$2` and `$3`.
+    fn scores_sql(&self, direction: ScoreDirection) -> String {
+        let scaled = match direction {
+            ScoreDirection::HigherIsBetter => format!("$2 + {} * ($3 - $2)", self.normalization.unit_sql()),
+            ScoreDirection::LowerIsBetter => format!("$3 - {} * ($3 - $2)", self.normalization.unit_sql()),
+        };
+
+        format!(
+            r##"
+            WITH ComponentScores AS (
+                SELECT Propensities.apn, Propensities.zip_or_postal_code, Components.model,
+                    Components.weight::FLOAT8 AS weight, {key} AS region,
+                    CASE WHEN PropensityModels.direction = 'lower' THEN -Propensities.score ELSE Propensities.score END::FLOAT8 AS signed_score
+                FROM PropensityCompositeComponents AS Components
+                INNER JOIN PropensityModels ON PropensityModels.name = Components.model
+                INNER JOIN Propensities ON Propensities.model = Components.model
+                {join}
+                WHERE Components.composite = $1
+                    AND {key} IS NOT NULL
+            ),
+            Normalized AS (
+                SELECT apn, zip_or_postal_code, weight, {normalized} AS normalized
+                FROM ComponentScores
+                WINDOW within_region AS (PARTITION BY model, region)
+            ),
+            Combined AS (
+                SELECT apn, MAX(zip_or_postal_code) AS zip_or_postal_code,
+                    SUM(weight * normalized) / SUM(weight) AS value
+                FROM Normalized
+                GROUP BY apn
+                HAVING COUNT(*) = (SELECT COUNT(*) FROM PropensityCompositeComponents WHERE composite = $1)
+            )
+            SELECT apn, zip_or_postal_code, ROUND({scaled})::SMALLINT AS score
+            FROM Combined
+            "##,
+            key = self.region.key_sql(),
+            join = self.region.join_sql(),
+            normalized = self.normalization.normalized_sql(),
+            scaled = scaled,
+        )
+    }
+}
+
+impl fmt::Display for CompositeModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components: Vec<String> = self.components.iter().map(|c| c.to_string()).collect();
+        write!(
+            f,
+            "{} = {} by {} within {}",
+            self.model,
+            components.join(" + "),
+            self.normalization,
+            self.region
+        )
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct CompositeModelRow {
+    model: String,
+    normalization: String,
+    region: String,
+}
+
+#[derive(Debug, FromRow)]
+struct CompositeComponentRow {
+    composite: String,
+    model: String,
+    weight: BigDecimal,
+}
+
+impl CompositeModelRow {
+    fn into_composite(self, components: Vec<CompositeComponentRow>) -> Result<CompositeModel, CoreError> {
+        let composite = || -> Result<CompositeModel, DomainError> {
+            let components = components
+                .into_iter()
+                .map(|c| CompositeComponent::new(c.model, c.weight))
+                .collect::<Result<Vec<_>, _>>()?;
+            CompositeModel::new(
+                self.model,
+                components,
+                self.normalization.parse()?,
+                self.region.parse()?,
+            )
+        };
+        composite().map_err(|err| CoreError::CoreError(err.into()))
+    }
+}
+
+pub struct PropensityCompositeRepository;
+
+impl PropensityCompositeRepository {
+    /// Defines a composite, or replaces the definition of a composite of the same model. The composite
+    /// and its components must be registered models, and a component may not itself be a composite.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn define(composite: &CompositeModel, pool: &PgPool) -> Result<CompositeModel, CoreError> {
+        let names: Vec<String> = std::iter::once(composite.model.clone())
+            .chain(composite.components.iter().map(|c| c.model.clone()))
+            .collect();
+        let registered: Vec<(String, bool)> = sqlx::query_as(
+            r##"
+            SELECT PropensityModels.name, PropensityComposites.model IS NOT NULL
+            FROM PropensityModels
+            LEFT JOIN PropensityComposites
+            ON PropensityComposites.model = PropensityModels.name
+            WHERE PropensityModels.name = ANY($1)
+            "##,
+        )
+        .bind(&names)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to find the models of a composite.")?;
+        let registered: HashMap<String, bool> = registered.into_iter().collect();
+
+        if let Some(name) = names.iter().find(|name| !registered.contains_key(name.as_str())) {
+            return Err(CoreError::CoreError(
+                DomainError::UnrecognizedPropensityModel(name.clone()).into(),
+            ));
+        }
+        if let Some(component) = composite.components.iter().find(|c| registered[&c.model]) {
+            return Err(CoreError::CoreError(
+                DomainError::InvalidComposite(format!(
+                    "component {} of {} is itself a composite",
+                    component.model, composite.model
+                ))
+                .into(),
+            ));
+        }
+
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to begin a transaction to define a composite model.")?;
+
+        sqlx::query(
+            r##"
+            INSERT INTO PropensityComposites (model, normalization, region)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (model) DO UPDATE
+            SET normalization = EXCLUDED.normalization,
+                region = EXCLUDED.region
+            "##,
+        )
+        .bind(&composite.model)
+        .bind(composite.normalization.as_str())
+        .bind(composite.region.as_str())
+        .execute(&mut transaction)
+        .await
+        .context("Failed to perform a query to define a composite model.")?;
+
+        sqlx::query("DELETE FROM PropensityCompositeComponents WHERE composite = $1")
+            .bind(&composite.model)
+            .execute(&mut transaction)
+            .await
+            .context("Failed to perform a query to replace the components of a composite model.")?;
+
+        for component in composite.components.iter() {
+            sqlx::query("INSERT INTO PropensityCompositeComponents (composite, model, weight) VALUES ($1, $2, $3)")
+                .bind(&composite.model)
+                .bind(&component.model)
+                .bind(&component.weight)
+                .execute(&mut transaction)
+                .await
+                .context("Failed to perform a query to define a composite model component.")?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the definition of a composite model.")?;
+
+        Ok(composite.clone())
+    }
+
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<CompositeModel>, CoreError> {
+        let rows: Vec<CompositeModelRow> = sqlx::query_as(
+            r##"
+            SELECT model, normalization, region
+            FROM PropensityComposites
+            ORDER BY model
+            "##,
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve composite models.")?;
+
+        let components: Vec<CompositeComponentRow> = sqlx::query_as(
+            r##"
+            SELECT composite, model, weight
+            FROM PropensityCompositeComponents
+            ORDER BY composite, model
+            "##,
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve composite model components.")?;
+        let mut components_by_composite: HashMap<String, Vec<CompositeComponentRow>> = HashMap::new();
+        for component in components {
+            components_by_composite
+                .entry(component.composite.clone())
+                .or_default()
+                .push(component);
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                let components = components_by_composite.remove(&row.model).unwrap_or_default();
+                row.into_composite(components)
+            })
+            .collect()
+    }
+
+    /// Replaces the scores of a composite's model with composite scores computed from the current
+    /// scores of its components, recording each in the score history as effective on the given date.
+    /// Properties no longer scored by every component lose their composite score. Returns the number
+    /// of composite scores materialized.
+    #[tracing::instrument(level = "info", skip(composite, model, pool), fields(composite = %composite.model))]
+    pub async fn materialize(
+        composite: &CompositeModel, model: &PropensityModel, effective_on: NaiveDate, pool: &PgPool,
+    ) -> Result<u64, CoreError> {
+        let materialized_on = Utc::now();
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to begin a transaction to materialize composite scores.")?;
+
+        sqlx::query(
+            r##"
+            CREATE TEMP TABLE CompositeScores (
+                apn VARCHAR(50) PRIMARY KEY,
+                zip_or_postal_code VARCHAR(20),
+                score SMALLINT NOT NULL
+            ) ON COMMIT DROP
+            "##,
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to create a table of composite scores.")?;
+
+        let sql = format!(
+            "INSERT INTO CompositeScores (apn, zip_or_postal_code, score) {}",
+            composite.scores_sql(model.scale.direction)
+        );
+        let nr_scores = sqlx::query(&sql)
+            .bind(&composite.model)
+            .bind(model.scale.min_score as f64)
+            .bind(model.scale.max_score as f64)
+            .execute(&mut transaction)
+            .await
+            .context("Failed to perform a query to compute composite scores.")?
+            .rows_affected();
+
+        sqlx::query(
+            r##"
+            INSERT INTO PropensityHistory (apn, model, score, zip_or_postal_code, effective_on, source, loaded_on)
+            SELECT apn, $1, score, zip_or_postal_code, $2, $3, $4
+            FROM CompositeScores
+            ON CONFLICT (apn, model, effective_on, source) DO UPDATE
+            SET score = EXCLUDED.score,
+                zip_or_postal_code = EXCLUDED.zip_or_postal_code,
+                loaded_on = EXCLUDED.loaded_on
+            "##,
+        )
+        .bind(&composite.model)
+        .bind(effective_on)
+        .bind(COMPOSITE_SOURCE)
+        .bind(materialized_on)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to record composite score versions.")?;
+
+        sqlx::query(
+            r##"
+            INSERT INTO Propensities (apn, model, zip_or_postal_code, score, created_on, last_updated_on)
+            SELECT apn, $1, zip_or_postal_code, score, $2, $2
+            FROM CompositeScores
+            ON CONFLICT (apn, model) DO UPDATE
+            SET score = EXCLUDED.score,
+                zip_or_postal_code = EXCLUDED.zip_or_postal_code,
+                last_updated_on = EXCLUDED.last_updated_on
+            "##,
+        )
+        .bind(&composite.model)
+        .bind(materialized_on)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to save composite scores.")?;
+
+        sqlx::query("DELETE FROM Propensities WHERE model = $1 AND last_updated_on < $2")
+            .bind(&composite.model)
+            .bind(materialized_on)
+            .execute(&mut transaction)
+            .await
+            .context("Failed to remove stale composite scores.")?;
+
+        sqlx::query("UPDATE PropensityComposites SET materialized_on = $2 WHERE model = $1")
+            .bind(&composite.model)
+            .bind(materialized_on)
+            .execute(&mut transaction)
+            .await
+            .context("Failed to record the materialization of a composite model.")?;
+
+        transaction.commit().await.context("Failed to commit composite scores.")?;
+
+        Ok(nr_scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    fn component(model: &str, weight: &str) -> CompositeComponent {
+        assert_ok!(format!("{}={}", model, weight).parse())
+    }
+
+    #[test]
+    fn test_composite_option_parsing() {
+        assert_eq!(assert_ok!("Z_SCORE".parse::<Normalization>()), Normalization::ZScore);
+        assert_eq!(
+            assert_ok!("percentile".parse::<Normalization>()),
+            Normalization::Percentile
+        );
+        assert_err!("rank".parse::<Normalization>());
+
+        assert_eq!(
+            assert_ok!("zip".parse::<NormalizationRegion>()),
+            NormalizationRegion::ZipCode
+        );
+        assert_eq!(
+            assert_ok!(" County".parse::<NormalizationRegion>()),
+            NormalizationRegion::County
+        );
+        assert_err!("city".parse::<NormalizationRegion>());
+
+        assert_eq!(
+            assert_ok!(" Refinance = 0.6".parse::<CompositeComponent>()),
+            CompositeComponent {
+                model: "refinance".to_string(),
+                weight: assert_ok!(BigDecimal::from_str("0.6"))
+            }
+        );
+        assert_err!("refinance".parse::<CompositeComponent>());
+        assert_err!("refinance=heavy".parse::<CompositeComponent>());
+        assert_err!("refinance=0".parse::<CompositeComponent>());
+        assert_err!("refinance=-1".parse::<CompositeComponent>());
+    }
+
+    #[test]
+    fn test_composite_validation() {
+        let composite = assert_ok!(CompositeModel::new(
+            "Equity",
+            vec![component("heloc", "2"), component("refinance", "1")],
+            Normalization::Percentile,
+            NormalizationRegion::County,
+        ));
+        assert_eq!(composite.model, "equity");
+        assert_eq!(
+            composite.to_string(),
+            "equity = heloc=2 + refinance=1 by percentile within county"
+        );
+
+        assert_err!(CompositeModel::new(
+            "equity",
+            vec![],
+            Normalization::Percentile,
+            NormalizationRegion::State
+        ));
+        assert_err!(CompositeModel::new(
+            "equity",
+            vec![component("heloc", "1"), component("equity", "1")],
+            Normalization::Percentile,
+            NormalizationRegion::State,
+        ));
+        assert_err!(CompositeModel::new(
+            "equity",
+            vec![component("heloc", "1"), component("HELOC", "2")],
+            Normalization::ZScore,
+            NormalizationRegion::State,
+        ));
+    }
+
+    #[test]
+    fn test_composite_scores_sql() {
+        let composite = assert_ok!(CompositeModel::new(
+            "equity",
+            vec![component("heloc", "1")],
+            Normalization::ZScore,
+            NormalizationRegion::ZipCode,
+        ));
+        let sql = composite.scores_sql(ScoreDirection::LowerIsBetter);
+        assert!(sql.contains("STDDEV_POP(signed_score) OVER within_region"));
+        assert!(sql.contains("$3 - LEAST(GREATEST((value + 3) / 6, 0), 1) * ($3 - $2)"));
+        assert!(!sql.contains("JOIN Properties"));
+
+        let composite = CompositeModel { region: NormalizationRegion::State, ..composite };
+        let sql = composite.scores_sql(ScoreDirection::HigherIsBetter);
+        assert!(sql.contains("LEFT JOIN Properties ON Propensities.apn = Properties.apn"));
+        assert!(sql.contains("COALESCE(Properties.state_or_region, 'zip:' || Propensities.zip_or_postal_code)"));
+        assert!(sql.contains("$2 + LEAST(GREATEST((value + 3) / 6, 0), 1) * ($3 - $2)"));
+    }
+}
@@ -0,0 +1,497 @@
+use crate::core::domain::DomainError;
+use crate::core::CoreError;
+use anyhow::Context;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Model whose scores were loaded before models were registered, and whose scores are served when a
+/// request does not name a model.
+pub const DEFAULT_MODEL: &str = "heloc";
+
+lazy_static::lazy_static! {
+    static ref RE_MODEL_NAME: regex::Regex = regex::Regex::new(r##"^[a-z][a-z0-9_]{0,49}$"##).unwrap();
+}
+
+/// Whether higher or lower scores of a model indicate a greater propensity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreDirection {
+    HigherIsBetter,
+    LowerIsBetter,
+}
+
+impl Default for ScoreDirection {
+    fn default() -> Self {
+        Self::HigherIsBetter
+    }
+}
+
+impl ScoreDirection {
+    pub const ALL: [ScoreDirection; 2] = [ScoreDirection::HigherIsBetter, ScoreDirection::LowerIsBetter];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HigherIsBetter => "higher",
+            Self::LowerIsBetter => "lower",
+        }
+    }
+
+    /// SQL sort order placing the greatest propensity first.
+    pub(crate) fn best_first(&self) -> &'static str {
+        match self {
+            Self::HigherIsBetter => "DESC",
+            Self::LowerIsBetter => "ASC",
+        }
+    }
+
+    /// SQL sort order placing the least propensity first.
+    pub(crate) fn worst_first(&self) -> &'static str {
+        match self {
+            Self::HigherIsBetter => "ASC",
+            Self::LowerIsBetter => "DESC",
+        }
+    }
+}
+
+impl fmt::Display for ScoreDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScoreDirection {
+    type Err = DomainError;
+
+    fn from_str(direction: &str) -> Result<Self, Self::Err> {
+        let direction = direction.trim().to_lowercase();
+        Self::ALL
+            .iter()
+            .find(|d| d.as_str() == direction)
+            .copied()
+            .ok_or_else(|| DomainError::InvalidPropensityModel(format!("{} is not one of higher or lower", direction)))
+    }
+}
+
+/// Range of valid scores of a model and whether higher or lower scores indicate a greater propensity.
+/// `PropensityScore` accepts any stored score; a model's scale is enforced when its scores are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreScale {
+    pub min_score: u16,
+    pub max_score: u16,
+    pub direction: ScoreDirection,
+}
+
+impl ScoreScale {
+    pub fn new(min_score: u16, max_score: u16, direction: ScoreDirection) -> Result<Self, DomainError> {
+        if max_score < min_score {
+            return Err(DomainError::InvalidPropensityModel(format!(
+                "score scale is empty: {} exceeds {}",
+                min_score, max_score
+            )));
+        }
+
+        Ok(Self { min_score, max_score, direction })
+    }
+
+    /// Whether a score lies within the scale.
+    pub fn contains(&self, score: u16) -> bool {
+        self.min_score <= score && score <= self.max_score
+    }
+}
+
+/// A named band of a model's scores, such as `High`, so users share the model's thresholds rather
+/// than each choosing their own. Bands are inclusive of both bounds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierBand {
+    pub name: String,
+    pub min_score: u16,
+    pub max_score: u16,
+}
+
+impl TierBand {
+    pub fn new(name: impl Into<String>, min_score: u16, max_score: u16) -> Result<Self, DomainError> {
+        let name = name.into().trim().to_string();
+        if name.is_empty() {
+            return Err(DomainError::InvalidPropensityModel("tier name is empty".to_string()));
+        }
+
+        if max_score < min_score {
+            return Err(DomainError::InvalidPropensityModel(format!(
+                "tier {} is empty: {} exceeds {}",
+                name, min_score, max_score
+            )));
+        }
+
+        Ok(Self { name, min_score, max_score })
+    }
+
+    pub fn contains(&self, score: u16) -> bool {
+        self.min_score <= score && score <= self.max_score
+    }
+
+    /// Whether the band is named by `name`, ignoring case; e.g., `very high` names `Very High`.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name.trim())
+    }
+}
+
+impl fmt::Display for TierBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}-{}", self.name, self.min_score, self.max_score)
+    }
+}
+
+/// Parses a band given as `NAME=MIN-MAX`; e.g., `Very High=801-950`.
+impl FromStr for TierBand {
+    type Err = DomainError;
+
+    fn from_str(band: &str) -> Result<Self, Self::Err> {
+        let invalid = || DomainError::InvalidPropensityModel(format!("tier {} is not of the form NAME=MIN-MAX", band));
+        let mut parts = band.rsplitn(2, '=');
+        let range = parts.next().ok_or_else(invalid)?;
+        let name = parts.next().ok_or_else(invalid)?;
+        let mut bounds = range.splitn(2, '-').map(|bound| bound.trim().parse::<u16>());
+        match (bounds.next(), bounds.next()) {
+            (Some(Ok(min_score)), Some(Ok(max_score))) => Self::new(name, min_score, max_score),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// A registered propensity model; e.g., interest in a home equity line of credit. Each property may
+/// carry a score from every registered model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PropensityModel {
+    /// Lowercase name identifying the model; e.g., `heloc`.
+    pub name: String,
+
+    pub scale: ScoreScale,
+
+    /// Tier bands of the model's scores, ordered by score; scores outside every band have no tier.
+    pub tiers: Vec<TierBand>,
+
+    pub description: String,
+}
+
+impl PropensityModel {
+    pub fn new(
+        name: impl Into<String>, scale: ScoreScale, mut tiers: Vec<TierBand>, description: impl Into<String>,
+    ) -> Result<Self, DomainError> {
+        let name = name.into().trim().to_lowercase();
+        if !RE_MODEL_NAME.is_match(&name) {
+            return Err(DomainError::InvalidPropensityModel(format!(
+                "name `{}` must start with a letter and contain only letters, digits and underscores",
+                name
+            )));
+        }
+
+        tiers.sort_by_key(|tier| tier.min_score);
+        if let Some(tier) = tiers
+            .iter()
+            .find(|tier| !scale.contains(tier.min_score) || !scale.contains(tier.max_score))
+        {
+            return Err(DomainError::InvalidPropensityModel(format!(
+                "tier {} lies outside the {} to {} scale of {}",
+                tier, scale.min_score, scale.max_score, name
+            )));
+        }
+        for (lower, upper) in tiers.iter().zip(tiers.iter().skip(1)) {
+            if upper.min_score <= lower.max_score {
+                return Err(DomainError::InvalidPropensityModel(format!(
+                    "tiers {} and {} of {} overlap",
+                    lower, upper, name
+                )));
+            }
+            if lower.is_named(&upper.name) {
+                return Err(DomainError::InvalidPropensityModel(format!(
+                    "tier {} of {} is defined more than once",
+                    upper.name, name
+                )));
+            }
+        }
+
+        Ok(Self {
+            name,
+            scale,
+            tiers,
+            description: description.into(),
+        })
+    }
+
+    /// Whether a score lies within the model's scale.
+    pub fn admits(&self, score: u16) -> bool {
+        self.scale.contains(score)
+    }
+
+    /// The tier band containing a score, if any.
+    pub fn tier_of(&self, score: u16) -> Option<&TierBand> {
+        self.tiers.iter().find(|tier| tier.contains(score))
+    }
+
+    /// The tier band named by `name`, ignoring case.
+    pub fn find_tier(&self, name: &str) -> Option<&TierBand> {
+        self.tiers.iter().find(|tier| tier.is_named(name))
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct PropensityModelRow {
+    name: String,
+    min_score: i16,
+    max_score: i16,
+    direction: String,
+    description: String,
+}
+
+#[derive(Debug, FromRow)]
+struct TierBandRow {
+    model: String,
+    name: String,
+    min_score: i16,
+    max_score: i16,
+}
+
+impl PropensityModelRow {
+    fn into_model(self, tiers: Vec<TierBandRow>) -> Result<PropensityModel, CoreError> {
+        let model = || -> Result<PropensityModel, DomainError> {
+            let scale = ScoreScale::new(self.min_score as u16, self.max_score as u16, self.direction.parse()?)?;
+            let tiers = tiers
+                .into_iter()
+                .map(|tier| TierBand::new(tier.name, tier.min_score as u16, tier.max_score as u16))
+                .collect::<Result<Vec<_>, _>>()?;
+            PropensityModel::new(self.name, scale, tiers, self.description)
+        };
+        model().map_err(|err| CoreError::CoreError(err.into()))
+    }
+}
+
+pub struct PropensityModelRepository;
+
+impl PropensityModelRepository {
+    /// Registers a model, or updates the scale, tiers and description of a model registered under the
+    /// same name. The model's tiers are replaced by those given.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn register(model: &PropensityModel, pool: &PgPool) -> Result<PropensityModel, CoreError> {
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to begin a transaction to register a propensity model.")?;
+
+        sqlx::query(
+            r##"
+            INSERT INTO PropensityModels (name, min_score, max_score, direction, description, created_on)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (name) DO UPDATE
+            SET min_score = EXCLUDED.min_score,
+                max_score = EXCLUDED.max_score,
+                direction = EXCLUDED.direction,
+                description = EXCLUDED.description
+            "##,
+        )
+        .bind(&model.name)
+        .bind(model.scale.min_score as i16)
+        .bind(model.scale.max_score as i16)
+        .bind(model.scale.direction.as_str())
+        .bind(&model.description)
+        .bind(Utc::now())
+        .execute(&mut transaction)
+        .await
+        .context("Failed to perform a query to register a propensity model.")?;
+
+        sqlx::query("DELETE FROM PropensityTiers WHERE model = $1")
+            .bind(&model.name)
+            .execute(&mut transaction)
+            .await
+            .context("Failed to perform a query to replace the tiers of a propensity model.")?;
+
+        for tier in model.tiers.iter() {
+            sqlx::query("INSERT INTO PropensityTiers (model, name, min_score, max_score) VALUES ($1, $2, $3, $4)")
+                .bind(&model.name)
+                .bind(&tier.name)
+                .bind(tier.min_score as i16)
+                .bind(tier.max_score as i16)
+                .execute(&mut transaction)
+                .await
+                .context("Failed to perform a query to register a propensity model tier.")?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the registration of a propensity model.")?;
+
+        Ok(model.clone())
+    }
+
+    #[tracing::instrument(level = "debug", skip(pool))]
+    pub async fn find(name: &str, pool: &PgPool) -> Result<Option<PropensityModel>, CoreError> {
+        let name = name.trim().to_lowercase();
+        let row: Option<PropensityModelRow> = sqlx::query_as(
+            r##"
+            SELECT name, min_score, max_score, direction, description
+            FROM PropensityModels
+            WHERE name = $1
+            "##,
+        )
+        .bind(&name)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to perform a query to retrieve a propensity model.")?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let tiers: Vec<TierBandRow> = sqlx::query_as(
+            r##"
+            SELECT model, name, min_score, max_score
+            FROM PropensityTiers
+            WHERE model = $1
+            "##,
+        )
+        .bind(&name)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve the tiers of a propensity model.")?;
+
+        row.into_model(tiers).map(Some)
+    }
+
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<PropensityModel>, CoreError> {
+        let rows: Vec<PropensityModelRow> = sqlx::query_as(
+            r##"
+            SELECT name, min_score, max_score, direction, description
+            FROM PropensityModels
+            ORDER BY name
+            "##,
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve propensity models.")?;
+
+        let tiers: Vec<TierBandRow> = sqlx::query_as("SELECT model, name, min_score, max_score FROM PropensityTiers")
+            .fetch_all(pool)
+            .await
+            .context("Failed to perform a query to retrieve propensity model tiers.")?;
+        let mut tiers_by_model: HashMap<String, Vec<TierBandRow>> = HashMap::new();
+        for tier in tiers {
+            tiers_by_model.entry(tier.model.clone()).or_default().push(tier);
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                let tiers = tiers_by_model.remove(&row.name).unwrap_or_default();
+                row.into_model(tiers)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    fn heloc_scale() -> ScoreScale {
+        assert_ok!(ScoreScale::new(1, 950, ScoreDirection::HigherIsBetter))
+    }
+
+    #[test]
+    fn test_score_scale() {
+        let scale = heloc_scale();
+        assert!(!scale.contains(0));
+        assert!(scale.contains(1));
+        assert!(scale.contains(950));
+        assert!(!scale.contains(951));
+        assert_err!(ScoreScale::new(951, 950, ScoreDirection::HigherIsBetter));
+
+        assert_eq!(
+            assert_ok!("Lower".parse::<ScoreDirection>()),
+            ScoreDirection::LowerIsBetter
+        );
+        assert_eq!(ScoreDirection::default(), ScoreDirection::HigherIsBetter);
+        assert_err!("sideways".parse::<ScoreDirection>());
+    }
+
+    #[test]
+    fn test_tier_band_parsing() {
+        assert_eq!(
+            assert_ok!("Very High=801-950".parse::<TierBand>()),
+            TierBand {
+                name: "Very High".to_string(),
+                min_score: 801,
+                max_score: 950
+            }
+        );
+        assert_eq!(
+            assert_ok!(" Low = 1 - 300 ".parse::<TierBand>()),
+            TierBand {
+                name: "Low".to_string(),
+                min_score: 1,
+                max_score: 300
+            }
+        );
+        assert_err!("Low".parse::<TierBand>());
+        assert_err!("Low=300".parse::<TierBand>());
+        assert_err!("=1-300".parse::<TierBand>());
+        assert_err!("Low=300-1".parse::<TierBand>());
+    }
+
+    #[test]
+    fn test_propensity_model_validation() -> anyhow::Result<()> {
+        let tiers: Vec<TierBand> = vec!["High=601-950".parse()?, "Low=1-300".parse()?, "Medium=301-600".parse()?];
+        let model = assert_ok!(PropensityModel::new(
+            " HELOC",
+            heloc_scale(),
+            tiers,
+            "Home equity line of credit"
+        ));
+        assert_eq!(model.name, "heloc");
+        let names: Vec<&str> = model.tiers.iter().map(|tier| tier.name.as_str()).collect();
+        assert_eq!(names, vec!["Low", "Medium", "High"]);
+        assert!(model.admits(950));
+        assert!(!model.admits(0));
+        assert_eq!(model.tier_of(300).map(|tier| tier.name.as_str()), Some("Low"));
+        assert_eq!(model.tier_of(601).map(|tier| tier.name.as_str()), Some("High"));
+        assert_eq!(model.find_tier("medium").map(|tier| tier.min_score), Some(301));
+        assert_eq!(model.find_tier("Very High"), None);
+
+        let gapped = assert_ok!(PropensityModel::new(
+            "refi2",
+            heloc_scale(),
+            vec!["Low=1-100".parse()?],
+            ""
+        ));
+        assert_eq!(gapped.tier_of(101), None);
+
+        assert_err!(PropensityModel::new("2refi", heloc_scale(), vec![], ""));
+        assert_err!(PropensityModel::new("home-equity", heloc_scale(), vec![], ""));
+        assert_err!(PropensityModel::new("", heloc_scale(), vec![], ""));
+        assert_err!(PropensityModel::new(
+            "heloc",
+            heloc_scale(),
+            vec!["Low=1-300".parse()?, "Medium=300-600".parse()?],
+            ""
+        ));
+        assert_err!(PropensityModel::new(
+            "heloc",
+            heloc_scale(),
+            vec!["Low=0-300".parse()?],
+            ""
+        ));
+        assert_err!(PropensityModel::new(
+            "heloc",
+            heloc_scale(),
+            vec!["Low=1-300".parse()?, "low=301-600".parse()?],
+            ""
+        ));
+        Ok(())
+    }
+}
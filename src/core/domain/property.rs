@@ -1,14 +1,18 @@
 use crate::core::domain::{
-    Address, AddressLine, AssessorParcelNumber, City, GeoCoordinate, LandUseType, SecondaryAddressLine, StateOrRegion,
-    StreetDirection, ZipOrPostalCode,
+    Address, AddressLine, AssessorParcelNumber, City, DomainError, GeoCoordinate, LandUseType, SecondaryAddressLine,
+    StateOrRegion, StreetDirection, ZipOrPostalCode,
 };
-use crate::core::CoreError;
+use crate::core::{CoreError, TableFreshness};
 use anyhow::Context;
 use bigdecimal::BigDecimal;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Postgres, Transaction};
-use std::convert::TryInto;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use validator::ValidationErrors;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Property {
@@ -28,6 +32,10 @@ pub struct Property {
     #[serde(alias = "standardized_land_use_type")]
     pub land_use_type: LandUseType,
 
+    /// Land use of the vendor's record, before it was classified by the land use taxonomy.
+    #[serde(default)]
+    pub raw_land_use_type: Option<String>,
+
     #[serde(default)]
     pub area_sq_ft: Option<u32>,
 
@@ -41,6 +49,300 @@ pub struct Property {
     pub total_area_sq_ft: Option<u32>,
 }
 
+/// Address columns as stored in the Properties table, for queries that select them alongside other
+/// data.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct StoredAddress {
+    pub street_number: String,
+    pub street_pre_direction: Option<String>,
+    pub street_name: String,
+    pub street_suffix: String,
+    pub street_post_direction: Option<String>,
+    pub secondary_designator: Option<String>,
+    pub secondary_number: Option<String>,
+    pub city: String,
+    pub state_or_region: String,
+    pub zip_or_postal_code: String,
+}
+
+impl StoredAddress {
+    pub fn into_address(self) -> Result<Address, CoreError> {
+        let secondary: Option<SecondaryAddressLine> = self
+            .secondary_designator
+            .zip(self.secondary_number)
+            .map(|(d, n)| SecondaryAddressLine::new(d, n));
+
+        Ok(Address::new_in_usa(
+            AddressLine::new(
+                self.street_number,
+                self.street_name,
+                self.street_suffix,
+                StreetDirection::new(self.street_pre_direction, self.street_post_direction),
+            ),
+            secondary,
+            City::new(self.city),
+            StateOrRegion::new(self.state_or_region),
+            ZipOrPostalCode::new(self.zip_or_postal_code)?,
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct ApnAddressRow {
+    apn: String,
+    address: StoredAddress,
+}
+
+impl<'r> FromRow<'r, PgRow> for ApnAddressRow {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            apn: row.try_get("apn")?,
+            address: StoredAddress::from_row(row)?,
+        })
+    }
+}
+
+/// Structured address fields used to identify a property when its APN is not known. Street suffix
+/// and secondary number are optional and, when omitted, are not used to discriminate between
+/// properties.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressCriteria {
+    pub street_number: String,
+    pub street_name: String,
+    pub street_suffix: Option<String>,
+    pub secondary_number: Option<String>,
+    pub zip_or_postal_code: ZipOrPostalCode,
+}
+
+impl AddressCriteria {
+    pub fn new(
+        street_number: impl Into<String>, street_name: impl Into<String>, street_suffix: Option<impl Into<String>>,
+        secondary_number: Option<impl Into<String>>, zip_or_postal_code: ZipOrPostalCode,
+    ) -> Self {
+        Self {
+            street_number: street_number.into().trim().to_uppercase(),
+            street_name: street_name.into().trim().to_uppercase(),
+            street_suffix: street_suffix.map(|s| s.into().trim().to_uppercase()),
+            secondary_number: secondary_number.map(|s| s.into().trim().to_uppercase()),
+            zip_or_postal_code,
+        }
+    }
+}
+
+/// Property attribute that may be requested alongside a propensity score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PropertyField {
+    LandUse,
+    Bedrooms,
+    Bathrooms,
+    AreaSqFt,
+    TotalAreaSqFt,
+    Coordinates,
+    County,
+}
+
+impl PropertyField {
+    pub const ALL: [PropertyField; 7] = [
+        PropertyField::LandUse,
+        PropertyField::Bedrooms,
+        PropertyField::Bathrooms,
+        PropertyField::AreaSqFt,
+        PropertyField::TotalAreaSqFt,
+        PropertyField::Coordinates,
+        PropertyField::County,
+    ];
+
+    /// Name by which the field is requested and reported.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LandUse => "land_use",
+            Self::Bedrooms => "bedrooms",
+            Self::Bathrooms => "bathrooms",
+            Self::AreaSqFt => "area_sq_ft",
+            Self::TotalAreaSqFt => "total_area_sq_ft",
+            Self::Coordinates => "coordinates",
+            Self::County => "county",
+        }
+    }
+
+    /// Columns of the Properties table holding the field.
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::LandUse => &["land_use_type"],
+            Self::Bedrooms => &["nr_bedrooms"],
+            Self::Bathrooms => &["nr_bathrooms"],
+            Self::AreaSqFt => &["area_sq_ft"],
+            Self::TotalAreaSqFt => &["total_area_sq_ft"],
+            Self::Coordinates => &["latitude", "longitude"],
+            Self::County => &["admin_division"],
+        }
+    }
+}
+
+impl fmt::Display for PropertyField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PropertyField {
+    type Err = DomainError;
+
+    fn from_str(field: &str) -> Result<Self, Self::Err> {
+        let field = field.trim().to_lowercase();
+        Self::ALL
+            .iter()
+            .find(|f| f.as_str() == field)
+            .copied()
+            .ok_or_else(|| DomainError::UnrecognizedPropertyField(field))
+    }
+}
+
+/// Set of property attributes to select, in a stable order so equivalent requests select the same
+/// columns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PropertyFields(BTreeSet<PropertyField>);
+
+impl PropertyFields {
+    pub fn with(mut self, field: PropertyField) -> Self {
+        self.0.insert(field);
+        self
+    }
+
+    pub fn without(mut self, field: PropertyField) -> Self {
+        self.0.remove(&field);
+        self
+    }
+
+    pub fn contains(&self, field: PropertyField) -> bool {
+        self.0.contains(&field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = PropertyField> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Columns of the fields qualified by the Properties table, each preceded by a comma so the list
+    /// can be appended to a query's select list.
+    pub(crate) fn select_list(&self) -> String {
+        self.iter()
+            .flat_map(|field| field.columns().iter())
+            .map(|column| format!(", Properties.{}", column))
+            .collect()
+    }
+}
+
+impl FromStr for PropertyFields {
+    type Err = DomainError;
+
+    /// Parses a comma separated list of field names; e.g., `land_use,bedrooms,county`.
+    fn from_str(fields: &str) -> Result<Self, Self::Err> {
+        fields
+            .split(',')
+            .filter(|field| !field.trim().is_empty())
+            .map(PropertyField::from_str)
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map(Self)
+    }
+}
+
+/// Requested attributes of a property. Attributes not requested, or unknown for the property, are
+/// `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PropertyAttributes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub land_use: Option<LandUseType>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bedrooms: Option<u8>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bathrooms: Option<BigDecimal>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub area_sq_ft: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_area_sq_ft: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coordinates: Option<GeoCoordinate>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub county: Option<String>,
+}
+
+impl PropertyAttributes {
+    /// Decodes the fields from a row selecting their columns, as listed by
+    /// `PropertyFields::select_list`.
+    pub(crate) fn from_row(row: &PgRow, fields: &PropertyFields) -> Result<Self, CoreError> {
+        let mut attributes = Self::default();
+        for field in fields.iter() {
+            match field {
+                PropertyField::LandUse => {
+                    let land_use: String = row.try_get("land_use_type")?;
+                    attributes.land_use = Some(LandUseType::from_code(&land_use));
+                }
+                PropertyField::Bedrooms => {
+                    let nr_bedrooms: Option<i16> = row.try_get("nr_bedrooms")?;
+                    attributes.bedrooms = nr_bedrooms.map(|v| v as u8);
+                }
+                PropertyField::Bathrooms => {
+                    attributes.bathrooms = row.try_get("nr_bathrooms")?;
+                }
+                PropertyField::AreaSqFt => {
+                    let area: Option<i32> = row.try_get("area_sq_ft")?;
+                    attributes.area_sq_ft = area.map(|v| v as u32);
+                }
+                PropertyField::TotalAreaSqFt => {
+                    let area: Option<i32> = row.try_get("total_area_sq_ft")?;
+                    attributes.total_area_sq_ft = area.map(|v| v as u32);
+                }
+                PropertyField::Coordinates => {
+                    let latitude: Option<BigDecimal> = row.try_get("latitude")?;
+                    let longitude: Option<BigDecimal> = row.try_get("longitude")?;
+                    attributes.coordinates = latitude.zip(longitude).map(|(lat, lng)| GeoCoordinate::new(lat, lng));
+                }
+                PropertyField::County => {
+                    attributes.county = row.try_get("admin_division")?;
+                }
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Copy of the attributes limited to the given fields.
+    pub fn retain(&self, fields: &PropertyFields) -> Self {
+        let keep = |field| fields.contains(field);
+        Self {
+            land_use: self.land_use.clone().filter(|_| keep(PropertyField::LandUse)),
+            bedrooms: self.bedrooms.filter(|_| keep(PropertyField::Bedrooms)),
+            bathrooms: self.bathrooms.clone().filter(|_| keep(PropertyField::Bathrooms)),
+            area_sq_ft: self.area_sq_ft.filter(|_| keep(PropertyField::AreaSqFt)),
+            total_area_sq_ft: self.total_area_sq_ft.filter(|_| keep(PropertyField::TotalAreaSqFt)),
+            coordinates: self.coordinates.clone().filter(|_| keep(PropertyField::Coordinates)),
+            county: self.county.clone().filter(|_| keep(PropertyField::County)),
+        }
+    }
+
+    /// Textual value of a field, for tabular formats.
+    pub fn value_of(&self, field: PropertyField) -> Option<String> {
+        match field {
+            PropertyField::LandUse => self.land_use.as_ref().map(|v| v.to_string()),
+            PropertyField::Bedrooms => self.bedrooms.map(|v| v.to_string()),
+            PropertyField::Bathrooms => self.bathrooms.as_ref().map(|v| v.to_string()),
+            PropertyField::AreaSqFt => self.area_sq_ft.map(|v| v.to_string()),
+            PropertyField::TotalAreaSqFt => self.total_area_sq_ft.map(|v| v.to_string()),
+            PropertyField::Coordinates => self.coordinates.as_ref().map(|v| v.to_string()),
+            PropertyField::County => self.county.clone(),
+        }
+    }
+}
+
 pub struct PropertyRecordRepository;
 
 impl PropertyRecordRepository {
@@ -67,6 +369,7 @@ impl PropertyRecordRepository {
                 longitude,
                 admin_division,
                 land_use_type,
+                raw_land_use_type,
                 area_sq_ft,
                 nr_bedrooms,
                 nr_bathrooms,
@@ -108,7 +411,8 @@ impl PropertyRecordRepository {
                 ),
                 admin_division: row.admin_division,
                 geo_coordinate,
-                land_use_type: row.land_use_type.as_str().try_into()?,
+                land_use_type: LandUseType::from_code(&row.land_use_type),
+                raw_land_use_type: row.raw_land_use_type,
                 area_sq_ft: row.area_sq_ft.map(|v| v as u32),
                 nr_bedrooms: row.nr_bedrooms.map(|v| v as u8),
                 nr_bathrooms: row.nr_bathrooms,
@@ -118,6 +422,166 @@ impl PropertyRecordRepository {
         .transpose()
     }
 
+    /// Resolves each of the address criteria to the APNs of the properties it matches, in a single
+    /// query. The result pairs the position of the criteria in the given slice with a matching APN;
+    /// criteria matching no property are absent and criteria matching several properties appear
+    /// once per match.
+    #[tracing::instrument(level = "info", skip(pool, criteria), fields(nr_criteria = criteria.len()))]
+    pub async fn find_apns_for_addresses(
+        criteria: &[AddressCriteria], pool: &PgPool,
+    ) -> Result<Vec<(usize, AssessorParcelNumber)>, CoreError> {
+        if criteria.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let positions: Vec<i32> = (0..criteria.len() as i32).collect();
+        let numbers: Vec<String> = criteria.iter().map(|c| c.street_number.clone()).collect();
+        let names: Vec<String> = criteria.iter().map(|c| c.street_name.clone()).collect();
+        let suffixes: Vec<Option<String>> = criteria.iter().map(|c| c.street_suffix.clone()).collect();
+        let secondaries: Vec<Option<String>> = criteria.iter().map(|c| c.secondary_number.clone()).collect();
+        let zips: Vec<String> = criteria.iter().map(|c| c.zip_or_postal_code.to_string()).collect();
+
+        let rows: Vec<(i32, String)> = sqlx::query_as(
+            r##"
+            SELECT criteria.position, Properties.apn
+            FROM UNNEST($1::INTEGER[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[])
+                AS criteria(position, street_number, street_name, street_suffix, secondary_number, zip_or_postal_code)
+            INNER JOIN Properties
+            ON Properties.street_number = criteria.street_number
+                AND Properties.street_name = criteria.street_name
+                AND Properties.zip_or_postal_code = criteria.zip_or_postal_code
+                AND (criteria.street_suffix IS NULL OR Properties.street_suffix = criteria.street_suffix)
+                AND (criteria.secondary_number IS NULL OR Properties.secondary_number = criteria.secondary_number)
+            ORDER BY criteria.position
+            "##,
+        )
+        .bind(&positions)
+        .bind(&numbers)
+        .bind(&names)
+        .bind(&suffixes)
+        .bind(&secondaries)
+        .bind(&zips)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to resolve addresses to property APNs.")?;
+
+        let result: Result<Vec<(usize, AssessorParcelNumber)>, ValidationErrors> = rows
+            .into_iter()
+            .map(|(position, apn)| AssessorParcelNumber::new(apn).map(|apn| (position as usize, apn)))
+            .collect();
+        result.map_err(|err| err.into())
+    }
+
+    /// Retrieves the APN and address of every stored property.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_all_addresses(pool: &PgPool) -> Result<Vec<(AssessorParcelNumber, Address)>, CoreError> {
+        let records = sqlx::query_as::<_, ApnAddressRow>(
+            r##"
+            SELECT apn, street_number, street_pre_direction, street_name, street_suffix, street_post_direction,
+                secondary_designator, secondary_number, city, state_or_region, zip_or_postal_code
+            FROM Properties
+            "##,
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve all property addresses.")?;
+
+        records
+            .into_iter()
+            .map(|record| -> Result<(AssessorParcelNumber, Address), CoreError> {
+                Ok((AssessorParcelNumber::new(record.apn)?, record.address.into_address()?))
+            })
+            .collect()
+    }
+
+    /// Finds the county of each of the given properties that is loaded with a known county.
+    #[tracing::instrument(level = "info", skip(pool, apns), fields(nr_apns = apns.len()))]
+    pub async fn find_counties(
+        apns: &[AssessorParcelNumber], pool: &PgPool,
+    ) -> Result<Vec<(AssessorParcelNumber, String)>, CoreError> {
+        if apns.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let apns: Vec<String> = apns.iter().map(|apn| apn.to_string()).collect();
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r##"
+            SELECT apn, admin_division
+            FROM Properties
+            WHERE apn = ANY($1) AND admin_division IS NOT NULL
+            "##,
+        )
+        .bind(&apns)
+        .fetch_all(pool)
+        .await
+        .context("Failed to perform a query to retrieve the counties of properties.")?;
+
+        let result: Result<Vec<(AssessorParcelNumber, String)>, ValidationErrors> = rows
+            .into_iter()
+            .map(|(apn, county)| AssessorParcelNumber::new(apn).map(|apn| (apn, county)))
+            .collect();
+        result.map_err(|err| err.into())
+    }
+
+    /// Replaces the stored address of a property, such as with its standardized form.
+    #[tracing::instrument(level = "info", skip(transaction))]
+    pub async fn update_address(
+        transaction: &mut Transaction<'_, Postgres>, apn: &AssessorParcelNumber, address: &Address,
+    ) -> Result<(), CoreError> {
+        let line = &address.address_line;
+        sqlx::query(
+            r##"
+            UPDATE Properties
+            SET street_number = $2, street_pre_direction = $3, street_name = $4, street_suffix = $5,
+                street_post_direction = $6, secondary_designator = $7, secondary_number = $8, city = $9,
+                state_or_region = $10, last_updated_on = $11
+            WHERE apn = $1
+            "##,
+        )
+        .bind(apn.as_ref())
+        .bind(&line.street_number)
+        .bind(line.street_direction.prefix())
+        .bind(&line.street_name)
+        .bind(&line.street_suffix)
+        .bind(line.street_direction.suffix())
+        .bind(address.secondary_address_line.as_ref().map(|s| s.designator.clone()))
+        .bind(address.secondary_address_line.as_ref().map(|s| s.number.clone()))
+        .bind(address.city.as_ref())
+        .bind(address.state_or_region.as_ref())
+        .bind(Utc::now())
+        .execute(transaction)
+        .await
+        .context("Failed to update the address of a property record.")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn find_freshness(pool: &PgPool) -> Result<TableFreshness, CoreError> {
+        let (nr_rows, last_updated_on): (i64, Option<DateTime<Utc>>) =
+            sqlx::query_as("SELECT COUNT(*), MAX(last_updated_on) FROM Properties")
+                .fetch_one(pool)
+                .await
+                .context("Failed to perform a query to assess freshness of property records.")?;
+        Ok(TableFreshness { nr_rows, last_updated_on })
+    }
+
+    /// Freshness of property records cheap enough to assess on every readiness probe: the row count
+    /// is the planner's estimate rather than an exact count.
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn estimate_freshness(pool: &PgPool) -> Result<TableFreshness, CoreError> {
+        let (nr_rows, last_updated_on): (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+            r##"
+            SELECT GREATEST(reltuples, 0)::BIGINT, (SELECT MAX(last_updated_on) FROM Properties)
+            FROM pg_class
+            WHERE oid = 'properties'::regclass
+            "##,
+        )
+        .fetch_one(pool)
+        .await
+        .context("Failed to perform a query to estimate freshness of property records.")?;
+        Ok(TableFreshness { nr_rows, last_updated_on })
+    }
+
     #[tracing::instrument(level = "info", skip(transaction))]
     pub async fn save(transaction: &mut Transaction<'_, Postgres>, record: &Property) -> Result<Property, CoreError> {
         let now = Utc::now();
@@ -150,6 +614,7 @@ impl PropertyRecordRepository {
                 longitude,
                 admin_division,
                 land_use_type,
+                raw_land_use_type,
                 area_sq_ft,
                 nr_bedrooms,
                 nr_bathrooms,
@@ -157,7 +622,7 @@ impl PropertyRecordRepository {
                 created_on,
                 last_updated_on
             )
-            VALUES(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21 )
+            VALUES(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22 )
             RETURNING id
             "##,
             record.apn.as_ref(),
@@ -175,6 +640,7 @@ impl PropertyRecordRepository {
             geo_long,
             &record.admin_division,
             &land_use,
+            record.raw_land_use_type.as_deref(),
             record.area_sq_ft.map(|v| v as i32),
             record.nr_bedrooms.map(|v| v as i16),
             record.nr_bathrooms.as_ref(),
@@ -188,3 +654,53 @@ impl PropertyRecordRepository {
         Ok(Property { id: Some(result.id as i32), ..record.clone() })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_property_fields_parsing() {
+        let fields: PropertyFields = assert_ok!(" County,land_use,, bedrooms ".parse());
+        assert_eq!(
+            fields.iter().collect::<Vec<_>>(),
+            vec![PropertyField::LandUse, PropertyField::Bedrooms, PropertyField::County]
+        );
+        assert_eq!(
+            fields.select_list(),
+            ", Properties.land_use_type, Properties.nr_bedrooms, Properties.admin_division"
+        );
+        assert_eq!(fields, assert_ok!("bedrooms,county,land_use,county".parse()));
+
+        let empty: PropertyFields = assert_ok!("".parse());
+        assert!(empty.is_empty());
+        assert_eq!(empty.select_list(), "");
+        assert_eq!(
+            empty.with(PropertyField::Coordinates).select_list(),
+            ", Properties.latitude, Properties.longitude"
+        );
+
+        assert_err!("bedrooms,garage".parse::<PropertyFields>());
+    }
+
+    #[test]
+    fn test_property_attributes_retain() {
+        let attributes = PropertyAttributes {
+            bedrooms: Some(3),
+            county: Some("KING".to_string()),
+            ..PropertyAttributes::default()
+        };
+        let fields = PropertyFields::default().with(PropertyField::County);
+        assert_eq!(
+            attributes.retain(&fields),
+            PropertyAttributes {
+                county: Some("KING".to_string()),
+                ..PropertyAttributes::default()
+            }
+        );
+        assert_eq!(attributes.value_of(PropertyField::Bedrooms), Some("3".to_string()));
+        assert_eq!(attributes.value_of(PropertyField::LandUse), None);
+    }
+}
@@ -1,5 +1,8 @@
 use std::fmt::Debug;
 
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use settings_loader::common::database::DatabaseSettings;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -7,20 +10,84 @@ use thiserror::Error;
 
 pub mod domain;
 
+/// Row count and most recent update of a table, used to detect when loaded data has changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TableFreshness {
+    pub nr_rows: i64,
+    pub last_updated_on: Option<DateTime<Utc>>,
+}
+
+/// Upper bound on connections held by a pool, set explicitly since the pool does not report it.
+pub const POOL_MAX_CONNECTIONS: u32 = 10;
+
 #[tracing::instrument(level = "info")]
 pub async fn get_connection_pool(settings: &DatabaseSettings) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
+        .max_connections(POOL_MAX_CONNECTIONS)
         .connect_timeout(std::time::Duration::from_secs(2))
         .connect_with(settings.with_db())
         .await
 }
 
+/// Version of the latest schema migration bundled with this build.
+pub fn expected_migration_version() -> Option<i64> {
+    sqlx::migrate!("./migrations").iter().map(|m| m.version).max()
+}
+
+/// Version of the latest schema migration successfully applied to the database.
+#[tracing::instrument(level = "info", skip(pool))]
+pub async fn find_applied_migration_version(pool: &PgPool) -> Result<Option<i64>, CoreError> {
+    let (version,): (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+        .fetch_one(pool)
+        .await
+        .context("Failed to perform a query to retrieve the applied schema migration version.")?;
+    Ok(version)
+}
+
+/// Postgres channel on which the loader announces each completed load, so that servers can discard
+/// responses and indexes built from the data as it was before.
+pub const DATA_LOADED_CHANNEL: &str = "address_propensity_data_loaded";
+
+/// The kind of data a completed load stored, sent as the payload of a `DATA_LOADED_CHANNEL`
+/// notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadedData {
+    Properties,
+    Propensities,
+}
+
+impl LoadedData {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Properties => "properties",
+            Self::Propensities => "propensities",
+        }
+    }
+
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        match payload {
+            "properties" => Some(Self::Properties),
+            "propensities" => Some(Self::Propensities),
+            _ => None,
+        }
+    }
+}
+
+/// Notifies listening servers that a load of the data has completed.
+#[tracing::instrument(level = "info", skip(pool))]
+pub async fn notify_data_loaded(data: LoadedData, pool: &PgPool) -> Result<(), CoreError> {
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(DATA_LOADED_CHANNEL)
+        .bind(data.as_str())
+        .execute(pool)
+        .await
+        .context("Failed to notify servers of completed data load.")?;
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum CoreError {
-    #[error("Unrecognized land use type: {0}")]
-    UnrecognizedLandUseType(String),
-
     #[error("Failed validation: {0}")]
     ValidationError(#[from] validator::ValidationErrors),
 
@@ -1,6 +1,3 @@
-#[macro_use]
-extern crate enum_display_derive;
-
 pub mod core;
 pub mod loader;
 pub mod server;
@@ -0,0 +1,71 @@
+use crate::core::domain::property::PropertyRecordRepository;
+use crate::core::domain::AddressStandardizer;
+use crate::core::LoadedData;
+use crate::loader::errors::LoaderError;
+use crate::loader::settings::Settings;
+use crate::loader::thresholds::{LoadBatch, LoadOutcome};
+use anyhow::Context;
+use console::style;
+
+/// Rewrites the stored addresses of properties loaded before addresses were standardized into their
+/// USPS Publication 28 forms. Properties whose addresses are already standardized are left
+/// untouched, so the command may be safely rerun; with `dry_run`, the properties to rewrite are only
+/// counted.
+#[tracing::instrument(level = "info", skip(settings))]
+pub async fn standardize_addresses(dry_run: bool, settings: Settings) -> Result<LoadOutcome, LoaderError> {
+    let connection_pool = crate::core::get_connection_pool(&settings.database)
+        .await
+        .context("Failed to connect to Postgres database.")?;
+
+    let addresses = PropertyRecordRepository::find_all_addresses(&connection_pool).await?;
+    let nr_properties = addresses.len();
+    let unstandardized: Vec<_> = addresses
+        .into_iter()
+        .filter_map(|(apn, address)| {
+            let standardized = AddressStandardizer::address(&address);
+            if standardized != address {
+                tracing::debug!(%apn, %address, %standardized, "property address is not standardized");
+                Some((apn, standardized))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if dry_run {
+        eprintln!(
+            " {}",
+            style(format!(
+                "{} of {} property addresses would be standardized",
+                unstandardized.len(),
+                nr_properties
+            ))
+            .bold()
+        );
+        return Ok(LoadOutcome::Clean);
+    }
+
+    let mut batch = LoadBatch::new(&connection_pool);
+    for (apn, standardized) in unstandardized.iter() {
+        let transaction = batch.transaction().await?;
+        PropertyRecordRepository::update_address(transaction, apn, standardized).await?;
+        batch.record_saved().await?;
+    }
+    batch.commit().await?;
+
+    let nr_standardized = batch.nr_committed();
+    tracing::info!(%nr_standardized, %nr_properties, "standardized property addresses");
+    eprintln!(
+        " {}",
+        style(format!(
+            "Standardized {} of {} property addresses",
+            nr_standardized, nr_properties
+        ))
+        .bold()
+    );
+
+    if 0 < nr_standardized {
+        crate::core::notify_data_loaded(LoadedData::Properties, &connection_pool).await?;
+    }
+    Ok(LoadOutcome::Clean)
+}
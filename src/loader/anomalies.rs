@@ -0,0 +1,437 @@
+use crate::core::domain::propensity_model::PropensityModel;
+use crate::core::domain::property::PropertyRecordRepository;
+use crate::core::domain::{AssessorParcelNumber, PropertyPropensityScore};
+use crate::loader::domain::ScoreColumn;
+use crate::loader::errors::LoaderError;
+use crate::loader::propensity_loader::read_scores;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Fewest scores a zip code or county needs for its statistics to be assessed.
+const MIN_REGION_SIZE: usize = 10;
+
+/// Fewest assessed regions of a model needed to judge whether a region's scores are extreme.
+const MIN_NR_REGIONS: usize = 3;
+
+/// Modified z-score beyond which a value is an outlier (Iglewicz and Hoaglin).
+const OUTLIER_THRESHOLD: f64 = 3.5;
+
+/// Share of a region's scores that, if identical, make the region suspiciously uniform.
+const UNIFORM_SHARE: f64 = 0.9;
+
+/// Number of properties whose counties are looked up per query.
+const COUNTY_LOOKUP_CHUNK: usize = 10_000;
+
+/// Region whose scores are assessed together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RegionLevel {
+    ZipCode,
+    County,
+}
+
+impl fmt::Display for RegionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZipCode => f.write_str("zip code"),
+            Self::County => f.write_str("county"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionAnomalyKind {
+    /// Most of the region's scores are the same score.
+    Uniform { score: u16, share: f64 },
+
+    /// The region's median score is an outlier among the median scores of the model's regions.
+    Extreme { median: f64, robust_z: f64 },
+}
+
+/// A zip code or county whose scores by a model are anomalous as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionAnomaly {
+    pub model: String,
+    pub level: RegionLevel,
+    pub region: String,
+    pub nr_scores: usize,
+    pub kind: RegionAnomalyKind,
+}
+
+impl fmt::Display for RegionAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RegionAnomalyKind::Uniform { score, share } => write!(
+                f,
+                "{} {} {}: {:.0}% of {} scores are {}",
+                self.model,
+                self.level,
+                self.region,
+                100.0 * share,
+                self.nr_scores,
+                score
+            ),
+            RegionAnomalyKind::Extreme { median, robust_z } => write!(
+                f,
+                "{} {} {}: median of {} scores is {:.1} (robust z {:.1})",
+                self.model, self.level, self.region, self.nr_scores, median, robust_z
+            ),
+        }
+    }
+}
+
+/// A score that is an outlier among the model's scores in its zip code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreAnomaly {
+    /// Position of the score's record in the file.
+    pub idx: usize,
+    pub apn: AssessorParcelNumber,
+    pub model: String,
+    pub score: u16,
+    pub zip_code: String,
+    pub zip_median: f64,
+    pub robust_z: f64,
+}
+
+impl fmt::Display for ScoreAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "record[{}] {} {} score {} against a median of {:.1} in zip code {} (robust z {:.1})",
+            self.idx, self.apn, self.model, self.score, self.zip_median, self.zip_code, self.robust_z
+        )
+    }
+}
+
+/// Scores of a propensity file that are anomalous against robust statistics (median and median
+/// absolute deviation) of the scores in their zip code and county.
+#[derive(Debug, Default)]
+pub struct AnomalyReport {
+    pub nr_scores: usize,
+    pub outliers: Vec<ScoreAnomaly>,
+    pub regions: Vec<RegionAnomaly>,
+
+    /// Number of scores that are outliers or lie in an anomalous region.
+    pub nr_anomalous: usize,
+}
+
+impl AnomalyReport {
+    /// Share of the file's scores that are anomalous.
+    pub fn rate(&self) -> f64 {
+        if self.nr_scores == 0 {
+            0.0
+        } else {
+            self.nr_anomalous as f64 / self.nr_scores as f64
+        }
+    }
+
+    fn detect(scores: &[(usize, PropertyPropensityScore)], counties: &HashMap<String, String>) -> Self {
+        let mut report = Self { nr_scores: scores.len(), ..Self::default() };
+        let mut anomalous: HashSet<usize> = HashSet::new();
+
+        // group positions of the scores by model and region.
+        let mut regions: BTreeMap<(&str, RegionLevel, &str), Vec<usize>> = BTreeMap::new();
+        for (pos, (_, score)) in scores.iter().enumerate() {
+            if let Some(zip_code) = score.zip_or_postal_code.as_ref() {
+                regions
+                    .entry((score.model.as_str(), RegionLevel::ZipCode, zip_code.as_ref()))
+                    .or_default()
+                    .push(pos);
+            }
+            if let Some(county) = counties.get(score.apn.as_ref()) {
+                regions
+                    .entry((score.model.as_str(), RegionLevel::County, county.as_str()))
+                    .or_default()
+                    .push(pos);
+            }
+        }
+
+        let mut medians: BTreeMap<(&str, RegionLevel), Vec<(&str, f64, usize)>> = BTreeMap::new();
+        for ((model, level, region), positions) in regions.iter() {
+            if positions.len() < MIN_REGION_SIZE {
+                continue;
+            }
+
+            let values: Vec<f64> = positions.iter().map(|pos| scores[*pos].1.score.score as f64).collect();
+            let (median, mad) = median_and_mad(&values);
+            medians
+                .entry((*model, *level))
+                .or_default()
+                .push((*region, median, positions.len()));
+
+            if let Some((score, share)) = modal_share(positions.iter().map(|pos| scores[*pos].1.score.score)) {
+                if UNIFORM_SHARE <= share {
+                    report.regions.push(RegionAnomaly {
+                        model: model.to_string(),
+                        level: *level,
+                        region: region.to_string(),
+                        nr_scores: positions.len(),
+                        kind: RegionAnomalyKind::Uniform { score, share },
+                    });
+                    anomalous.extend(positions.iter().copied());
+                }
+            }
+
+            if *level == RegionLevel::ZipCode && 0.0 < mad {
+                for pos in positions.iter() {
+                    let (idx, score) = &scores[*pos];
+                    let z = robust_z(score.score.score as f64, median, mad);
+                    if OUTLIER_THRESHOLD < z.abs() {
+                        report.outliers.push(ScoreAnomaly {
+                            idx: *idx,
+                            apn: score.apn.clone(),
+                            model: score.model.clone(),
+                            score: score.score.score,
+                            zip_code: region.to_string(),
+                            zip_median: median,
+                            robust_z: z,
+                        });
+                        anomalous.insert(*pos);
+                    }
+                }
+            }
+        }
+
+        for ((model, level), region_medians) in medians {
+            if region_medians.len() < MIN_NR_REGIONS {
+                continue;
+            }
+
+            let values: Vec<f64> = region_medians.iter().map(|(_, median, _)| *median).collect();
+            let (median_of_medians, mad) = median_and_mad(&values);
+            if mad <= 0.0 {
+                continue;
+            }
+
+            for (region, median, nr_scores) in region_medians {
+                let z = robust_z(median, median_of_medians, mad);
+                if OUTLIER_THRESHOLD < z.abs() {
+                    report.regions.push(RegionAnomaly {
+                        model: model.to_string(),
+                        level,
+                        region: region.to_string(),
+                        nr_scores,
+                        kind: RegionAnomalyKind::Extreme { median, robust_z: z },
+                    });
+                    anomalous.extend(regions[&(model, level, region)].iter().copied());
+                }
+            }
+        }
+
+        report.nr_anomalous = anomalous.len();
+        report
+    }
+}
+
+impl fmt::Display for AnomalyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} scores ({:.1}%) are anomalous: {} outlying scores and {} anomalous regions",
+            self.nr_anomalous,
+            self.nr_scores,
+            100.0 * self.rate(),
+            self.outliers.len(),
+            self.regions.len()
+        )
+    }
+}
+
+/// Assesses the scores of a propensity file for anomalies before it is loaded. Scores are assessed
+/// within the zip code supplied with them and the county of their property, if loaded.
+#[tracing::instrument(level = "info", skip(score_columns, models, pool))]
+pub async fn assess_anomalies(
+    file: &PathBuf, score_columns: &[(usize, ScoreColumn)], models: &HashMap<String, PropensityModel>, pool: &PgPool,
+) -> Result<AnomalyReport, LoaderError> {
+    let (scores, _) = read_scores(file, score_columns, models)?;
+
+    let mut apns: Vec<AssessorParcelNumber> = scores.iter().map(|(_, score)| score.apn.clone()).collect();
+    apns.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    apns.dedup();
+    let mut counties = HashMap::with_capacity(apns.len());
+    for chunk in apns.chunks(COUNTY_LOOKUP_CHUNK) {
+        for (apn, county) in PropertyRecordRepository::find_counties(chunk, pool).await? {
+            counties.insert(apn.to_string(), county);
+        }
+    }
+
+    let report = AnomalyReport::detect(&scores, &counties);
+    tracing::info!(
+        nr_scores=%report.nr_scores, nr_anomalous=%report.nr_anomalous, nr_outliers=%report.outliers.len(),
+        nr_regions=%report.regions.len(), "assessed propensity scores for anomalies"
+    );
+    Ok(report)
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Median and median absolute deviation of a non-empty set of values.
+fn median_and_mad(values: &[f64]) -> (f64, f64) {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = median(&sorted);
+    let mut deviations: Vec<f64> = sorted.iter().map(|v| (v - median).abs()).collect();
+    deviations.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    (median, self::median(&deviations))
+}
+
+/// Modified z-score of a value, comparable to a standard score for normally distributed values.
+fn robust_z(value: f64, median: f64, mad: f64) -> f64 {
+    0.6745 * (value - median) / mad
+}
+
+/// The most common score and its share of the scores.
+fn modal_share(scores: impl Iterator<Item = u16>) -> Option<(u16, f64)> {
+    let mut counts: HashMap<u16, usize> = HashMap::new();
+    let mut nr_scores = 0;
+    for score in scores {
+        *counts.entry(score).or_default() += 1;
+        nr_scores += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|(a_score, a_count), (b_score, b_count)| a_count.cmp(b_count).then_with(|| b_score.cmp(a_score)))
+        .map(|(score, count)| (score, count as f64 / nr_scores as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::{PropensityScore, ZipOrPostalCode};
+    use claim::{assert_none, assert_ok, assert_some};
+    use pretty_assertions::assert_eq;
+
+    fn scores_in_zip(first_idx: usize, zip_code: &str, values: &[u16]) -> Vec<(usize, PropertyPropensityScore)> {
+        let zip_code = Some(assert_ok!(ZipOrPostalCode::new(zip_code)));
+        values
+            .iter()
+            .enumerate()
+            .map(|(offset, value)| {
+                let idx = first_idx + offset;
+                let apn = assert_ok!(AssessorParcelNumber::new(format!("{}", 1_000_000 + idx)));
+                let score = assert_ok!(PropensityScore::new(*value));
+                (
+                    idx,
+                    assert_ok!(PropertyPropensityScore::new(score, "heloc", &apn, &zip_code)),
+                )
+            })
+            .collect()
+    }
+
+    /// Ten scores centered on a median without any outlier or dominant score.
+    fn spread_around(median: u16) -> Vec<u16> {
+        vec![
+            median - 4,
+            median - 3,
+            median - 2,
+            median - 1,
+            median,
+            median,
+            median + 1,
+            median + 2,
+            median + 3,
+            median + 4,
+        ]
+    }
+
+    #[test]
+    fn test_robust_statistics() {
+        assert_eq!(median_and_mad(&[1.0, 2.0, 3.0, 4.0, 100.0]), (3.0, 1.0));
+        assert_eq!(median_and_mad(&[4.0, 1.0, 3.0, 2.0]), (2.5, 1.0));
+        assert_eq!(median_and_mad(&[7.0, 7.0, 7.0]), (7.0, 0.0));
+
+        assert!((robust_z(10.0, 3.0, 1.0) - 4.7215).abs() < 1e-9);
+        assert!((robust_z(1.0, 3.0, 2.0) + 0.6745).abs() < 1e-9);
+
+        assert_eq!(assert_some!(modal_share(vec![5, 7, 7, 5, 9].into_iter())), (5, 0.4));
+        assert_eq!(assert_some!(modal_share(vec![9, 9, 9, 1].into_iter())), (9, 0.75));
+        assert_none!(modal_share(std::iter::empty()));
+    }
+
+    #[test]
+    fn test_detect_outlying_scores() {
+        let mut scores = scores_in_zip(
+            0,
+            "98101",
+            &[490, 495, 497, 498, 500, 500, 500, 502, 503, 505, 510, 900],
+        );
+        // too few scores to assess, despite being uniform
+        scores.extend(scores_in_zip(100, "98102", &[100; MIN_REGION_SIZE - 1]));
+
+        let report = AnomalyReport::detect(&scores, &HashMap::new());
+        assert_eq!(report.nr_scores, 12 + MIN_REGION_SIZE - 1);
+        assert!(report.regions.is_empty());
+        assert_eq!(report.outliers.len(), 1);
+        let outlier = &report.outliers[0];
+        assert_eq!(outlier.idx, 11);
+        assert_eq!(outlier.score, 900);
+        assert_eq!(outlier.zip_code, "98101");
+        assert_eq!(outlier.zip_median, 500.0);
+        assert!(OUTLIER_THRESHOLD < outlier.robust_z);
+        assert_eq!(report.nr_anomalous, 1);
+    }
+
+    #[test]
+    fn test_detect_uniform_region() {
+        let mut values = vec![700; 9];
+        values.push(100);
+        let scores = scores_in_zip(0, "98103", &values);
+
+        let report = AnomalyReport::detect(&scores, &HashMap::new());
+        assert_eq!(report.regions.len(), 1);
+        assert_eq!(report.regions[0].region, "98103");
+        assert_eq!(report.regions[0].level, RegionLevel::ZipCode);
+        assert_eq!(
+            report.regions[0].kind,
+            RegionAnomalyKind::Uniform { score: 700, share: 0.9 }
+        );
+
+        // with no deviation around the median, outliers are not assessed
+        assert!(report.outliers.is_empty());
+        assert_eq!(report.nr_anomalous, 10);
+        assert_eq!(report.rate(), 1.0);
+    }
+
+    #[test]
+    fn test_detect_extreme_regions() {
+        let mut scores = vec![];
+        let mut counties = HashMap::new();
+        for (pos, (zip_code, county, median)) in [
+            ("98101", "KING", 500),
+            ("98201", "SNOHOMISH", 510),
+            ("98301", "PIERCE", 490),
+            ("98401", "KITSAP", 900),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let zip_scores = scores_in_zip(100 * pos, zip_code, &spread_around(*median));
+            for (_, score) in zip_scores.iter() {
+                counties.insert(score.apn.to_string(), county.to_string());
+            }
+            scores.extend(zip_scores);
+        }
+
+        let report = AnomalyReport::detect(&scores, &counties);
+        assert!(report.outliers.is_empty());
+        let regions: Vec<(RegionLevel, &str)> = report.regions.iter().map(|r| (r.level, r.region.as_str())).collect();
+        assert_eq!(
+            regions,
+            vec![(RegionLevel::ZipCode, "98401"), (RegionLevel::County, "KITSAP")]
+        );
+        assert!(matches!(
+            report.regions[0].kind,
+            RegionAnomalyKind::Extreme { median, .. } if median == 900.0
+        ));
+        assert_eq!(report.nr_anomalous, 10);
+        assert_eq!(report.nr_scores, 40);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::core::domain::api_key::{ApiKey, ApiKeyRepository};
+use crate::loader::errors::LoaderError;
+use crate::loader::settings::{ApiKeyCommand, Settings};
+use anyhow::Context;
+use console::style;
+use itertools::Itertools;
+
+#[tracing::instrument(level = "info", skip(settings))]
+pub async fn manage_api_keys(command: ApiKeyCommand, settings: Settings) -> Result<(), LoaderError> {
+    let connection_pool = crate::core::get_connection_pool(&settings.database)
+        .await
+        .context("Failed to connect to Postgres database.")?;
+
+    match command {
+        ApiKeyCommand::Create { name, scopes } => {
+            let scopes: Vec<_> = scopes.into_iter().unique().collect();
+            let (api_key, key) = ApiKeyRepository::create(name.as_str(), &scopes, &connection_pool).await?;
+            tracing::info!(api_key_id=%api_key.id, api_key_name=%api_key.name, scopes=?api_key.scopes, "created API key");
+            eprintln!(
+                " {}",
+                style(format!(
+                    "Created API key for {} with scopes: {}",
+                    api_key.name,
+                    api_key.scopes.iter().join(", ")
+                ))
+                .bold()
+            );
+            eprintln!(
+                " {}",
+                style("Store this key now; it cannot be retrieved later:").yellow()
+            );
+            println!("{}", key);
+        }
+
+        ApiKeyCommand::List => {
+            let api_keys = ApiKeyRepository::find_all(&connection_pool).await?;
+            eprintln!(" {}", style(format!("{} API keys", api_keys.len())).bold());
+            for api_key in api_keys {
+                println!("{}", describe(&api_key));
+            }
+        }
+
+        ApiKeyCommand::Revoke { name } => {
+            let nr_revoked = ApiKeyRepository::revoke(name.as_str(), &connection_pool).await?;
+            tracing::info!(api_key_name=%name, %nr_revoked, "revoked API keys");
+            eprintln!(
+                " {}",
+                style(format!("Revoked {} API keys for {}", nr_revoked, name)).bold()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn describe(api_key: &ApiKey) -> String {
+    let status = match api_key.revoked_on {
+        Some(revoked_on) => format!("revoked {}", revoked_on.to_rfc3339()),
+        None => "active".to_string(),
+    };
+
+    format!(
+        "{}\t{}\t{}...\t{}\tcreated {}\t{}",
+        api_key.id,
+        api_key.name,
+        api_key.key_prefix,
+        api_key.scopes.iter().join(","),
+        api_key.created_on.to_rfc3339(),
+        status
+    )
+}
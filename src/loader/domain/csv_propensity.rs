@@ -1,8 +1,14 @@
 use super::RE_APN;
-use crate::core::domain::{AssessorParcelNumber, PropensityScore, PropertyPropensityScore, ZipOrPostalCode};
+use crate::core::domain::propensity_model::DEFAULT_MODEL;
+use crate::core::domain::{
+    AddressLine, AddressStandardizer, AssessorParcelNumber, PropensityScore, PropertyPropensityScore, StreetDirection,
+    ZipOrPostalCode,
+};
 use crate::loader::errors::LoaderError;
+use csv::StringRecord;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
+use std::str::FromStr;
 use validator::Validate;
 
 #[derive(Debug, Validate, Clone, PartialEq, Serialize, Deserialize)]
@@ -43,29 +49,142 @@ pub struct CsvPropertyPropensityScore {
     #[serde(default, alias = "SitusZIP5")]
     pub zip_or_postal_code: Option<String>,
 
-    #[serde(default, alias = "HomeEquityIntelScore_LineofCredit")]
-    #[validate(range(min = 0))]
-    pub propensity_score: Option<u16>,
+    /// Scores read from the record's score columns, one per mapped model.
+    #[serde(skip_deserializing)]
+    pub scores: Vec<CsvModelScore>,
 }
 
-impl TryInto<Option<PropertyPropensityScore>> for CsvPropertyPropensityScore {
+/// A score read from a score column of a propensity record, along with the model that produced it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsvModelScore {
+    pub model: String,
+    pub score: Option<u16>,
+}
+
+/// Maps a score column of a propensity file to the registered model that produced its scores; given
+/// on the command line as `COLUMN=MODEL`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreColumn {
+    pub column: String,
+    pub model: String,
+}
+
+impl ScoreColumn {
+    /// Score column of the propensity files loaded before models were registered.
+    pub const DEFAULT_COLUMN: &'static str = "HomeEquityIntelScore_LineofCredit";
+
+    pub fn default_columns() -> Vec<Self> {
+        vec![Self {
+            column: Self::DEFAULT_COLUMN.to_string(),
+            model: DEFAULT_MODEL.to_string(),
+        }]
+    }
+
+    /// Finds the position of each score column among a file's headers.
+    pub fn locate(columns: Vec<Self>, headers: &StringRecord) -> Result<Vec<(usize, Self)>, LoaderError> {
+        columns
+            .into_iter()
+            .map(
+                |column| match headers.iter().position(|header| header.trim() == column.column) {
+                    Some(position) => Ok((position, column)),
+                    None => Err(LoaderError::InvalidScoreColumn(format!(
+                        "score column {} not found in file",
+                        column.column
+                    ))),
+                },
+            )
+            .collect()
+    }
+}
+
+impl FromStr for ScoreColumn {
+    type Err = LoaderError;
+
+    fn from_str(mapping: &str) -> Result<Self, Self::Err> {
+        let mut parts = mapping.splitn(2, '=').map(str::trim);
+        match (parts.next(), parts.next()) {
+            (Some(column), Some(model)) if !column.is_empty() && !model.is_empty() => Ok(Self {
+                column: column.to_string(),
+                model: model.to_lowercase(),
+            }),
+            _ => Err(LoaderError::InvalidScoreColumn(format!(
+                "{} is not of the form COLUMN=MODEL",
+                mapping
+            ))),
+        }
+    }
+}
+
+impl TryInto<Vec<PropertyPropensityScore>> for CsvPropertyPropensityScore {
     type Error = LoaderError;
 
-    fn try_into(self) -> Result<Option<PropertyPropensityScore>, Self::Error> {
-        self.propensity_score
-            .map(|score| {
+    fn try_into(self) -> Result<Vec<PropertyPropensityScore>, Self::Error> {
+        let apn = self.extract_apn()?;
+        let zip_or_postal_code = self.extract_zip_or_postal_code()?;
+        self.scores
+            .iter()
+            .filter_map(|model_score| model_score.score.map(|score| (&model_score.model, score)))
+            .map(|(model, score)| {
                 Ok(PropertyPropensityScore {
                     id: None,
-                    apn: self.extract_apn()?,
-                    zip_or_postal_code: self.extract_zip_or_postal_code()?,
+                    apn: apn.clone(),
+                    model: model.clone(),
+                    zip_or_postal_code: zip_or_postal_code.clone(),
                     score: PropensityScore::new(score)?,
                 })
             })
-            .transpose()
+            .collect()
     }
 }
 
 impl CsvPropertyPropensityScore {
+    /// Deserializes a propensity record, reading its scores from the located score columns.
+    pub fn from_record(
+        record: &StringRecord, headers: &StringRecord, score_columns: &[(usize, ScoreColumn)],
+    ) -> Result<Self, LoaderError> {
+        let mut csv_score: Self = record.deserialize(Some(headers))?;
+        csv_score.scores = score_columns
+            .iter()
+            .map(|(position, column)| {
+                let score = record
+                    .get(*position)
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(|value| {
+                        value.parse::<u16>().map_err(|_| LoaderError::UnparseableScore {
+                            column: column.column.clone(),
+                            value: value.to_string(),
+                        })
+                    })
+                    .transpose()?;
+                Ok(CsvModelScore { model: column.model.clone(), score })
+            })
+            .collect::<Result<_, LoaderError>>()?;
+        Ok(csv_score)
+    }
+
+    /// Whether the record carries a score from any of its models.
+    pub fn has_scores(&self) -> bool {
+        self.scores.iter().any(|model_score| model_score.score.is_some())
+    }
+
+    /// The record's situs street line, standardized, if the record has a street number and name.
+    pub fn extract_address_line(&self) -> Option<AddressLine> {
+        let present = |field: &Option<String>| field.as_ref().filter(|value| !value.trim().is_empty()).cloned();
+        let street_number = present(&self.street_number)?;
+        let street_name = present(&self.street_name)?;
+        let address_line = AddressLine::new(
+            street_number,
+            street_name,
+            present(&self.street_suffix).unwrap_or_default(),
+            StreetDirection::new(
+                present(&self.street_pre_direction),
+                present(&self.street_post_direction),
+            ),
+        );
+        Some(AddressStandardizer::address_line(&address_line))
+    }
+
     fn extract_apn(&self) -> Result<AssessorParcelNumber, LoaderError> {
         AssessorParcelNumber::new(&self.apn).map_err(|err| err.into())
     }
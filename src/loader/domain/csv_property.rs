@@ -1,13 +1,12 @@
 use super::RE_APN;
 use crate::core::domain::property::Property;
 use crate::core::domain::{
-    Address, AddressLine, AssessorParcelNumber, City, GeoCoordinate, LandUseType, SecondaryAddressLine, StateOrRegion,
-    StreetDirection, ZipOrPostalCode,
+    Address, AddressLine, AddressStandardizer, AssessorParcelNumber, City, GeoCoordinate, LandUseClassifier,
+    LandUseType, SecondaryAddressLine, StateOrRegion, StreetDirection, ZipOrPostalCode,
 };
 use crate::loader::errors::LoaderError;
 use bigdecimal::{BigDecimal, FromPrimitive};
 use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
 use validator::Validate;
 
 #[derive(Debug, Validate, Clone, PartialEq, Serialize, Deserialize)]
@@ -71,26 +70,24 @@ pub struct CsvProperty {
     pub total_area_sq_ft: Option<u32>,
 }
 
-impl TryInto<Property> for CsvProperty {
-    type Error = LoaderError;
-
-    fn try_into(self) -> Result<Property, Self::Error> {
+impl CsvProperty {
+    /// Converts the record into a property, classifying its land use.
+    pub fn into_property(self, land_uses: &mut LandUseClassifier) -> Result<Property, LoaderError> {
         Ok(Property {
             id: None,
             apn: self.extract_apn()?,
             address: self.extract_address()?,
             admin_division: self.extract_admin_division()?,
             geo_coordinate: self.extract_geo_coordinate()?,
-            land_use_type: self.extract_land_use_type()?,
+            land_use_type: self.extract_land_use_type(land_uses),
+            raw_land_use_type: self.extract_raw_land_use_type(),
             area_sq_ft: self.extract_area_sq_ft()?,
             nr_bedrooms: self.extract_nr_bedrooms()?,
             nr_bathrooms: self.extract_nr_bathrooms()?,
             total_area_sq_ft: self.extract_total_area_sq_ft()?,
         })
     }
-}
 
-impl CsvProperty {
     fn extract_apn(&self) -> Result<AssessorParcelNumber, LoaderError> {
         AssessorParcelNumber::new(&self.apn).map_err(|err| err.into())
     }
@@ -109,13 +106,14 @@ impl CsvProperty {
             .zip(self.secondary_number.as_ref())
             .map(|(d, n)| SecondaryAddressLine::new(d, n));
 
-        Ok(Address::new_in_usa(
+        let address = Address::new_in_usa(
             address_line,
             secondary_line,
             City::new(&self.city),
             StateOrRegion::new(&self.state_or_region),
             ZipOrPostalCode::new(&self.zip_or_postal_code)?,
-        ))
+        );
+        Ok(AddressStandardizer::address(&address))
     }
 
     fn extract_admin_division(&self) -> Result<String, LoaderError> {
@@ -130,10 +128,15 @@ impl CsvProperty {
             .map(|(latitude, longitude)| GeoCoordinate { latitude, longitude }))
     }
 
-    #[tracing::instrument(level = "debug")]
-    fn extract_land_use_type(&self) -> Result<LandUseType, LoaderError> {
-        let land_use_type = self.land_use_type.as_str().try_into()?;
-        Ok(land_use_type)
+    #[tracing::instrument(level = "debug", skip(land_uses))]
+    fn extract_land_use_type(&self, land_uses: &mut LandUseClassifier) -> LandUseType {
+        land_uses.classify(&self.land_use_type)
+    }
+
+    fn extract_raw_land_use_type(&self) -> Option<String> {
+        Some(self.land_use_type.trim())
+            .filter(|raw| !raw.is_empty())
+            .map(|raw| raw.to_string())
     }
 
     fn extract_area_sq_ft(&self) -> Result<Option<u32>, LoaderError> {
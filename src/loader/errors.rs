@@ -19,9 +19,30 @@ pub enum LoaderError {
     #[error("{0}")]
     RepositoryError(#[from] sqlx::Error),
 
-    #[error("Unrecognized land use type: {0}")]
-    UnrecognizedLandUseType(String),
+    #[error("Invalid score column: {0}")]
+    InvalidScoreColumn(String),
+
+    #[error("Unparseable score in column {column}: {value}")]
+    UnparseableScore { column: String, value: String },
+
+    #[error("Load aborted: {percent:.1}% of scores are anomalous, exceeding the maximum of {max_percent:.1}%")]
+    AnomalyRateExceeded { percent: f64, max_percent: f64 },
+
+    #[error("Load aborted: {0}")]
+    ThresholdExceeded(#[from] crate::loader::thresholds::ThresholdBreach),
 
     #[error("{0}")]
     LoaderError(#[from] anyhow::Error),
 }
+
+impl LoaderError {
+    /// Process exit code reporting the error: whether a load was aborted by a threshold or failed.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ThresholdExceeded(_) | Self::AnomalyRateExceeded { .. } => {
+                crate::loader::thresholds::EXIT_ABORTED_BY_THRESHOLD
+            }
+            _ => crate::loader::thresholds::EXIT_FATAL,
+        }
+    }
+}
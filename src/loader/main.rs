@@ -1,5 +1,9 @@
+use address_propensity::loader::errors::LoaderError;
 use address_propensity::loader::settings::{LoaderCliOptions, Settings, SubCommand};
-use address_propensity::loader::{propensity_loader, property_loader};
+use address_propensity::loader::thresholds::LoadOutcome;
+use address_propensity::loader::{
+    address_standardization, api_keys, models, propensity_drift, propensity_loader, property_loader,
+};
 use address_propensity::tracing::{get_subscriber, init_subscriber};
 use clap::Clap;
 use settings_loader::SettingsLoader;
@@ -16,10 +20,58 @@ async fn main() {
     tracing::info!(?options, "Options parsed");
     let command = options.sub_command.clone();
     let command_label = format!("{}", command);
-    let settings = Settings::load(options).expect("failed to load settings");
+    let outcome = match Settings::load(options) {
+        Ok(settings) => run(command, settings).await,
+        Err(err) => Err(LoaderError::from(err)),
+    };
+
+    let exit_code = match outcome {
+        Ok(outcome) => {
+            tracing::info!(?outcome, "{} completed", command_label);
+            outcome.exit_code()
+        }
+        Err(err) => {
+            tracing::error!(error=?err, "failure in {} loading", command_label);
+            eprintln!("failure in {} loading: {}", command_label, err);
+            err.exit_code()
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+async fn run(command: SubCommand, settings: Settings) -> Result<LoadOutcome, LoaderError> {
     match command {
-        SubCommand::Property { file } => property_loader::load_property_data(file, settings).await,
-        SubCommand::Propensity { file } => propensity_loader::load_propensity_data(file, settings).await,
+        SubCommand::Property { file, land_use_taxonomy, thresholds } => {
+            property_loader::load_property_data(file, land_use_taxonomy, thresholds, settings).await
+        }
+        SubCommand::Propensity {
+            file,
+            effective_on,
+            source,
+            scores,
+            max_anomaly_rate,
+            thresholds,
+        } => {
+            propensity_loader::load_propensity_data(
+                file,
+                effective_on,
+                source,
+                scores,
+                max_anomaly_rate,
+                thresholds,
+                settings,
+            )
+            .await
+        }
+        SubCommand::Standardize { dry_run } => address_standardization::standardize_addresses(dry_run, settings).await,
+        SubCommand::Diff { file, scores, movers, output, histogram_dir } => {
+            propensity_drift::report_score_drift(file, scores, movers, output, histogram_dir, settings)
+                .await
+                .map(|_| LoadOutcome::Clean)
+        }
+        SubCommand::Model { command } => models::manage_models(command, settings).await.map(|_| LoadOutcome::Clean),
+        SubCommand::ApiKey { command } => api_keys::manage_api_keys(command, settings)
+            .await
+            .map(|_| LoadOutcome::Clean),
     }
-    .expect(format!("failure in {} loading", command_label).as_str());
 }
@@ -1,8 +1,14 @@
+pub mod address_standardization;
+pub mod anomalies;
+pub mod api_keys;
 pub mod domain;
 pub mod errors;
+pub mod models;
+pub mod propensity_drift;
 pub mod propensity_loader;
 pub mod property_loader;
 pub mod settings;
+pub mod thresholds;
 
 // use  serde::de::DeserializeOwned;
 // use crate::loader::errors::LoaderError;
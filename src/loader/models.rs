@@ -0,0 +1,138 @@
+use crate::core::domain::propensity_composite::{CompositeModel, PropensityCompositeRepository};
+use crate::core::domain::propensity_model::{PropensityModel, PropensityModelRepository, ScoreScale};
+use crate::core::domain::DomainError;
+use crate::loader::errors::LoaderError;
+use crate::loader::settings::{ModelCommand, Settings};
+use anyhow::Context;
+use chrono::Utc;
+use console::style;
+
+#[tracing::instrument(level = "info", skip(settings))]
+pub async fn manage_models(command: ModelCommand, settings: Settings) -> Result<(), LoaderError> {
+    let connection_pool = crate::core::get_connection_pool(&settings.database)
+        .await
+        .context("Failed to connect to Postgres database.")?;
+
+    match command {
+        ModelCommand::Register {
+            name,
+            min_score,
+            max_score,
+            direction,
+            tiers,
+            description,
+        } => {
+            let scale = ScoreScale::new(min_score, max_score, direction).map_err(anyhow::Error::from)?;
+            let model = PropensityModel::new(name, scale, tiers, description).map_err(anyhow::Error::from)?;
+            let model = PropensityModelRepository::register(&model, &connection_pool).await?;
+            tracing::info!(?model, "registered propensity model");
+            eprintln!(
+                " {}",
+                style(format!(
+                    "Registered propensity model {} scoring {} to {} ({} is better) with {} tiers",
+                    model.name,
+                    model.scale.min_score,
+                    model.scale.max_score,
+                    model.scale.direction,
+                    model.tiers.len()
+                ))
+                .bold()
+            );
+        }
+
+        ModelCommand::List => {
+            let models = PropensityModelRepository::find_all(&connection_pool).await?;
+            eprintln!(" {}", style(format!("{} propensity models", models.len())).bold());
+            for model in models {
+                println!("{}", describe(&model));
+            }
+
+            let composites = PropensityCompositeRepository::find_all(&connection_pool).await?;
+            if !composites.is_empty() {
+                eprintln!(" {}", style(format!("{} composite models", composites.len())).bold());
+                for composite in composites {
+                    println!("{}", composite);
+                }
+            }
+        }
+
+        ModelCommand::Composite { name, components, normalization, within } => {
+            let composite =
+                CompositeModel::new(name, components, normalization, within).map_err(anyhow::Error::from)?;
+            let composite = PropensityCompositeRepository::define(&composite, &connection_pool).await?;
+            tracing::info!(?composite, "defined composite model");
+            eprintln!(" {}", style(format!("Defined composite model {}", composite)).bold());
+        }
+
+        ModelCommand::Materialize { names, effective_on } => {
+            let effective_on = effective_on.unwrap_or_else(|| Utc::today().naive_utc());
+            let composites = PropensityCompositeRepository::find_all(&connection_pool).await?;
+            let composites = select_composites(composites, &names)?;
+            let mut nr_materialized = 0;
+            for composite in composites {
+                let model = PropensityModelRepository::find(&composite.model, &connection_pool)
+                    .await?
+                    .ok_or_else(|| {
+                        anyhow::Error::from(DomainError::UnrecognizedPropensityModel(composite.model.clone()))
+                    })?;
+                let nr_scores =
+                    PropensityCompositeRepository::materialize(&composite, &model, effective_on, &connection_pool)
+                        .await?;
+                tracing::info!(composite=%composite.model, %nr_scores, %effective_on, "materialized composite scores");
+                eprintln!(
+                    " {}",
+                    style(format!(
+                        "Materialized {} {} scores effective on {}",
+                        nr_scores, composite.model, effective_on
+                    ))
+                    .bold()
+                );
+                nr_materialized += nr_scores;
+            }
+
+            if 0 < nr_materialized {
+                crate::core::notify_data_loaded(crate::core::LoadedData::Propensities, &connection_pool).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Selects the named composites, or every composite if none are named.
+fn select_composites(composites: Vec<CompositeModel>, names: &[String]) -> Result<Vec<CompositeModel>, LoaderError> {
+    if names.is_empty() {
+        return Ok(composites);
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            let name = name.trim().to_lowercase();
+            composites
+                .iter()
+                .find(|composite| composite.model == name)
+                .cloned()
+                .ok_or_else(|| {
+                    anyhow::Error::from(DomainError::InvalidComposite(format!(
+                        "{} is not a composite model",
+                        name
+                    )))
+                    .into()
+                })
+        })
+        .collect()
+}
+
+fn describe(model: &PropensityModel) -> String {
+    let tiers: Vec<String> = model.tiers.iter().map(|tier| tier.to_string()).collect();
+    format!(
+        "{}\t{}-{}\t{}\t{}\t{}",
+        model.name,
+        model.scale.min_score,
+        model.scale.max_score,
+        model.scale.direction,
+        tiers.join(", "),
+        model.description
+    )
+}
@@ -0,0 +1,275 @@
+use crate::core::domain::propensity_model::PropensityModel;
+use crate::core::domain::{LocatedScore, PropertyPropensityScore, PropertyPropensityScoreRepository};
+use crate::loader::domain::ScoreColumn;
+use crate::loader::errors::LoaderError;
+use crate::loader::propensity_loader::{draw_histogram, find_models, read_scores};
+use crate::loader::settings::Settings;
+use anyhow::Context;
+use console::style;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const UNKNOWN_REGION: &str = "(unknown)";
+
+/// A score of the incoming file compared with the property's stored score by the same model.
+#[derive(Debug, Clone, PartialEq)]
+struct ScoreDelta {
+    apn: String,
+    zip_code: Option<String>,
+    county: Option<String>,
+    stored: i32,
+    incoming: i32,
+}
+
+impl ScoreDelta {
+    fn delta(&self) -> i32 {
+        self.incoming - self.stored
+    }
+}
+
+/// Distribution of score deltas across a set of compared scores.
+#[derive(Debug, Clone, PartialEq)]
+struct DeltaSummary {
+    count: usize,
+    mean: f64,
+    std_dev: f64,
+    min: i32,
+    p25: i32,
+    median: i32,
+    p75: i32,
+    max: i32,
+}
+
+impl DeltaSummary {
+    fn of(mut deltas: Vec<i32>) -> Option<Self> {
+        if deltas.is_empty() {
+            return None;
+        }
+
+        deltas.sort_unstable();
+        let count = deltas.len();
+        let mean = deltas.iter().map(|d| *d as f64).sum::<f64>() / count as f64;
+        let variance = deltas.iter().map(|d| (*d as f64 - mean).powi(2)).sum::<f64>() / count as f64;
+        let quantile = |q: f64| deltas[((count - 1) as f64 * q).round() as usize];
+        Some(Self {
+            count,
+            mean,
+            std_dev: variance.sqrt(),
+            min: deltas[0],
+            p25: quantile(0.25),
+            median: quantile(0.5),
+            p75: quantile(0.75),
+            max: deltas[count - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for DeltaSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\t{:.2}\t{:.2}\t{}\t{}\t{}\t{}\t{}",
+            self.count, self.mean, self.std_dev, self.min, self.p25, self.median, self.p75, self.max
+        )
+    }
+}
+
+const SUMMARY_HEADER: &str = "compared\tmean\tstd_dev\tmin\tp25\tmedian\tp75\tmax";
+
+/// How the scores of a model in an incoming file differ from those stored.
+#[derive(Debug, Default)]
+struct ModelDrift {
+    nr_new: usize,
+    nr_removed: usize,
+    compared: Vec<ScoreDelta>,
+}
+
+impl ModelDrift {
+    fn compare(mut incoming: HashMap<String, PropertyPropensityScore>, stored: Vec<LocatedScore>) -> Self {
+        let mut drift = Self::default();
+        for stored in stored {
+            match incoming.remove(stored.apn.as_ref()) {
+                Some(score) => drift.compared.push(ScoreDelta {
+                    apn: stored.apn.to_string(),
+                    zip_code: score
+                        .zip_or_postal_code
+                        .map(|z| z.as_ref().to_string())
+                        .or(stored.zip_code),
+                    county: stored.county,
+                    stored: stored.score.score as i32,
+                    incoming: score.score.score as i32,
+                }),
+                None => drift.nr_removed += 1,
+            }
+        }
+        drift.nr_new = incoming.len();
+        drift
+    }
+
+    fn nr_changed(&self) -> usize {
+        self.compared.iter().filter(|d| d.delta() != 0).count()
+    }
+
+    fn deltas(&self) -> Vec<i32> {
+        self.compared.iter().map(|d| d.delta()).collect()
+    }
+
+    /// Summarizes the deltas of compared scores grouped by the given region of each score.
+    fn summarize_by<'a>(
+        &'a self, region: impl Fn(&'a ScoreDelta) -> Option<&'a str>,
+    ) -> BTreeMap<&'a str, DeltaSummary> {
+        let mut groups: BTreeMap<&str, Vec<i32>> = BTreeMap::new();
+        for delta in self.compared.iter() {
+            groups
+                .entry(region(delta).unwrap_or(UNKNOWN_REGION))
+                .or_default()
+                .push(delta.delta());
+        }
+        groups
+            .into_iter()
+            .filter_map(|(region, deltas)| DeltaSummary::of(deltas).map(|summary| (region, summary)))
+            .collect()
+    }
+
+    /// The compared scores that moved furthest, largest first.
+    fn largest_movers(&self, n: usize) -> Vec<&ScoreDelta> {
+        let mut movers: Vec<&ScoreDelta> = self.compared.iter().filter(|d| d.delta() != 0).collect();
+        movers.sort_by(|a, b| b.delta().abs().cmp(&a.delta().abs()).then_with(|| a.apn.cmp(&b.apn)));
+        movers.truncate(n);
+        movers
+    }
+}
+
+/// Compares the scores of a propensity file with the stored scores of the same models without
+/// loading it, reporting how much the file would move scores if it were loaded. The report is written
+/// to `output`, apart from the logs, and each model's histogram of deltas is saved in
+/// `histogram_dir`.
+#[tracing::instrument(level = "info", skip(settings))]
+pub async fn report_score_drift(
+    file: PathBuf, score_columns: Vec<ScoreColumn>, nr_movers: usize, output: PathBuf, histogram_dir: Option<PathBuf>,
+    settings: Settings,
+) -> Result<(), LoaderError> {
+    let connection_pool = crate::core::get_connection_pool(&settings.database)
+        .await
+        .context("Failed to connect to Postgres database.")?;
+
+    let score_columns = if score_columns.is_empty() {
+        ScoreColumn::default_columns()
+    } else {
+        score_columns
+    };
+    let headers = csv::Reader::from_path(&file)?.headers()?.clone();
+    let score_columns = ScoreColumn::locate(score_columns, &headers)?;
+    let models = find_models(&score_columns, &connection_pool).await?;
+
+    eprintln!(
+        " {}...",
+        style(format!("Reading propensity scores from {:?}", file)).bold()
+    );
+    let (scores, nr_unreadable) = read_scores(&file, &score_columns, &models)?;
+    let mut incoming: HashMap<String, HashMap<String, PropertyPropensityScore>> = HashMap::new();
+    for (_, score) in scores {
+        incoming
+            .entry(score.model.clone())
+            .or_default()
+            .insert(score.apn.to_string(), score);
+    }
+    if 0 < nr_unreadable {
+        eprintln!(
+            " {}",
+            style(format!(
+                "{} unreadable records or out of scale scores were not compared",
+                nr_unreadable
+            ))
+            .bold()
+        );
+    }
+
+    let histogram_dir = histogram_dir.unwrap_or_default();
+    let mut report =
+        BufWriter::new(File::create(&output).with_context(|| format!("Unable to create drift report {:?}", output))?);
+    let mut models: Vec<&PropensityModel> = models.values().collect();
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    for model in models {
+        let stored = PropertyPropensityScoreRepository::find_located_scores(&model.name, &connection_pool).await?;
+        let drift = ModelDrift::compare(incoming.remove(&model.name).unwrap_or_default(), stored);
+        report_model_drift(model, &drift, nr_movers, &mut report, &histogram_dir)?;
+    }
+    report
+        .flush()
+        .with_context(|| format!("Unable to write drift report {:?}", output))?;
+    eprintln!(
+        " {}.",
+        style(format!("Score drift report was saved to {:?}", output)).bold()
+    );
+
+    Ok(())
+}
+
+fn report_model_drift(
+    model: &PropensityModel, drift: &ModelDrift, nr_movers: usize, report: &mut impl Write, histogram_dir: &Path,
+) -> anyhow::Result<()> {
+    let nr_changed = drift.nr_changed();
+    tracing::info!(
+        model=%model.name, nr_new=%drift.nr_new, nr_removed=%drift.nr_removed, %nr_changed,
+        nr_compared=%drift.compared.len(), "propensity score drift"
+    );
+    eprintln!(
+        " {}",
+        style(format!(
+            "Model {}: {} new, {} removed, {} changed and {} unchanged scores",
+            model.name,
+            drift.nr_new,
+            drift.nr_removed,
+            nr_changed,
+            drift.compared.len() - nr_changed
+        ))
+        .bold()
+    );
+
+    let deltas = drift.deltas();
+    let summary = match DeltaSummary::of(deltas.clone()) {
+        Some(summary) => summary,
+        None => return Ok(()),
+    };
+    writeln!(report, "model\t{}", SUMMARY_HEADER)?;
+    writeln!(report, "{}\t{}", model.name, summary)?;
+
+    eprintln!(" {}", style(format!("{} score deltas by zip code", model.name)).bold());
+    writeln!(report, "zip_code\t{}", SUMMARY_HEADER)?;
+    for (zip_code, summary) in drift.summarize_by(|d| d.zip_code.as_deref()) {
+        writeln!(report, "{}\t{}", zip_code, summary)?;
+    }
+
+    eprintln!(" {}", style(format!("{} score deltas by county", model.name)).bold());
+    writeln!(report, "county\t{}", SUMMARY_HEADER)?;
+    for (county, summary) in drift.summarize_by(|d| d.county.as_deref()) {
+        writeln!(report, "{}\t{}", county, summary)?;
+    }
+
+    eprintln!(" {}", style(format!("{} largest movers", model.name)).bold());
+    writeln!(report, "apn\tzip_code\tcounty\tstored\tincoming\tdelta")?;
+    for mover in drift.largest_movers(nr_movers) {
+        writeln!(
+            report,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            mover.apn,
+            mover.zip_code.as_deref().unwrap_or_default(),
+            mover.county.as_deref().unwrap_or_default(),
+            mover.stored,
+            mover.incoming,
+            mover.delta()
+        )?;
+    }
+
+    let histogram = histogram_dir.join(format!("propensity_score_drift_{}.png", model.name));
+    draw_histogram(
+        &histogram.to_string_lossy(),
+        &format!("Score Drift of {}", model.name),
+        "Score Delta",
+        summary.min..(summary.max + 1),
+        &deltas,
+    )
+}
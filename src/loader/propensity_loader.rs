@@ -1,38 +1,73 @@
-use crate::core::domain::property::PropertyRecordRepository;
+use crate::core::domain::propensity_model::{PropensityModel, PropensityModelRepository};
+use crate::core::domain::property::{Property, PropertyRecordRepository};
 use crate::core::domain::{
-    PropensityScore, PropertyPropensityScore, PropertyPropensityScoreRepository, ZipOrPostalCode,
+    AddressStandardizer, PropensityScore, PropensityScoreVersion, PropertyPropensityScore,
+    PropertyPropensityScoreRepository, ZipOrPostalCode,
 };
-use crate::loader::domain::CsvPropertyPropensityScore;
+use crate::loader::anomalies::{self, AnomalyReport};
+use crate::loader::domain::{CsvPropertyPropensityScore, ScoreColumn};
 use crate::loader::errors::LoaderError;
 use crate::loader::settings::Settings;
+use crate::loader::thresholds::{AbortThresholds, LoadBatch, LoadOutcome, ThresholdBreach};
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
 use console::style;
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 use plotters::prelude::*;
-use sqlx::PgPool;
-use std::collections::HashMap;
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::ops::Range;
 use std::path::PathBuf;
-use validator::{Validate, ValidationErrors};
+use validator::{Validate, ValidationError, ValidationErrors};
 
 #[derive(Default)]
 struct QualityMeasure {
     pub propensity_zips: Vec<(PropensityScore, Option<ZipOrPostalCode>)>,
+
+    /// Loaded scores by the name of their model, whose scales may differ.
+    pub model_scores: HashMap<String, Vec<i32>>,
     pub deserialization_failures: Vec<(usize, anyhow::Error)>,
     pub validation_failures: Vec<(usize, ValidationErrors)>,
     pub save_failures: Vec<(CsvPropertyPropensityScore, anyhow::Error)>,
     pub missing_scores: Vec<usize>,
+
+    /// Records with any of the failures above, each counted once however many of its scores failed.
+    pub rejected_records: HashSet<usize>,
     pub not_in_core_properties: Vec<PropertyPropensityScore>,
+
+    /// Records whose standardized situs street line differs from that of their core property.
+    pub address_mismatches: Vec<usize>,
+    pub anomalies: AnomalyReport,
+}
+
+impl QualityMeasure {
+    fn tally_score(&mut self, record: &PropertyPropensityScore) {
+        self.propensity_zips
+            .push((record.score, record.zip_or_postal_code.clone()));
+        self.model_scores
+            .entry(record.model.clone())
+            .or_default()
+            .push(record.score.score as i32);
+    }
+
+    fn reject(&mut self, index: usize) {
+        self.rejected_records.insert(index);
+    }
+
+    /// Number of records rejected in whole or in part by the load. Anomalous scores are loaded, so
+    /// are not rejects.
+    fn nr_rejects(&self) -> usize {
+        self.rejected_records.len()
+    }
 }
 
 impl fmt::Debug for QualityMeasure {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let nr_issues = self.deserialization_failures.len()
-            + self.validation_failures.len()
-            + self.save_failures.len()
-            + self.missing_scores.len();
+        let nr_issues = self.nr_rejects() + self.anomalies.nr_anomalous;
 
         let mut result =
             f.write_str(format!("{} issues found{}", nr_issues, if 0 < nr_issues { ":" } else { "" }).as_str());
@@ -52,6 +87,16 @@ impl fmt::Debug for QualityMeasure {
             if !self.missing_scores.is_empty() {
                 result = f.write_str(format!("\n\t{} missing scores", self.missing_scores.len()).as_str());
             }
+            if 0 < self.anomalies.nr_anomalous {
+                result = f.write_str(
+                    format!(
+                        "\n\t{} anomalous scores (but still loaded) in {} anomalous zip codes or counties",
+                        self.anomalies.nr_anomalous,
+                        self.anomalies.regions.len()
+                    )
+                    .as_str(),
+                );
+            }
             if !self.not_in_core_properties.is_empty() {
                 result = f.write_str(
                     format!(
@@ -61,6 +106,15 @@ impl fmt::Debug for QualityMeasure {
                     .as_str(),
                 );
             }
+            if !self.address_mismatches.is_empty() {
+                result = f.write_str(
+                    format!(
+                        "\n\t{} addresses differ from their core property's (but still loaded)",
+                        self.address_mismatches.len()
+                    )
+                    .as_str(),
+                );
+            }
 
             result
         } else {
@@ -69,9 +123,38 @@ impl fmt::Debug for QualityMeasure {
     }
 }
 
+/// Provenance recorded with each version of a score loaded from a file.
+#[derive(Debug, Clone, PartialEq)]
+struct ScoreProvenance {
+    effective_on: NaiveDate,
+    source: String,
+}
+
+impl ScoreProvenance {
+    fn new(file: &PathBuf, effective_on: Option<NaiveDate>, source: Option<String>) -> Self {
+        let source = source.unwrap_or_else(|| {
+            file.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        });
+        Self {
+            effective_on: effective_on.unwrap_or_else(|| Utc::today().naive_utc()),
+            source,
+        }
+    }
+}
+
 //todo: there's likely similarity between property loading and propensity loading. Future work to unify.
 #[tracing::instrument(level = "info", skip(settings))]
-pub async fn load_propensity_data(file: PathBuf, settings: Settings) -> Result<(), LoaderError> {
+pub async fn load_propensity_data(
+    file: PathBuf, effective_on: Option<NaiveDate>, source: Option<String>, score_columns: Vec<ScoreColumn>,
+    max_anomaly_rate: Option<f64>, thresholds: AbortThresholds, settings: Settings,
+) -> Result<LoadOutcome, LoaderError> {
+    let provenance = ScoreProvenance::new(&file, effective_on, source);
+    tracing::info!(
+        ?provenance,
+        "loading propensity scores as a new version of each property's score"
+    );
     let mut reader = csv::Reader::from_path(&file)?;
     let mut quality = QualityMeasure::default();
     let mut skipped_records = vec![];
@@ -79,10 +162,32 @@ pub async fn load_propensity_data(file: PathBuf, settings: Settings) -> Result<(
 
     let connection_pool = crate::core::get_connection_pool(&settings.database)
         .await
-        .expect("Failed to connect to Postgres database.");
+        .context("Failed to connect to Postgres database.")?;
+
+    let score_columns = if score_columns.is_empty() {
+        ScoreColumn::default_columns()
+    } else {
+        score_columns
+    };
+    let headers = reader.headers()?.clone();
+    let score_columns = ScoreColumn::locate(score_columns, &headers)?;
+    let models = find_models(&score_columns, &connection_pool).await?;
+    tracing::info!(?score_columns, "loading scores from columns");
+
+    eprintln!(" {}...", style("Assessing propensity scores for anomalies").bold());
+    quality.anomalies = anomalies::assess_anomalies(&file, &score_columns, &models, &connection_pool).await?;
+    if let Some(max_anomaly_rate) = max_anomaly_rate {
+        if max_anomaly_rate < quality.anomalies.rate() {
+            report_anomalies(&quality.anomalies);
+            return Err(LoaderError::AnomalyRateExceeded {
+                percent: 100.0 * quality.anomalies.rate(),
+                max_percent: 100.0 * max_anomaly_rate,
+            });
+        }
+    }
 
+    let mut batch = LoadBatch::new(&connection_pool);
     let mut nr_valid_records: usize = 0;
-    let mut nr_saved_records: usize = 0;
 
     tracing::info!("loading propensity records from source file: {:?}", file);
     eprintln!(" {}...", style("Loading propensity scores").bold());
@@ -93,19 +198,37 @@ pub async fn load_propensity_data(file: PathBuf, settings: Settings) -> Result<(
     let progress = ProgressBar::new(nr_records as u64);
     progress.set_style(sty);
 
-    for (pos, record) in reader.deserialize().enumerate().progress_with(progress) {
+    let mut nr_processed_records: usize = 0;
+    for (pos, record) in reader.records().enumerate().progress_with(progress) {
+        if let Err(breach) = thresholds.check(quality.nr_rejects(), pos, false) {
+            return abort_load(
+                breach,
+                batch,
+                &file,
+                &skipped_records,
+                &quality,
+                &models,
+                &connection_pool,
+            )
+            .await;
+        }
+
         let idx = pos + 1;
+        nr_processed_records = idx;
 
-        let record: Result<CsvPropertyPropensityScore, csv::Error> = record;
+        let record: Result<CsvPropertyPropensityScore, LoaderError> = record
+            .map_err(LoaderError::from)
+            .and_then(|record| CsvPropertyPropensityScore::from_record(&record, &headers, &score_columns));
         let ingress = match record {
             Ok(ref property_propensity) => {
-                if property_propensity.propensity_score.is_none() {
+                if !property_propensity.has_scores() {
                     tracing::warn!(
                         ?record,
                         "propensity record[{}] does not have a propensity score - skipping.",
                         idx
                     );
                     quality.missing_scores.push(idx);
+                    quality.reject(idx);
                     skipped_records.push(idx);
                     continue;
                 }
@@ -114,6 +237,7 @@ pub async fn load_propensity_data(file: PathBuf, settings: Settings) -> Result<(
             Err(err) => {
                 tracing::error!(error=?err, record_index=%idx, "failed to load propensity record[{}]", idx);
                 quality.deserialization_failures.push((idx, err.into()));
+                quality.reject(idx);
                 skipped_records.push(idx);
                 continue;
             }
@@ -123,56 +247,204 @@ pub async fn load_propensity_data(file: PathBuf, settings: Settings) -> Result<(
         if let Err(err) = ingress.validate() {
             tracing::error!(error=?err, "propensity record[{}] failed initial validation.", idx);
             quality.validation_failures.push((idx, err));
+            quality.reject(idx);
             skipped_records.push(idx);
             continue;
         }
         tracing::debug!(?ingress, "record[{}] validated", idx);
 
-        // should never be None due to above check; however this is easy and resilient to future modification.
-        let propensity_record: Result<Option<PropertyPropensityScore>, LoaderError> = ingress.clone().try_into();
-        let propensity_record = match propensity_record {
-            Ok(rec) => rec,
+        let propensity_records: Result<Vec<PropertyPropensityScore>, LoaderError> = ingress.clone().try_into();
+        let propensity_records = match propensity_records {
+            Ok(recs) => recs,
             Err(err) => {
                 tracing::error!(error=?err, "failed to convert csv record into domain -- skipped");
                 quality.deserialization_failures.push((idx, err.into()));
+                quality.reject(idx);
                 skipped_records.push(idx);
                 continue;
             }
         };
 
-        let propensity_record = match propensity_record {
-            Some(score) => score,
-            None => {
-                tracing::error!(
-                    ?propensity_record,
-                    "redundant propensity score failed - data load okay but check code"
-                );
-                tracing::warn!("Propensity record[{}] did not contain a score - skipping.", idx);
-                quality.missing_scores.push(idx);
-                skipped_records.push(idx);
-                continue;
-            }
-        };
+        // a score outside its model's scale is skipped without discarding the record's other scores.
+        let (propensity_records, out_of_range): (Vec<_>, Vec<_>) = propensity_records
+            .into_iter()
+            .partition(|rec| models[&rec.model].admits(rec.score.score));
+        for rec in out_of_range {
+            let model = &models[&rec.model];
+            tracing::error!(
+                score=%rec.score.score, model=%model.name,
+                "propensity record[{}] score is outside the model's scale of {} to {} - skipping score.",
+                idx, model.scale.min_score, model.scale.max_score
+            );
+            quality
+                .validation_failures
+                .push((idx, out_of_range_errors(model, rec.score)));
+            quality.reject(idx);
+        }
+        if propensity_records.is_empty() {
+            skipped_records.push(idx);
+            continue;
+        }
 
         nr_valid_records += 1;
-        tracing::debug!(?propensity_record, %nr_valid_records, "csv record[{}] converted to save to database.", idx);
+        tracing::debug!(?propensity_records, %nr_valid_records, "csv record[{}] converted to save to database.", idx);
+
+        let mut nr_saved_scores = 0;
+        for propensity_record in propensity_records.iter() {
+            let saved = save_record(
+                propensity_record,
+                &provenance,
+                batch.transaction().await?,
+                idx,
+                &ingress,
+                &mut quality,
+            )
+            .await;
+            if saved {
+                nr_saved_scores += 1;
+            }
+        }
+
+        if 0 < nr_saved_scores {
+            batch.record_saved().await?;
+            let record = &propensity_records[0];
+            match do_assess_for_property(&connection_pool, record).await.unwrap_or(None) {
+                None => quality.not_in_core_properties.push(record.clone()),
+                Some(property) => {
+                    let core_line = AddressStandardizer::address_line(&property.address.address_line);
+                    if let Some(situs_line) = ingress.extract_address_line() {
+                        if situs_line != core_line {
+                            tracing::warn!(
+                                situs=%situs_line, core=%core_line,
+                                "propensity record[{}] address differs from its core property's", idx
+                            );
+                            quality.address_mismatches.push(idx);
+                        }
+                    }
+                }
+            }
+        } else {
+            skipped_records.push(idx);
+        }
+    }
 
-        let saved = save_record(
-            &propensity_record,
+    if let Err(breach) = thresholds.check(quality.nr_rejects(), nr_processed_records, true) {
+        return abort_load(
+            breach,
+            batch,
+            &file,
+            &skipped_records,
+            &quality,
+            &models,
             &connection_pool,
-            idx,
-            &ingress,
-            &mut quality,
-            &mut skipped_records,
         )
         .await;
-        if saved {
-            nr_saved_records += 1;
+    }
+    batch.commit().await?;
+
+    if 0 < batch.nr_committed() {
+        crate::core::notify_data_loaded(crate::core::LoadedData::Propensities, &connection_pool).await?;
+    }
+    summarize(batch.nr_committed(), &file, &skipped_records, &quality, &models)?;
+    Ok(LoadOutcome::of(quality.nr_rejects()))
+}
+
+/// Aborts a load whose rejects exceed a threshold, rolling back the batch in progress. Scores of
+/// previously committed batches remain loaded.
+async fn abort_load(
+    breach: ThresholdBreach, mut batch: LoadBatch, file: &PathBuf, skipped: &[usize], quality: &QualityMeasure,
+    models: &HashMap<String, PropensityModel>, pool: &PgPool,
+) -> Result<LoadOutcome, LoaderError> {
+    tracing::error!(%breach, "aborting propensity load");
+    let nr_discarded = batch.rollback().await?;
+    eprintln!(
+        " {}",
+        style(format!(
+            "Aborting load: {} - rolled back {} records of the batch in progress",
+            breach, nr_discarded
+        ))
+        .red()
+        .bold()
+    );
+
+    if 0 < batch.nr_committed() {
+        crate::core::notify_data_loaded(crate::core::LoadedData::Propensities, pool).await?;
+    }
+    if let Err(error) = summarize(batch.nr_committed(), file, skipped, quality, models) {
+        tracing::error!(?error, "failed to summarize aborted propensity load");
+    }
+    Err(breach.into())
+}
+
+/// Finds the registered model of each score column, failing if any is not registered.
+#[tracing::instrument(level = "info", skip(pool))]
+pub(crate) async fn find_models(
+    score_columns: &[(usize, ScoreColumn)], pool: &PgPool,
+) -> Result<HashMap<String, PropensityModel>, LoaderError> {
+    let mut models = HashMap::with_capacity(score_columns.len());
+    for (_, column) in score_columns {
+        let model = PropensityModelRepository::find(&column.model, pool)
+            .await?
+            .ok_or_else(|| {
+                LoaderError::InvalidScoreColumn(format!(
+                    "model {} of score column {} is not registered",
+                    column.model, column.column
+                ))
+            })?;
+        models.insert(model.name.clone(), model);
+    }
+    Ok(models)
+}
+
+/// Reads the scores of a propensity file that lie within their model's scale, along with the position
+/// of each score's record. Also returns the number of records that cannot be read and scores outside
+/// their model's scale, which are left out.
+pub(crate) fn read_scores(
+    file: &PathBuf, score_columns: &[(usize, ScoreColumn)], models: &HashMap<String, PropensityModel>,
+) -> Result<(Vec<(usize, PropertyPropensityScore)>, usize), LoaderError> {
+    let mut reader = csv::Reader::from_path(file)?;
+    let headers = reader.headers()?.clone();
+    let mut scores = vec![];
+    let mut nr_unreadable: usize = 0;
+    for (pos, record) in reader.records().enumerate() {
+        let idx = pos + 1;
+        let record_scores = record
+            .map_err(LoaderError::from)
+            .and_then(|record| CsvPropertyPropensityScore::from_record(&record, &headers, score_columns))
+            .and_then(|csv_score| {
+                csv_score.validate()?;
+                let record_scores: Vec<PropertyPropensityScore> = csv_score.try_into()?;
+                Ok(record_scores)
+            });
+        match record_scores {
+            Ok(record_scores) => {
+                for score in record_scores {
+                    if models[&score.model].admits(score.score.score) {
+                        scores.push((idx, score));
+                    } else {
+                        tracing::debug!(score=%score.score.score, model=%score.model, "propensity record[{}] score is outside the model's scale.", idx);
+                        nr_unreadable += 1;
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::debug!(error=?err, "propensity record[{}] is unreadable.", idx);
+                nr_unreadable += 1;
+            }
         }
     }
+    Ok((scores, nr_unreadable))
+}
 
-    summarize(nr_saved_records, &file, &skipped_records, &quality);
-    Ok(())
+fn out_of_range_errors(model: &PropensityModel, score: PropensityScore) -> ValidationErrors {
+    let mut error = ValidationError::new("range");
+    error.add_param("model".into(), &model.name);
+    error.add_param("min".into(), &model.scale.min_score);
+    error.add_param("max".into(), &model.scale.max_score);
+    error.add_param("value".into(), &score.score);
+    let mut errors = ValidationErrors::new();
+    errors.add("score", error);
+    errors
 }
 
 #[tracing::instrument(level = "info")]
@@ -181,83 +453,66 @@ fn count_nr_records(path: &PathBuf) -> Result<usize, LoaderError> {
     Ok(reader.lines().count() - 1) // subtract header line
 }
 
-#[tracing::instrument(level = "info", skip(pool, csv_record, quality, skipped_records,))]
+#[tracing::instrument(level = "info", skip(transaction, csv_record, quality,))]
 async fn save_record(
-    record: &PropertyPropensityScore, pool: &PgPool, index: usize, csv_record: &CsvPropertyPropensityScore,
-    quality: &mut QualityMeasure, skipped_records: &mut Vec<usize>,
+    record: &PropertyPropensityScore, provenance: &ScoreProvenance, transaction: &mut Transaction<'static, Postgres>,
+    index: usize, csv_record: &CsvPropertyPropensityScore, quality: &mut QualityMeasure,
 ) -> bool {
-    match PropertyPropensityScoreRepository::find_for_apn(&record.apn, &pool).await {
-        Err(err) => {
-            tracing::error!(error=?err, apn=?record.apn, "error while checking if propensity record[{}] was previously loaded - skipping", index);
-            skipped_records.push(index);
-            false
+    let save_span = tracing::info_span!("save", apn=%record.apn, model=%record.model, %index,);
+    let _save_span_guardian = save_span.enter();
+
+    match do_save(transaction, record, provenance, index).await {
+        Ok(Some(_current)) => {
+            quality.tally_score(record);
+            tracing::info!("saved property propensity score version");
+            true
         }
 
-        Ok(Some(record)) => {
-            quality
-                .propensity_zips
-                .push((record.score, record.zip_or_postal_code.clone()));
-            tracing::info!(apn=?record.apn, "propensity record[{}] previously loaded - skipping", index);
-            skipped_records.push(index);
+        Ok(None) => {
+            quality.tally_score(record);
+            tracing::info!(apn=?record.apn, model=%record.model, "propensity record[{}] score previously loaded - skipping", index);
             false
         }
 
-        Ok(None) => {
-            let matched = do_assess_for_property(pool, record).await.unwrap_or(false);
-            if !matched {
-                quality.not_in_core_properties.push(record.clone());
-            }
-            let save_span = tracing::info_span!("save", apn=%record.apn, %index,);
-            let _save_span_guardian = save_span.enter();
-
-            match do_save(pool, record, index).await {
-                Ok(_score) => {
-                    quality
-                        .propensity_zips
-                        .push((record.score, record.zip_or_postal_code.clone()));
-                    tracing::info!("saved property propensity score");
-                    true
-                }
-
-                Err(err) => {
-                    tracing::error!(error=?err, "failed to save property propensity score - skipping.");
-                    quality.save_failures.push((csv_record.clone(), err.into()));
-                    skipped_records.push(index);
-                    false
-                }
-            }
+        Err(err) => {
+            tracing::error!(error=?err, "failed to save property propensity score - skipping.");
+            quality.save_failures.push((csv_record.clone(), err.into()));
+            quality.reject(index);
+            false
         }
     }
 }
 
-#[tracing::instrument(level = "info", skip(pool))]
+/// Records the score as a new version of the property's score, returning the property's current
+/// score or `None` if the version was previously loaded. The version is recorded within a savepoint
+/// of the batch's transaction, so a failed save leaves the batch's other scores intact.
+#[tracing::instrument(level = "info", skip(transaction))]
 async fn do_save(
-    pool: &PgPool, record: &PropertyPropensityScore, index: usize,
-) -> Result<PropertyPropensityScore, LoaderError> {
-    let mut transaction = pool
-        .begin()
-        .await
-        .expect("Failed to acquire Postgres connection from the pool.");
-
-    let nr_updated = PropertyPropensityScoreRepository::save(&mut transaction, record).await?;
-
-    transaction
-        .commit()
-        .await
-        .expect("Failed to commit SQL transaction to store loaded propensity record.");
-
-    tracing::info!("Saved RECORD[{}]: => {:?}", index, nr_updated);
-    Ok(nr_updated)
+    transaction: &mut Transaction<'static, Postgres>, record: &PropertyPropensityScore, provenance: &ScoreProvenance,
+    index: usize,
+) -> Result<Option<PropertyPropensityScore>, LoaderError> {
+    let mut savepoint = transaction.begin().await?;
+    let version = PropensityScoreVersion::new(record, provenance.effective_on, &provenance.source, Utc::now());
+    let current = PropertyPropensityScoreRepository::record_version(&mut savepoint, &version).await?;
+    savepoint.commit().await?;
+
+    tracing::info!("Saved RECORD[{}]: => {:?}", index, current);
+    Ok(current)
 }
 
 #[tracing::instrument(level = "info", skip(pool))]
-async fn do_assess_for_property(pool: &PgPool, record: &PropertyPropensityScore) -> Result<bool, LoaderError> {
+async fn do_assess_for_property(
+    pool: &PgPool, record: &PropertyPropensityScore,
+) -> Result<Option<Property>, LoaderError> {
     let result = PropertyRecordRepository::find(&record.apn, pool).await?;
-    Ok(result.is_some())
+    Ok(result)
 }
 
-#[tracing::instrument(level = "info", skip(nr_saved_records, file, skipped, quality))]
-fn summarize(nr_saved_records: usize, file: &PathBuf, skipped: &[usize], quality: &QualityMeasure) {
+#[tracing::instrument(level = "info", skip(nr_saved_records, file, skipped, quality, models))]
+fn summarize(
+    nr_saved_records: usize, file: &PathBuf, skipped: &[usize], quality: &QualityMeasure,
+    models: &HashMap<String, PropensityModel>,
+) -> Result<(), LoaderError> {
     eprintln!(
         " {}",
         style(format!(
@@ -277,14 +532,74 @@ fn summarize(nr_saved_records: usize, file: &PathBuf, skipped: &[usize], quality
         quality
     );
 
-    visualize_score_distribution(&quality.propensity_zips).expect("Failed to save propensity_score_distribution.png");
-    visualize_zipcode_scores(&quality.propensity_zips).expect("Failed to save score_zipcode_distribution.png");
+    report_anomalies(&quality.anomalies);
+    visualize_score_distribution(models, &quality.model_scores)?;
+    visualize_zipcode_scores(&quality.propensity_zips).context("Failed to save score_zipcode_distribution.png")?;
+    Ok(())
 }
 
-#[tracing::instrument(level = "info", skip(score_zips))]
-fn visualize_score_distribution(score_zips: &Vec<(PropensityScore, Option<ZipOrPostalCode>)>) -> anyhow::Result<()> {
-    let out_filename = "propensity_score_distribution.png";
-    let scores: Vec<u32> = score_zips.iter().map(|(s, _)| s.score as u32).collect();
+/// Number of outlying scores listed in the load summary; every outlier is logged.
+const NR_OUTLIERS_REPORTED: usize = 20;
+
+fn report_anomalies(anomalies: &AnomalyReport) {
+    if anomalies.nr_anomalous == 0 {
+        return;
+    }
+
+    eprintln!(" {}", style(anomalies).bold());
+    tracing::warn!("{}", anomalies);
+    for region in anomalies.regions.iter() {
+        eprintln!("\tanomalous {}", region);
+        tracing::warn!(?region, "anomalous region");
+    }
+    for (pos, outlier) in anomalies.outliers.iter().enumerate() {
+        if pos < NR_OUTLIERS_REPORTED {
+            eprintln!("\toutlying {}", outlier);
+        }
+        tracing::warn!(?outlier, "outlying score");
+    }
+    if NR_OUTLIERS_REPORTED < anomalies.outliers.len() {
+        eprintln!(
+            "\t... and {} more outlying scores",
+            anomalies.outliers.len() - NR_OUTLIERS_REPORTED
+        );
+    }
+}
+
+/// Draws a histogram of each model's loaded scores over that model's scale.
+#[tracing::instrument(level = "info", skip(models, model_scores))]
+fn visualize_score_distribution(
+    models: &HashMap<String, PropensityModel>, model_scores: &HashMap<String, Vec<i32>>,
+) -> anyhow::Result<()> {
+    for (name, scores) in model_scores.iter() {
+        let scale = &models[name].scale;
+        let out_filename = score_distribution_filename(name);
+        draw_histogram(
+            &out_filename,
+            &format!("{} Score Distribution", name),
+            "Score",
+            scale.min_score as i32..(scale.max_score as i32 + 1),
+            scores,
+        )
+        .with_context(|| format!("Failed to save {}", out_filename))?;
+    }
+    Ok(())
+}
+
+fn score_distribution_filename(model: &str) -> String {
+    format!("propensity_score_distribution_{}.png", model)
+}
+
+/// Draws a histogram of the values over the given range to a PNG file.
+#[tracing::instrument(level = "info", skip(values), fields(nr_values = values.len()))]
+pub(crate) fn draw_histogram(
+    out_filename: &str, caption: &str, x_desc: &str, x_range: Range<i32>, values: &[i32],
+) -> anyhow::Result<()> {
+    let mut counts: HashMap<i32, u32> = HashMap::default();
+    for value in values {
+        *counts.entry(*value).or_default() += 1;
+    }
+    let y_max = counts.values().max().copied().unwrap_or(0).max(1);
 
     let background = BitMapBackend::new(out_filename, (640, 480)).into_drawing_area();
     background.fill(&WHITE)?;
@@ -292,28 +607,30 @@ fn visualize_score_distribution(score_zips: &Vec<(PropensityScore, Option<ZipOrP
         .x_label_area_size(35)
         .y_label_area_size(40)
         .margin(5)
-        .caption("Propensity Score Distribution", ("sans-serif", 50.0))
-        .build_cartesian_2d((0_u32..950).into_segmented(), 0_u32..100_u32)?;
+        .caption(caption, ("sans-serif", 50.0))
+        .build_cartesian_2d(x_range.into_segmented(), 0_u32..(y_max + y_max / 10 + 1))?;
 
     chart
         .configure_mesh()
         .disable_x_mesh()
         .bold_line_style(&WHITE.mix(0.3))
         .y_desc("Count")
-        .x_desc("Score")
+        .x_desc(x_desc)
         .axis_desc_style(("sans-serif", 15))
         .draw()?;
 
     chart.draw_series(
         Histogram::vertical(&chart)
             .style(RED.mix(0.5).filled())
-            .data(scores.iter().map(|x| (*x, 1))),
+            .data(values.iter().map(|x| (*x, 1))),
     )?;
 
-    background.present().expect("Unable to write result to file");
+    background
+        .present()
+        .with_context(|| format!("Unable to write {}", out_filename))?;
     eprintln!(
         " {}.",
-        style(format!("Propensity score visualization was saved to {}", out_filename)).bold()
+        style(format!("{} visualization was saved to {}", caption, out_filename)).bold()
     );
     Ok(())
 }
@@ -365,7 +682,9 @@ fn visualize_zipcode_scores(score_zips: &Vec<(PropensityScore, Option<ZipOrPosta
             .data(x_counts.into_iter().map(|x| (x, 1))),
     )?;
 
-    background.present().expect("Unable to write result ro file");
+    background
+        .present()
+        .with_context(|| format!("Unable to write {}", out_filename))?;
     eprintln!(
         " {}.",
         style(format!(
@@ -376,3 +695,28 @@ fn visualize_zipcode_scores(score_zips: &Vec<(PropensityScore, Option<ZipOrPosta
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::assert_ok;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_nr_rejects_counts_records_not_scores() {
+        let mut quality = QualityMeasure::default();
+        for (idx, nr_failed_scores) in vec![(1, 3), (2, 1)] {
+            for _ in 0..nr_failed_scores {
+                quality.validation_failures.push((idx, ValidationErrors::new()));
+                quality.reject(idx);
+            }
+        }
+        quality.missing_scores.push(3);
+        quality.reject(3);
+
+        assert_eq!(quality.validation_failures.len(), 4);
+        assert_eq!(quality.nr_rejects(), 3);
+        let thresholds = AbortThresholds { max_errors: None, max_error_rate: Some(1.0) };
+        assert_ok!(thresholds.check(quality.nr_rejects(), 3, true));
+    }
+}
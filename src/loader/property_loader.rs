@@ -1,11 +1,14 @@
 use crate::core::domain::property::{Property, PropertyRecordRepository};
+use crate::core::domain::{LandUseClassifier, LandUseTaxonomy};
 use crate::loader::domain::CsvProperty;
 use crate::loader::errors::LoaderError;
 use crate::loader::settings::Settings;
+use crate::loader::thresholds::{AbortThresholds, LoadBatch, LoadOutcome, ThresholdBreach};
+use anyhow::Context;
 use console::style;
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
-use sqlx::PgPool;
-use std::convert::TryInto;
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
+use std::collections::HashSet;
 use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -19,9 +22,16 @@ struct QualityMeasure {
     pub save_failures: Vec<(CsvProperty, anyhow::Error)>,
 }
 
+impl QualityMeasure {
+    /// Number of records rejected by the load.
+    fn nr_rejects(&self) -> usize {
+        self.deserialization_failures.len() + self.validation_failures.len() + self.save_failures.len()
+    }
+}
+
 impl fmt::Debug for QualityMeasure {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let nr_issues = self.deserialization_failures.len() + self.validation_failures.len() + self.save_failures.len();
+        let nr_issues = self.nr_rejects();
 
         let mut result =
             f.write_str(format!("{} issues found{}", nr_issues, if 0 < nr_issues { ":" } else { "" }).as_str());
@@ -53,7 +63,14 @@ fn count_nr_records(path: &PathBuf) -> Result<usize, LoaderError> {
 }
 
 #[tracing::instrument(level = "info", skip(settings))]
-pub async fn load_property_data(file: PathBuf, settings: Settings) -> Result<(), LoaderError> {
+pub async fn load_property_data(
+    file: PathBuf, land_use_taxonomy: Option<PathBuf>, thresholds: AbortThresholds, settings: Settings,
+) -> Result<LoadOutcome, LoaderError> {
+    let mut land_uses = match land_use_taxonomy {
+        Some(path) => LandUseClassifier::new(LandUseTaxonomy::from_path(&path)?),
+        None => LandUseClassifier::new(LandUseTaxonomy::default()),
+    };
+
     let mut reader = csv::Reader::from_path(&file)?;
     let mut quality = QualityMeasure::default();
     let mut skipped_records = vec![];
@@ -61,10 +78,11 @@ pub async fn load_property_data(file: PathBuf, settings: Settings) -> Result<(),
 
     let connection_pool = crate::core::get_connection_pool(&settings.database)
         .await
-        .expect("Failed to connect to Postgres database.");
+        .context("Failed to connect to Postgres database.")?;
 
+    let mut batch = LoadBatch::new(&connection_pool);
+    let mut saved_apns: HashSet<String> = HashSet::new();
     let mut nr_valid_records: usize = 0;
-    let mut nr_saved_records: usize = 0;
 
     tracing::info!("loading property records from source file: {:?}", file);
     eprintln!(" {}...", style("Loading property records").bold());
@@ -75,8 +93,23 @@ pub async fn load_property_data(file: PathBuf, settings: Settings) -> Result<(),
     let progress = ProgressBar::new(nr_records as u64);
     progress.set_style(sty);
 
+    let mut nr_processed_records: usize = 0;
     for (pos, record) in reader.deserialize().enumerate().progress_with(progress) {
+        if let Err(breach) = thresholds.check(quality.nr_rejects(), pos, false) {
+            return abort_load(
+                breach,
+                batch,
+                &file,
+                &skipped_records,
+                &quality,
+                &land_uses,
+                &connection_pool,
+            )
+            .await;
+        }
+
         let idx = pos + 1;
+        nr_processed_records = idx;
 
         let ingress: CsvProperty = match record {
             Ok(property) => property,
@@ -97,7 +130,7 @@ pub async fn load_property_data(file: PathBuf, settings: Settings) -> Result<(),
         }
         tracing::debug!(?ingress, "record[{}] validated", idx);
 
-        let property_record: Result<Property, LoaderError> = ingress.clone().try_into();
+        let property_record: Result<Property, LoaderError> = ingress.clone().into_property(&mut land_uses);
         let property_record = match property_record {
             Ok(rec) => rec,
             Err(err) => {
@@ -111,9 +144,16 @@ pub async fn load_property_data(file: PathBuf, settings: Settings) -> Result<(),
         nr_valid_records += 1;
         tracing::debug!(?property_record, %nr_valid_records, "csv record[{}] converted to save to database", idx);
 
+        if saved_apns.contains(property_record.apn.as_ref()) {
+            tracing::info!(apn=?property_record.apn, "property record[{}] previously loaded - skipping", idx);
+            skipped_records.push(idx);
+            continue;
+        }
+
         let saved = save_record(
             &property_record,
             &connection_pool,
+            batch.transaction().await?,
             idx,
             &ingress,
             &mut quality,
@@ -121,16 +161,61 @@ pub async fn load_property_data(file: PathBuf, settings: Settings) -> Result<(),
         )
         .await;
         if saved {
-            nr_saved_records += 1;
+            saved_apns.insert(property_record.apn.to_string());
+            batch.record_saved().await?;
         }
     }
 
-    summarize(nr_saved_records, &file, &skipped_records, &quality);
-    Ok(())
+    if let Err(breach) = thresholds.check(quality.nr_rejects(), nr_processed_records, true) {
+        return abort_load(
+            breach,
+            batch,
+            &file,
+            &skipped_records,
+            &quality,
+            &land_uses,
+            &connection_pool,
+        )
+        .await;
+    }
+    batch.commit().await?;
+
+    summarize(batch.nr_committed(), &file, &skipped_records, &quality, &land_uses);
+    if 0 < batch.nr_committed() {
+        crate::core::notify_data_loaded(crate::core::LoadedData::Properties, &connection_pool).await?;
+    }
+    Ok(LoadOutcome::of(quality.nr_rejects()))
+}
+
+/// Aborts a load whose rejects exceed a threshold, rolling back the batch in progress. Records of
+/// previously committed batches remain loaded.
+async fn abort_load(
+    breach: ThresholdBreach, mut batch: LoadBatch, file: &PathBuf, skipped: &[usize], quality: &QualityMeasure,
+    land_uses: &LandUseClassifier, pool: &PgPool,
+) -> Result<LoadOutcome, LoaderError> {
+    tracing::error!(%breach, "aborting property load");
+    let nr_discarded = batch.rollback().await?;
+    eprintln!(
+        " {}",
+        style(format!(
+            "Aborting load: {} - rolled back {} records of the batch in progress",
+            breach, nr_discarded
+        ))
+        .red()
+        .bold()
+    );
+
+    summarize(batch.nr_committed(), file, skipped, quality, land_uses);
+    if 0 < batch.nr_committed() {
+        crate::core::notify_data_loaded(crate::core::LoadedData::Properties, pool).await?;
+    }
+    Err(breach.into())
 }
 
 #[tracing::instrument(level = "info", skip(nr_saved_records, file, skipped, quality))]
-fn summarize(nr_saved_records: usize, file: &PathBuf, skipped: &[usize], quality: &QualityMeasure) {
+fn summarize(
+    nr_saved_records: usize, file: &PathBuf, skipped: &[usize], quality: &QualityMeasure, land_uses: &LandUseClassifier,
+) {
     eprintln!(
         " {}",
         style(format!(
@@ -162,16 +247,66 @@ fn summarize(nr_saved_records: usize, file: &PathBuf, skipped: &[usize], quality
             .bold()
         );
     }
+
+    report_land_use_classification(land_uses);
 }
 
-#[tracing::instrument(level = "info", skip(pool, csv_record, quality, skipped_records))]
+/// Lists the raw land use types unmatched by the taxonomy, so it can be extended to cover them, and
+/// those matching more than one land use, whose classification rests on the taxonomy's order. Both
+/// are listed most common first.
+fn report_land_use_classification(land_uses: &LandUseClassifier) {
+    let unmatched = land_uses.unmatched();
+    if !unmatched.is_empty() {
+        eprintln!(
+            " {}",
+            style(format!(
+                "{} land use types were unmatched by the taxonomy and loaded as Unknown:",
+                unmatched.len()
+            ))
+            .bold()
+        );
+        for (raw, count) in unmatched {
+            eprintln!("\t{:?}: {} records", raw, count);
+            tracing::warn!(land_use_type=%raw, nr_records=%count, "unmatched land use type");
+        }
+    }
+
+    let ambiguous = land_uses.ambiguous();
+    if !ambiguous.is_empty() {
+        eprintln!(
+            " {}",
+            style(format!(
+                "{} land use types matched more than one land use of the taxonomy:",
+                ambiguous.len()
+            ))
+            .bold()
+        );
+        for (raw, classification, count) in ambiguous {
+            let also_matched: Vec<&str> = classification.also_matched.iter().map(|lu| lu.code()).collect();
+            eprintln!(
+                "\t{:?}: {} records classified as {}, also matching {}",
+                raw,
+                count,
+                classification.land_use,
+                also_matched.join(", ")
+            );
+            tracing::warn!(
+                land_use_type=%raw, nr_records=%count, land_use=%classification.land_use, ?also_matched,
+                "ambiguous land use type"
+            );
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", skip(pool, transaction, csv_record, quality, skipped_records))]
 async fn save_record(
-    record: &Property, pool: &PgPool, index: usize, csv_record: &CsvProperty, quality: &mut QualityMeasure,
-    skipped_records: &mut Vec<usize>,
+    record: &Property, pool: &PgPool, transaction: &mut Transaction<'static, Postgres>, index: usize,
+    csv_record: &CsvProperty, quality: &mut QualityMeasure, skipped_records: &mut Vec<usize>,
 ) -> bool {
     match PropertyRecordRepository::find(&record.apn, &pool).await {
         Err(err) => {
             tracing::error!(error=?err, apn=?record.apn, "error looking while checking if property record[{}] was previously loaded.", index);
+            quality.save_failures.push((csv_record.clone(), err.into()));
             skipped_records.push(index);
             false
         }
@@ -186,7 +321,7 @@ async fn save_record(
             let save_span = tracing::info_span!("save", apn=%record.apn, %index,);
             let _save_span_guardian = save_span.enter();
 
-            match do_save(pool, record, index).await {
+            match do_save(transaction, record, index).await {
                 Ok(_property) => {
                     tracing::info!("saved property record.");
                     true
@@ -203,19 +338,15 @@ async fn save_record(
     }
 }
 
-#[tracing::instrument(level = "info", skip(pool))]
-async fn do_save(pool: &PgPool, record: &Property, index: usize) -> Result<Property, LoaderError> {
-    let mut transaction = pool
-        .begin()
-        .await
-        .expect("Failed to acquire Postgres connection from the pool.");
-
-    let nr_updated = PropertyRecordRepository::save(&mut transaction, &record).await?;
-
-    transaction
-        .commit()
-        .await
-        .expect("Failed to commit SQL transaction to store loaded property records.");
+/// Saves the record within a savepoint of the batch's transaction, so a failed save leaves the
+/// batch's other records intact.
+#[tracing::instrument(level = "info", skip(transaction))]
+async fn do_save(
+    transaction: &mut Transaction<'static, Postgres>, record: &Property, index: usize,
+) -> Result<Property, LoaderError> {
+    let mut savepoint = transaction.begin().await?;
+    let nr_updated = PropertyRecordRepository::save(&mut savepoint, &record).await?;
+    savepoint.commit().await?;
 
     tracing::info!("Saved RECORD[{}]: => {:?}", index, nr_updated,);
     Ok(nr_updated)
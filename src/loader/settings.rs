@@ -1,3 +1,9 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::propensity_composite::{CompositeComponent, Normalization, NormalizationRegion};
+use crate::core::domain::propensity_model::{ScoreDirection, TierBand};
+use crate::loader::domain::ScoreColumn;
+use crate::loader::thresholds::AbortThresholds;
+use chrono::NaiveDate;
 use clap::{AppSettings, Clap, ValueHint};
 use serde::{Deserialize, Serialize};
 use settings_loader::common::database::DatabaseSettings;
@@ -14,7 +20,7 @@ impl SettingsLoader for Settings {
     type Options = LoaderCliOptions;
 }
 
-#[derive(Clap, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clap, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[clap(version = "0.1.1", author = "Damon Rolfs")]
 #[clap(setting = AppSettings::ColoredHelp)]
 pub struct LoaderCliOptions {
@@ -45,7 +51,7 @@ impl LoadingOptions for LoaderCliOptions {
     }
 }
 
-#[derive(Clap, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clap, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[clap(version = "0.1.1", author = "Damon Rolfs")]
 #[clap(setting = AppSettings::ColoredHelp)]
 pub enum SubCommand {
@@ -58,6 +64,13 @@ pub enum SubCommand {
         // Specify file to output propensity distribution visualization
         // #[clap(short, long, parse(from_os_str), value_hint = ValueHint::FilePath)]
         // distribution: Option<PathBuf>,
+        /// Land use taxonomy file used to classify the records' land use types; defaults to the
+        /// built-in taxonomy of resources/land_use_taxonomy.yaml
+        #[clap(long, parse(from_os_str), value_hint = ValueHint::FilePath)]
+        land_use_taxonomy: Option<PathBuf>,
+
+        #[clap(flatten)]
+        thresholds: AbortThresholds,
     },
 
     /// Specify path to propensity data file in CSV format
@@ -69,14 +82,212 @@ pub enum SubCommand {
         // /// Specify file to output propensity distribution visualization
         // #[clap(short, long, parse(from_os_str), value_hint = ValueHint::FilePath)]
         // distribution: Option<PathBuf>,
+        /// Date from which the loaded scores are in effect (YYYY-MM-DD); defaults to today
+        #[clap(long)]
+        effective_on: Option<NaiveDate>,
+
+        /// Source that produced the scores, recorded in their history; defaults to the file name
+        #[clap(long)]
+        source: Option<String>,
+
+        /// Score column and the registered model that produced its scores, as COLUMN=MODEL; may be
+        /// repeated. Defaults to HomeEquityIntelScore_LineofCredit=heloc
+        #[clap(long = "score", multiple_occurrences = true, number_of_values = 1)]
+        scores: Vec<ScoreColumn>,
+
+        /// Largest share of anomalous scores, as a fraction (0.05) or percentage (5%), above which the
+        /// load is aborted before any score is saved
+        #[clap(long, parse(try_from_str = parse_rate))]
+        max_anomaly_rate: Option<f64>,
+
+        #[clap(flatten)]
+        thresholds: AbortThresholds,
+    },
+
+    /// Standardize the stored addresses of properties loaded before addresses were standardized
+    #[clap(name = "standardize")]
+    Standardize {
+        /// Count the property addresses to standardize without rewriting them
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Compare the scores of a propensity data file with those stored, without loading it
+    #[clap(name = "diff")]
+    Diff {
+        /// Incoming propensity data file in CSV format
+        #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::AnyPath)]
+        file: PathBuf,
+
+        /// Score column and the registered model that produced its scores, as COLUMN=MODEL; may be
+        /// repeated. Defaults to HomeEquityIntelScore_LineofCredit=heloc
+        #[clap(long = "score", multiple_occurrences = true, number_of_values = 1)]
+        scores: Vec<ScoreColumn>,
+
+        /// Number of the largest score movers to report for each model
+        #[clap(long, default_value = "20")]
+        movers: usize,
+
+        /// File to write the tab-separated drift report to
+        #[clap(
+            short,
+            long,
+            parse(from_os_str),
+            value_hint = ValueHint::FilePath,
+            default_value = "propensity_score_drift.tsv"
+        )]
+        output: PathBuf,
+
+        /// Directory to save each model's histogram of score deltas in; defaults to the working
+        /// directory
+        #[clap(long, parse(from_os_str), value_hint = ValueHint::DirPath)]
+        histogram_dir: Option<PathBuf>,
+    },
+
+    /// Manage the registry of propensity models whose scores are loaded
+    #[clap(name = "model")]
+    Model {
+        #[clap(subcommand)]
+        command: ModelCommand,
+    },
+
+    /// Manage the API keys clients use to access the server
+    #[clap(name = "api-key")]
+    ApiKey {
+        #[clap(subcommand)]
+        command: ApiKeyCommand,
+    },
+}
+
+#[derive(Clap, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiKeyCommand {
+    /// Create an API key; the key is printed once and cannot be retrieved later
+    #[clap(name = "create")]
+    Create {
+        /// Name identifying the client the key is issued to
+        #[clap(name = "NAME")]
+        name: String,
+
+        /// Scope granted to the key (scores:read, addresses:read); may be repeated
+        #[clap(
+            short,
+            long = "scope",
+            required = true,
+            multiple_occurrences = true,
+            number_of_values = 1
+        )]
+        scopes: Vec<Scope>,
+    },
+
+    /// List API keys
+    #[clap(name = "list")]
+    List,
+
+    /// Revoke the API keys issued under a name
+    #[clap(name = "revoke")]
+    Revoke {
+        /// Name identifying the client whose keys are revoked
+        #[clap(name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(Clap, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModelCommand {
+    /// Register a propensity model, or update the score scale, tiers and description of a registered model
+    #[clap(name = "register")]
+    Register {
+        /// Lowercase name identifying the model; e.g., refinance
+        #[clap(name = "NAME")]
+        name: String,
+
+        /// Lowest valid score of the model
+        #[clap(long)]
+        min_score: u16,
+
+        /// Highest valid score of the model
+        #[clap(long)]
+        max_score: u16,
+
+        /// Whether higher or lower scores indicate a greater propensity (higher, lower)
+        #[clap(long, default_value = "higher")]
+        direction: ScoreDirection,
+
+        /// Tier band of the model's scores given as NAME=MIN-MAX; e.g., "Very High=801-950". May be
+        /// repeated, and replaces the tiers of a registered model
+        #[clap(long = "tier", multiple_occurrences = true, number_of_values = 1)]
+        tiers: Vec<TierBand>,
+
+        /// Description of what the model's scores measure
+        #[clap(long, default_value = "")]
+        description: String,
     },
+
+    /// List registered propensity models and composite definitions
+    #[clap(name = "list")]
+    List,
+
+    /// Define a registered model as a composite of other models' scores, or replace its definition.
+    /// Its scores are computed by the materialize subcommand
+    #[clap(name = "composite")]
+    Composite {
+        /// Registered model holding the composite scores
+        #[clap(name = "NAME")]
+        name: String,
+
+        /// Component model and its relative weight, as MODEL=WEIGHT; e.g., refinance=0.6. May be repeated
+        #[clap(
+            long = "component",
+            required = true,
+            multiple_occurrences = true,
+            number_of_values = 1
+        )]
+        components: Vec<CompositeComponent>,
+
+        /// How component scores are normalized before weighting (percentile, z-score)
+        #[clap(long, default_value = "percentile")]
+        normalization: Normalization,
+
+        /// Region within which component scores are normalized (state, county, zip_code)
+        #[clap(long, default_value = "state")]
+        within: NormalizationRegion,
+    },
+
+    /// Compute and save the scores of composite models from the current scores of their components
+    #[clap(name = "materialize")]
+    Materialize {
+        /// Composite models to materialize; defaults to every composite
+        #[clap(name = "NAME")]
+        names: Vec<String>,
+
+        /// Date from which the composite scores are in effect (YYYY-MM-DD); defaults to today
+        #[clap(long)]
+        effective_on: Option<NaiveDate>,
+    },
+}
+
+/// Parses a rate given as a fraction between 0 and 1 (e.g., 0.05) or a percentage (e.g., 5%).
+pub(crate) fn parse_rate(rate: &str) -> Result<f64, String> {
+    let rate = rate.trim();
+    let parsed = match rate.strip_suffix('%') {
+        Some(percentage) => percentage.trim().parse::<f64>().map(|p| p / 100.0),
+        None => rate.parse::<f64>(),
+    };
+    match parsed {
+        Ok(parsed) if (0.0..=1.0).contains(&parsed) => Ok(parsed),
+        _ => Err(format!("{} is not a rate between 0 and 1 or 0% and 100%", rate)),
+    }
 }
 
 impl fmt::Display for SubCommand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let label = match self {
-            Self::Property { file: _ } => "property",
-            Self::Propensity { file: _ } => "propensity",
+            Self::Property { .. } => "property",
+            Self::Propensity { .. } => "propensity",
+            Self::Standardize { .. } => "standardize",
+            Self::Diff { .. } => "diff",
+            Self::Model { command: _ } => "model",
+            Self::ApiKey { command: _ } => "api-key",
         };
 
         write!(f, "{}", label)
@@ -0,0 +1,239 @@
+use crate::loader::errors::LoaderError;
+use crate::loader::settings::parse_rate;
+use anyhow::Context;
+use clap::Clap;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Number of saved records committed together. A load aborted by a threshold rolls back only the
+/// records of its current batch; earlier batches remain saved.
+pub const LOAD_BATCH_SIZE: usize = 1_000;
+
+/// Number of records a load processes before its error rate is evaluated, so a few early rejects do
+/// not abort it. The rate is always evaluated once the whole file is processed.
+const MIN_RECORDS_FOR_RATE: usize = 100;
+
+/// Process exit code of a load that completed without rejecting any record.
+pub const EXIT_CLEAN: i32 = 0;
+
+/// Process exit code of a command that failed before completing, such as on losing the database.
+pub const EXIT_FATAL: i32 = 1;
+
+/// Process exit code of a command line that could not be parsed, with which clap exits.
+pub const EXIT_USAGE: i32 = 2;
+
+/// Process exit code of a load aborted because its rejects or anomalies exceeded a threshold.
+pub const EXIT_ABORTED_BY_THRESHOLD: i32 = 3;
+
+/// Process exit code of a load that completed but rejected some records; distinct from `EXIT_USAGE`
+/// so a scheduler does not mistake an invalid command line for a completed load.
+pub const EXIT_COMPLETED_WITH_REJECTS: i32 = 4;
+
+/// How a command that ran to completion went, reported by the loader's exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOutcome {
+    Clean,
+    CompletedWithRejects,
+}
+
+impl LoadOutcome {
+    pub fn of(nr_rejects: usize) -> Self {
+        if nr_rejects == 0 {
+            Self::Clean
+        } else {
+            Self::CompletedWithRejects
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Clean => EXIT_CLEAN,
+            Self::CompletedWithRejects => EXIT_COMPLETED_WITH_REJECTS,
+        }
+    }
+}
+
+/// Rejected records beyond which a load is aborted, evaluated as the load progresses.
+#[derive(Clap, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AbortThresholds {
+    /// Most records that may be rejected before the load is aborted
+    #[clap(long)]
+    pub max_errors: Option<usize>,
+
+    /// Largest share of rejected records, as a fraction (0.05) or percentage (5%), above which the
+    /// load is aborted. Evaluated once 100 records are processed
+    #[clap(long, parse(try_from_str = parse_rate))]
+    pub max_error_rate: Option<f64>,
+}
+
+/// A threshold exceeded by a load's rejected records.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ThresholdBreach {
+    #[error("{nr_rejects} records were rejected, exceeding the maximum of {max}")]
+    TooManyErrors { nr_rejects: usize, max: usize },
+
+    #[error("{percent:.1}% of {nr_records} records were rejected, exceeding the maximum of {max_percent:.1}%")]
+    ErrorRateExceeded {
+        nr_records: usize,
+        percent: f64,
+        max_percent: f64,
+    },
+}
+
+impl AbortThresholds {
+    /// Checks the rejects among the records processed so far against the thresholds; `complete` is
+    /// whether every record of the file has been processed.
+    pub fn check(&self, nr_rejects: usize, nr_records: usize, complete: bool) -> Result<(), ThresholdBreach> {
+        if let Some(max) = self.max_errors {
+            if max < nr_rejects {
+                return Err(ThresholdBreach::TooManyErrors { nr_rejects, max });
+            }
+        }
+
+        if let Some(max_rate) = self.max_error_rate {
+            if 0 < nr_records && (complete || MIN_RECORDS_FOR_RATE <= nr_records) {
+                let rate = nr_rejects as f64 / nr_records as f64;
+                if max_rate < rate {
+                    return Err(ThresholdBreach::ErrorRateExceeded {
+                        nr_records,
+                        percent: 100.0 * rate,
+                        max_percent: 100.0 * max_rate,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Saves a load's records in batches, each committed in its own transaction, so an aborted load
+/// rolls back only the batch in progress. Each record should be saved within a savepoint of the
+/// batch's transaction, so a record that fails to save does not fail its batch.
+pub struct LoadBatch {
+    pool: PgPool,
+    transaction: Option<Transaction<'static, Postgres>>,
+    nr_pending: usize,
+    nr_committed: usize,
+}
+
+impl LoadBatch {
+    pub fn new(pool: &PgPool) -> Self {
+        Self {
+            pool: pool.clone(),
+            transaction: None,
+            nr_pending: 0,
+            nr_committed: 0,
+        }
+    }
+
+    /// Number of saved records whose batches were committed.
+    pub fn nr_committed(&self) -> usize {
+        self.nr_committed
+    }
+
+    /// The transaction of the batch in progress, beginning a batch if none is in progress.
+    pub async fn transaction(&mut self) -> Result<&mut Transaction<'static, Postgres>, LoaderError> {
+        if self.transaction.is_none() {
+            let transaction = self
+                .pool
+                .begin()
+                .await
+                .context("Failed to begin a transaction for a batch of loaded records.")?;
+            self.transaction = Some(transaction);
+        }
+
+        Ok(self.transaction.as_mut().expect("batch transaction begun"))
+    }
+
+    /// Counts a record saved in the batch in progress, committing the batch once it is full.
+    pub async fn record_saved(&mut self) -> Result<(), LoaderError> {
+        self.nr_pending += 1;
+        if LOAD_BATCH_SIZE <= self.nr_pending {
+            self.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Commits the batch in progress, if any.
+    pub async fn commit(&mut self) -> Result<(), LoaderError> {
+        if let Some(transaction) = self.transaction.take() {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit a batch of loaded records.")?;
+            tracing::info!(nr_records=%self.nr_pending, "committed batch of loaded records");
+        }
+        self.nr_committed += self.nr_pending;
+        self.nr_pending = 0;
+        Ok(())
+    }
+
+    /// Rolls back the batch in progress, if any, returning the number of saved records discarded.
+    pub async fn rollback(&mut self) -> Result<usize, LoaderError> {
+        if let Some(transaction) = self.transaction.take() {
+            transaction
+                .rollback()
+                .await
+                .context("Failed to roll back a batch of loaded records.")?;
+        }
+        let nr_discarded = self.nr_pending;
+        tracing::warn!(%nr_discarded, "rolled back batch of loaded records");
+        self.nr_pending = 0;
+        Ok(nr_discarded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_load_outcome() {
+        assert_eq!(LoadOutcome::of(0), LoadOutcome::Clean);
+        assert_eq!(LoadOutcome::of(1), LoadOutcome::CompletedWithRejects);
+        assert_eq!(LoadOutcome::Clean.exit_code(), EXIT_CLEAN);
+        assert_eq!(
+            LoadOutcome::CompletedWithRejects.exit_code(),
+            EXIT_COMPLETED_WITH_REJECTS
+        );
+        assert_ne!(EXIT_COMPLETED_WITH_REJECTS, EXIT_USAGE);
+    }
+
+    #[test]
+    fn test_max_errors_threshold() {
+        let thresholds = AbortThresholds { max_errors: Some(5), max_error_rate: None };
+        assert_ok!(thresholds.check(4, 10, false));
+        assert_ok!(thresholds.check(5, 10, false));
+        assert_eq!(
+            assert_err!(thresholds.check(6, 10, false)),
+            ThresholdBreach::TooManyErrors { nr_rejects: 6, max: 5 }
+        );
+
+        assert_ok!(AbortThresholds::default().check(1_000, 1_000, true));
+    }
+
+    #[test]
+    fn test_max_error_rate_threshold() {
+        let thresholds = AbortThresholds { max_errors: None, max_error_rate: Some(0.1) };
+
+        // the rate is not evaluated until enough records are processed
+        assert_ok!(thresholds.check(50, MIN_RECORDS_FOR_RATE - 1, false));
+        assert_err!(thresholds.check(11, MIN_RECORDS_FOR_RATE, false));
+
+        // exactly the maximum rate is accepted
+        assert_ok!(thresholds.check(10, 100, false));
+        let breach = assert_err!(thresholds.check(21, 200, false));
+        assert!(matches!(
+            breach,
+            ThresholdBreach::ErrorRateExceeded { nr_records: 200, .. }
+        ));
+
+        // a smaller file is evaluated once complete
+        assert_ok!(thresholds.check(1, 20, true));
+        assert_err!(thresholds.check(3, 20, true));
+        assert_ok!(thresholds.check(0, 0, true));
+    }
+}
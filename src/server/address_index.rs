@@ -0,0 +1,292 @@
+use crate::core::domain::property::PropertyRecordRepository;
+use crate::core::domain::{Address, AddressStandardizer, AssessorParcelNumber, ZipOrPostalCode};
+use crate::core::{CoreError, TableFreshness};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressSuggestion {
+    pub apn: AssessorParcelNumber,
+    pub display: String,
+    pub address: Address,
+}
+
+#[derive(Debug)]
+struct IndexEntry {
+    key: String,
+    apn: AssessorParcelNumber,
+    address: Address,
+}
+
+/// In-memory prefix index over the normalized display form of property addresses, standardized as
+/// loaded addresses are so that properties stored before standardization are found alike. Entries
+/// are kept sorted by key so that the entries sharing a prefix are found by binary search and are
+/// contiguous, and each zip code keeps its own (also sorted) list of entry positions.
+#[derive(Debug)]
+pub struct AddressIndex {
+    entries: Vec<IndexEntry>,
+    by_zip: HashMap<String, Vec<usize>>,
+    freshness: TableFreshness,
+}
+
+impl AddressIndex {
+    pub fn new(addresses: Vec<(AssessorParcelNumber, Address)>, freshness: TableFreshness) -> Self {
+        let mut entries: Vec<IndexEntry> = addresses
+            .into_iter()
+            .map(|(apn, address)| {
+                let address = AddressStandardizer::address(&address);
+                IndexEntry {
+                    key: Self::normalize(&address.to_string()),
+                    apn,
+                    address,
+                }
+            })
+            .collect();
+        entries.sort_by(|lhs, rhs| lhs.key.cmp(&rhs.key));
+
+        let mut by_zip: HashMap<String, Vec<usize>> = HashMap::new();
+        for (pos, entry) in entries.iter().enumerate() {
+            by_zip
+                .entry(entry.address.zip_or_postal_code.to_string())
+                .or_insert_with(Vec::new)
+                .push(pos);
+        }
+
+        Self { entries, by_zip, freshness }
+    }
+
+    #[tracing::instrument(level = "info", skip(pool))]
+    pub async fn load(pool: &PgPool) -> Result<Self, CoreError> {
+        let freshness = PropertyRecordRepository::find_freshness(pool).await?;
+        let addresses = PropertyRecordRepository::find_all_addresses(pool).await?;
+        Ok(Self::new(addresses, freshness))
+    }
+
+    /// Normalizes free text to the form used for index keys: upper case, without punctuation and with
+    /// single spaces between words.
+    pub fn normalize(text: &str) -> String {
+        text.to_uppercase()
+            .replace(|c: char| !c.is_alphanumeric() && c != '#', " ")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn freshness(&self) -> &TableFreshness {
+        &self.freshness
+    }
+
+    /// Suggests up to `limit` addresses beginning with the prefix, optionally restricted to a zip
+    /// code, in key order. The prefix is searched both as typed and with its words standardized, so
+    /// "123 Main Street" finds "123 MAIN ST" while a partly typed word, such as "123 Main Stre", still
+    /// matches as typed. The search is abandoned once the deadline passes, returning the suggestions
+    /// found so far.
+    pub fn suggest(
+        &self, prefix: &str, zip_code: Option<&ZipOrPostalCode>, limit: usize, deadline: Instant,
+    ) -> Vec<AddressSuggestion> {
+        let prefix = Self::normalize(prefix);
+        let standardized = AddressStandardizer::address_words(&prefix);
+
+        let mut positions = self.find_prefixed(&prefix, zip_code, limit, deadline);
+        if standardized != prefix {
+            positions.extend(self.find_prefixed(&standardized, zip_code, limit, deadline));
+            positions.sort_unstable();
+            positions.dedup();
+            positions.truncate(limit);
+        }
+
+        positions
+            .into_iter()
+            .map(|pos| {
+                let entry = &self.entries[pos];
+                AddressSuggestion {
+                    apn: entry.apn.clone(),
+                    display: entry.address.to_string(),
+                    address: entry.address.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Positions, in key order, of up to `limit` entries whose keys begin with the normalized prefix.
+    fn find_prefixed(
+        &self, prefix: &str, zip_code: Option<&ZipOrPostalCode>, limit: usize, deadline: Instant,
+    ) -> Vec<usize> {
+        let positions: Box<dyn Iterator<Item = usize> + '_> = match zip_code {
+            Some(zip) => {
+                let zip_positions = match self.by_zip.get(zip.as_ref()) {
+                    Some(positions) => positions.as_slice(),
+                    None => return vec![],
+                };
+                let start = zip_positions.partition_point(|pos| self.entries[*pos].key.as_str() < prefix);
+                Box::new(zip_positions[start..].iter().copied())
+            }
+            None => {
+                let start = self.entries.partition_point(|entry| entry.key.as_str() < prefix);
+                Box::new(start..self.entries.len())
+            }
+        };
+
+        let mut found = Vec::with_capacity(limit);
+        for pos in positions {
+            if !self.entries[pos].key.starts_with(prefix) || limit <= found.len() {
+                break;
+            }
+            if deadline <= Instant::now() {
+                tracing::warn!(%prefix, nr_suggestions=%found.len(), "address suggestion exceeded latency budget");
+                break;
+            }
+            found.push(pos);
+        }
+        found
+    }
+}
+
+/// Shared handle to the current address index, which is swapped out wholesale when refreshed so that
+/// suggestions are never blocked by a rebuild.
+#[derive(Debug, Clone, Default)]
+pub struct AddressIndexHandle(Arc<RwLock<Option<Arc<AddressIndex>>>>);
+
+impl AddressIndexHandle {
+    /// The current index, or `None` while the index is warming up at server start.
+    pub fn current(&self) -> Option<Arc<AddressIndex>> {
+        self.0.read().ok().and_then(|index| index.clone())
+    }
+
+    /// Rebuilds the index if the stored properties changed since it was last built, returning whether
+    /// the index was rebuilt.
+    #[tracing::instrument(level = "info", skip(self, pool))]
+    pub async fn refresh(&self, pool: &PgPool) -> Result<bool, CoreError> {
+        let freshness = PropertyRecordRepository::find_freshness(pool).await?;
+        if self.current().map(|index| index.freshness == freshness).unwrap_or(false) {
+            return Ok(false);
+        }
+
+        let index = AddressIndex::load(pool).await?;
+        tracing::info!(nr_addresses=%index.len(), freshness=?index.freshness, "address index rebuilt");
+        if let Ok(mut current) = self.0.write() {
+            *current = Some(Arc::new(index));
+        }
+        Ok(true)
+    }
+
+    /// Warms the index and then keeps it fresh by periodically checking whether properties were loaded.
+    pub fn spawn_refresh(self, pool: PgPool, refresh_interval: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(error) = self.refresh(&pool).await {
+                    tracing::error!(?error, "failed to refresh address index");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::{AddressLine, City, StateOrRegion, StreetDirection};
+    use claim::assert_ok;
+    use pretty_assertions::assert_eq;
+
+    fn address(number: &str, name: &str, suffix: &str, zip: &str) -> anyhow::Result<Address> {
+        Ok(Address::new_in_usa(
+            AddressLine::new(number, name, suffix, StreetDirection::None),
+            None,
+            City::new("SEATTLE"),
+            StateOrRegion::new("WA"),
+            ZipOrPostalCode::new(zip)?,
+        ))
+    }
+
+    #[test]
+    fn test_address_index_suggest() -> anyhow::Result<()> {
+        let index = AddressIndex::new(
+            vec![
+                (
+                    assert_ok!(AssessorParcelNumber::new("11")),
+                    address("583", "BATTERY", "ST", "98121")?,
+                ),
+                (
+                    assert_ok!(AssessorParcelNumber::new("22")),
+                    address("76", "CEDAR", "ST", "98121")?,
+                ),
+                (
+                    assert_ok!(AssessorParcelNumber::new("33")),
+                    address("583", "BELL", "ST", "98121")?,
+                ),
+                (
+                    assert_ok!(AssessorParcelNumber::new("44")),
+                    address("583", "BATTERY", "AVENUE", "98027")?,
+                ),
+                (
+                    assert_ok!(AssessorParcelNumber::new("55")),
+                    address("123", "LAKE FOREST", "DRIVE", "98121")?,
+                ),
+            ],
+            TableFreshness { nr_rows: 5, last_updated_on: None },
+        );
+        let deadline = Instant::now() + Duration::from_secs(1);
+
+        let actual: Vec<String> = index
+            .suggest("583 b", None, 10, deadline)
+            .into_iter()
+            .map(|s| s.apn.to_string())
+            .collect();
+        assert_eq!(actual, vec!["00000000000044", "00000000000011", "00000000000033"]);
+
+        let zip = assert_ok!(ZipOrPostalCode::new("98121"));
+        let actual: Vec<String> = index
+            .suggest("583 Battery", Some(&zip), 10, deadline)
+            .into_iter()
+            .map(|s| s.apn.to_string())
+            .collect();
+        assert_eq!(actual, vec!["00000000000011"]);
+
+        let actual: Vec<String> = index
+            .suggest("583 Battery Street", None, 10, deadline)
+            .into_iter()
+            .map(|s| s.apn.to_string())
+            .collect();
+        assert_eq!(actual, vec!["00000000000011"]);
+
+        // stored addresses are standardized when indexed
+        assert_eq!(
+            index.suggest("76 Cedar St", None, 10, deadline)[0].display,
+            "76 CEDAR ST, SEATTLE, WA 98121, USA"
+        );
+        let actual: Vec<String> = index
+            .suggest("583 Battery Av", None, 10, deadline)
+            .into_iter()
+            .map(|s| s.apn.to_string())
+            .collect();
+        assert_eq!(actual, vec!["00000000000044"]);
+
+        // only the trailing suffix is abbreviated, so multi-word street names are found
+        for prefix in &["123 Lake Forest", "123 Lake Forest Dr", "123 Lake Forest Drive"] {
+            let actual: Vec<String> = index
+                .suggest(prefix, None, 10, deadline)
+                .into_iter()
+                .map(|s| s.apn.to_string())
+                .collect();
+            assert_eq!(actual, vec!["00000000000055"], "suggestions for {}", prefix);
+        }
+
+        assert_eq!(index.suggest("583", None, 2, deadline).len(), 2);
+        assert!(index.suggest("9", None, 10, deadline).is_empty());
+        Ok(())
+    }
+}
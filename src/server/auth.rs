@@ -0,0 +1,201 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage};
+use sqlx::PgPool;
+
+use crate::core::domain::api_key::{ApiKey, ApiKeyRepository, Scope};
+use crate::server::errors::ProblemDetails;
+use crate::server::routes::PropensityRouteError;
+use crate::server::settings::AuthSettings;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Paths served without an API key so that orchestrators, metric scrapers and readers of the API
+/// documentation need no credentials.
+pub(crate) const PUBLIC_PATHS: [&str; 6] = [
+    "/health_check",
+    "/health/live",
+    "/health/ready",
+    "/metrics",
+    "/openapi.json",
+    "/docs",
+];
+
+/// The client making a request, as identified by its API key. Handlers extract it via
+/// `web::ReqData<ApiCaller>` to check the scopes the request requires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiCaller {
+    pub key_id: Option<i32>,
+    pub name: String,
+    pub key_prefix: Option<String>,
+    scopes: Vec<Scope>,
+}
+
+impl ApiCaller {
+    /// Caller used when authentication is disabled, which is granted every scope.
+    pub fn unauthenticated() -> Self {
+        Self {
+            key_id: None,
+            name: "unauthenticated".to_string(),
+            key_prefix: None,
+            scopes: Scope::ALL.to_vec(),
+        }
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn require_scope(&self, scope: Scope) -> Result<(), PropensityRouteError> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(PropensityRouteError::MissingScope(scope))
+        }
+    }
+}
+
+impl From<ApiKey> for ApiCaller {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            key_id: Some(key.id),
+            name: key.name,
+            key_prefix: Some(key.key_prefix),
+            scopes: key.scopes,
+        }
+    }
+}
+
+/// Middleware authenticating requests by API key, presented either as a bearer token in the
+/// `Authorization` header or in the `X-API-Key` header. The authenticated `ApiCaller` is added to the
+/// request extensions, and every authenticated request is written to the audit log.
+#[derive(Clone)]
+pub struct ApiKeyAuth {
+    pool: PgPool,
+    settings: AuthSettings,
+}
+
+impl ApiKeyAuth {
+    pub fn new(pool: PgPool, settings: AuthSettings) -> Self {
+        Self { pool, settings }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyAuthMiddleware {
+            service: Rc::new(service),
+            pool: self.pool.clone(),
+            settings: self.settings.clone(),
+        }))
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: Rc<S>,
+    pool: PgPool,
+    settings: AuthSettings,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if !self.settings.enabled || PUBLIC_PATHS.contains(&req.path()) {
+            req.extensions_mut().insert(ApiCaller::unauthenticated());
+            return Box::pin(self.service.call(req));
+        }
+
+        let service = Rc::clone(&self.service);
+        let pool = self.pool.clone();
+        Box::pin(async move {
+            let key = match presented_key(&req) {
+                Some(key) => key,
+                None => return Err(unauthorized("An API key is required.")),
+            };
+
+            let caller: ApiCaller = match ApiKeyRepository::find_active(&key, &pool).await {
+                Ok(Some(api_key)) => api_key.into(),
+                Ok(None) => {
+                    tracing::warn!(target: "audit", path=%req.path(), "rejected unrecognized or revoked API key");
+                    return Err(unauthorized("The API key is not recognized or has been revoked."));
+                }
+                Err(error) => {
+                    tracing::error!(?error, "failed to authenticate API key");
+                    let problem = ProblemDetails::for_status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .with_detail("An unexpected error occurred while authenticating the request.");
+                    return Err(InternalError::from_response(error, problem.into_response()).into());
+                }
+            };
+
+            let method = req.method().to_string();
+            let path = req.path().to_string();
+            let query = req.query_string().to_string();
+            req.extensions_mut().insert(caller.clone());
+
+            let response = service.call(req).await;
+            let status = match &response {
+                Ok(response) => response.status(),
+                Err(error) => error.as_response_error().status_code(),
+            };
+            tracing::info!(
+                target: "audit",
+                api_key_id=?caller.key_id,
+                api_key_name=%caller.name,
+                api_key_prefix=?caller.key_prefix,
+                %method, %path, %query, status=%status.as_u16(),
+                "api request"
+            );
+            response
+        })
+    }
+}
+
+fn presented_key(req: &ServiceRequest) -> Option<String> {
+    let headers = req.headers();
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let api_key = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+
+    bearer
+        .or(api_key)
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+}
+
+fn unauthorized(detail: &str) -> Error {
+    let problem = ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized", "Unauthorized").with_detail(detail);
+    let mut response = problem.into_response();
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    InternalError::from_response(detail.to_string(), response).into()
+}
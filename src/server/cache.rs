@@ -0,0 +1,319 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+
+use crate::core::domain::property::PropertyFields;
+use crate::core::domain::{ScoredProperty, ZipOrPostalCode, ZipSearchOptions};
+use crate::core::{LoadedData, DATA_LOADED_CHANNEL};
+use crate::server::address_index::AddressIndexHandle;
+use crate::server::metrics;
+use crate::server::settings::ResponseCacheSettings;
+
+/// Delay before listening for data load notifications again after the listener fails.
+const LISTENER_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct CacheEntry<V> {
+    value: V,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct CacheState<K, V> {
+    entries: HashMap<K, CacheEntry<V>>,
+    /// Keys by the tick at which they were last used, least recent first.
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+    /// Advanced by each flush, so responses loaded before a flush are not cached after it.
+    generation: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> CacheState<K, V> {
+    fn touch(&mut self, key: &K) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.recency.insert(tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+/// Bounded in-memory cache of responses, evicting the least recently used response beyond its
+/// capacity and expiring responses after their time to live. Clones share the same cache.
+#[derive(Debug, Clone)]
+pub struct ResponseCache<K, V> {
+    name: &'static str,
+    settings: ResponseCacheSettings,
+    state: Arc<Mutex<CacheState<K, V>>>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> ResponseCache<K, V> {
+    pub fn new(name: &'static str, settings: ResponseCacheSettings) -> Self {
+        Self {
+            name,
+            settings,
+            state: Arc::new(Mutex::new(CacheState {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+                generation: 0,
+            })),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.settings.enabled && 0 < self.settings.capacity
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.settings.ttl_secs)
+    }
+
+    pub fn get(&self, key: &K, now: Instant) -> Option<V> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let mut state = self.state.lock().expect("response cache lock poisoned");
+        let expired = match state.entries.get(key) {
+            None => return None,
+            Some(entry) => self.ttl() <= now.saturating_duration_since(entry.cached_at),
+        };
+
+        if expired {
+            state.remove(key);
+            metrics::set_cache_size(self.name, state.entries.len());
+            None
+        } else {
+            state.touch(key);
+            state.entries.get(key).map(|entry| entry.value.clone())
+        }
+    }
+
+    /// The current generation of the cache, to be taken before loading a response to insert.
+    pub fn generation(&self) -> u64 {
+        self.state.lock().map(|state| state.generation).unwrap_or(0)
+    }
+
+    /// Caches the response loaded as of the given generation, unless the cache was flushed since.
+    pub fn insert(&self, key: K, value: V, generation: u64, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut state = self.state.lock().expect("response cache lock poisoned");
+        if generation < state.generation {
+            tracing::debug!(
+                cache=%self.name, %generation, current=%state.generation,
+                "discarding response loaded before the cache was flushed"
+            );
+            return;
+        }
+
+        state.remove(&key);
+        state
+            .entries
+            .insert(key.clone(), CacheEntry { value, cached_at: now, last_used: 0 });
+        state.touch(&key);
+
+        while self.settings.capacity < state.entries.len() {
+            let least_recent = state.recency.iter().next().map(|(_, key)| key.clone());
+            match least_recent {
+                Some(evicted) => state.remove(&evicted),
+                None => break,
+            }
+        }
+        metrics::set_cache_size(self.name, state.entries.len());
+    }
+
+    /// Returns the cached response for the key, or loads and caches it on a miss.
+    pub async fn get_or_try_load<F, E>(&self, key: K, load: F) -> Result<V, E>
+    where
+        F: Future<Output = Result<V, E>>,
+    {
+        if let Some(value) = self.get(&key, Instant::now()) {
+            metrics::record_cache_lookup(self.name, true);
+            return Ok(value);
+        }
+
+        metrics::record_cache_lookup(self.name, false);
+        let generation = self.generation();
+        let value = load.await?;
+        self.insert(key, value.clone(), generation, Instant::now());
+        Ok(value)
+    }
+
+    /// Discards every cached response, returning the number discarded.
+    pub fn flush(&self) -> usize {
+        let mut state = self.state.lock().expect("response cache lock poisoned");
+        let nr_flushed = state.entries.len();
+        state.generation += 1;
+        state.entries.clear();
+        state.recency.clear();
+        metrics::set_cache_size(self.name, 0);
+        nr_flushed
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().map(|state| state.entries.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Normalized parameters of a propensity search, so equivalent requests share a cached response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ZipScoresKey {
+    pub zip_code: String,
+    pub model: String,
+    pub limit: u16,
+    pub fields: PropertyFields,
+    pub options: ZipSearchOptions,
+}
+
+impl ZipScoresKey {
+    pub fn new(
+        zip_code: &ZipOrPostalCode, model: &str, limit: u16, fields: &PropertyFields, options: ZipSearchOptions,
+    ) -> Self {
+        Self {
+            zip_code: zip_code.as_ref().to_string(),
+            model: model.to_string(),
+            limit,
+            fields: fields.clone(),
+            options,
+        }
+    }
+}
+
+/// Cache of the top scored properties in a zip code, in front of
+/// `PropertyPropensityScoreRepository::find_address_scores_for_zip_code`. Addresses are cached
+/// regardless of the caller, and are omitted for callers lacking the scope when responding.
+pub type ZipScoresCache = ResponseCache<ZipScoresKey, Arc<Vec<ScoredProperty>>>;
+
+pub const ZIP_SCORES_CACHE: &str = "zip_scores";
+
+/// Listens for data load notifications from the loader, flushing the cache when any load completes
+/// and rebuilding the address index when properties were loaded. Since notifications sent while the
+/// listener is disconnected are lost, the cache is also flushed whenever the listener reconnects.
+pub fn spawn_invalidation_listener(cache: ZipScoresCache, address_index: AddressIndexHandle, pool: PgPool) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(error) = listen_for_data_loads(&cache, &address_index, &pool).await {
+                tracing::error!(?error, "data load listener failed; retrying");
+            }
+            tokio::time::sleep(LISTENER_RETRY_DELAY).await;
+        }
+    });
+}
+
+async fn listen_for_data_loads(
+    cache: &ZipScoresCache, address_index: &AddressIndexHandle, pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(DATA_LOADED_CHANNEL).await?;
+    tracing::info!(channel=%DATA_LOADED_CHANNEL, "listening for data load notifications");
+
+    loop {
+        match listener.try_recv().await? {
+            Some(notification) => {
+                let loaded = LoadedData::from_payload(notification.payload());
+                let nr_flushed = cache.flush();
+                tracing::info!(?loaded, %nr_flushed, "data load completed; flushed response cache");
+
+                if loaded != Some(LoadedData::Propensities) {
+                    if let Err(error) = address_index.refresh(pool).await {
+                        tracing::error!(?error, "failed to refresh address index after data load");
+                    }
+                }
+            }
+            None => {
+                let nr_flushed = cache.flush();
+                tracing::warn!(%nr_flushed, "data load listener reconnecting; flushed response cache");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_none, assert_ok};
+    use pretty_assertions::assert_eq;
+
+    fn cache(capacity: usize) -> ResponseCache<&'static str, u32> {
+        ResponseCache::new("test", ResponseCacheSettings { enabled: true, capacity, ttl_secs: 60 })
+    }
+
+    #[test]
+    fn test_response_cache_evicts_least_recently_used() {
+        let cache = cache(2);
+        let now = Instant::now();
+        cache.insert("98121", 1, 0, now);
+        cache.insert("98122", 2, 0, now);
+        assert_eq!(cache.get(&"98121", now), Some(1));
+
+        cache.insert("98123", 3, 0, now);
+        assert_eq!(cache.len(), 2);
+        assert_none!(cache.get(&"98122", now));
+        assert_eq!(cache.get(&"98121", now), Some(1));
+        assert_eq!(cache.get(&"98123", now), Some(3));
+
+        assert_eq!(cache.flush(), 2);
+        assert!(cache.is_empty());
+        assert_none!(cache.get(&"98121", now));
+    }
+
+    #[test]
+    fn test_response_cache_expires_entries() {
+        let cache = cache(2);
+        let now = Instant::now();
+        cache.insert("98121", 1, 0, now);
+        assert_eq!(cache.get(&"98121", now + Duration::from_secs(59)), Some(1));
+        assert_none!(cache.get(&"98121", now + Duration::from_secs(60)));
+        assert!(cache.is_empty());
+
+        let disabled = ResponseCache::new(
+            "test",
+            ResponseCacheSettings { enabled: false, capacity: 2, ttl_secs: 60 },
+        );
+        disabled.insert("98121", 1, 0, now);
+        assert_none!(disabled.get(&"98121", now));
+
+        let loaded: Result<u32, ()> =
+            actix_web::rt::System::new().block_on(cache.get_or_try_load("98122", async { Ok(2) }));
+        assert_eq!(assert_ok!(loaded), 2);
+        assert_eq!(cache.get(&"98122", Instant::now()), Some(2));
+    }
+    #[test]
+    fn test_response_cache_discards_responses_loaded_before_flush() {
+        let cache = cache(2);
+        let now = Instant::now();
+        let before_flush = cache.generation();
+        cache.insert("98121", 1, before_flush, now);
+        assert_eq!(cache.flush(), 1);
+
+        cache.insert("98122", 2, before_flush, now);
+        assert!(cache.is_empty());
+        assert_none!(cache.get(&"98122", now));
+
+        let after_flush = cache.generation();
+        cache.insert("98122", 2, after_flush, now);
+        assert_eq!(cache.get(&"98122", now), Some(2));
+    }
+}
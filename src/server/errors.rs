@@ -1,5 +1,13 @@
+use std::borrow::Cow;
 use std::fmt::Debug;
+use std::future::Future;
+
+use actix_web::error::{InternalError, JsonPayloadError, QueryPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
 use thiserror::Error;
+use validator::ValidationErrors;
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -10,3 +18,184 @@ pub enum PropensityError {
     #[error("{0}")]
     IOError(#[from] std::io::Error),
 }
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+tokio::task_local! {
+    static REQUEST_ID: Option<String>;
+}
+
+/// Runs the request future with its tracing request id available to problem responses, so the id a
+/// client reports can be correlated with the server logs.
+pub fn with_request_id<F: Future>(request_id: Option<String>, request: F) -> impl Future<Output = F::Output> {
+    REQUEST_ID.scope(request_id, request)
+}
+
+fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok().flatten()
+}
+
+/// Problem details for HTTP APIs (RFC 7807) returned as the body of every error response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+
+    pub title: String,
+
+    pub status: u16,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid_params: Vec<InvalidParam>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InvalidParam {
+    pub name: String,
+    pub reason: String,
+}
+
+impl InvalidParam {
+    pub fn new(name: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self { name: name.into(), reason: reason.into() }
+    }
+
+    /// Describes each field failing validation as an invalid request parameter. Fields are reported
+    /// under `param_name` when given, since domain types name their fields independently of the
+    /// request parameters they are parsed from.
+    pub fn from_validation_errors(errors: &ValidationErrors, param_name: Option<&str>) -> Vec<Self> {
+        let mut params: Vec<Self> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, field_errors)| {
+                field_errors.iter().map(move |error| {
+                    let reason = error.message.clone().unwrap_or_else(|| Cow::from(error.code.to_string()));
+                    Self::new(param_name.unwrap_or(field), reason)
+                })
+            })
+            .collect();
+        params.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        params
+    }
+}
+
+impl ProblemDetails {
+    /// A problem identified by a short, stable `kind` (e.g., "invalid-parameter").
+    pub fn new(status: StatusCode, kind: &str, title: impl Into<String>) -> Self {
+        Self {
+            problem_type: format!("urn:address-propensity:problem:{}", kind),
+            title: title.into(),
+            status: status.as_u16(),
+            detail: None,
+            invalid_params: vec![],
+            request_id: current_request_id(),
+        }
+    }
+
+    /// A problem without further semantics than its HTTP status.
+    pub fn for_status(status: StatusCode) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: None,
+            invalid_params: vec![],
+            request_id: current_request_id(),
+        }
+    }
+
+    pub fn with_detail(self, detail: impl Into<String>) -> Self {
+        Self { detail: Some(detail.into()), ..self }
+    }
+
+    pub fn with_invalid_params(self, invalid_params: Vec<InvalidParam>) -> Self {
+        Self { invalid_params, ..self }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type(PROBLEM_CONTENT_TYPE)
+            .json(self)
+    }
+}
+
+/// Problem response for query strings that fail to deserialize into a route's parameters, e.g.
+/// because a required parameter is missing.
+pub fn query_error_handler(error: QueryPayloadError, _req: &HttpRequest) -> Error {
+    let detail = error.to_string();
+    let problem = ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid-query", "Invalid query parameters")
+        .with_invalid_params(invalid_params_from_serde_message(&detail))
+        .with_detail(detail);
+    InternalError::from_response(error, problem.into_response()).into()
+}
+
+/// Problem response for request bodies that are not acceptable JSON for the route.
+pub fn json_error_handler(error: JsonPayloadError, _req: &HttpRequest) -> Error {
+    let status = error.status_code();
+    let problem = ProblemDetails::new(status, "invalid-body", "Invalid request body").with_detail(error.to_string());
+    InternalError::from_response(error, problem.into_response()).into()
+}
+
+/// Problem response for requests that do not match any route.
+pub async fn not_found() -> HttpResponse {
+    ProblemDetails::for_status(StatusCode::NOT_FOUND).into_response()
+}
+
+fn invalid_params_from_serde_message(message: &str) -> Vec<InvalidParam> {
+    lazy_static::lazy_static! {
+        static ref RE_MISSING_FIELD: regex::Regex = regex::Regex::new(r##"missing field `([^`]+)`"##).unwrap();
+    }
+
+    RE_MISSING_FIELD
+        .captures(message)
+        .map(|c| vec![InvalidParam::new(&c[1], "required parameter is missing")])
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use validator::ValidationError;
+
+    #[test]
+    fn test_problem_details_serialization() -> anyhow::Result<()> {
+        let mut errors = ValidationErrors::new();
+        let mut error = ValidationError::new("regex");
+        error.message = Some(Cow::from("only 5 digit US zip codes are supported"));
+        errors.add("code", error);
+
+        let problem = ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid-parameter", "Invalid parameter")
+            .with_invalid_params(InvalidParam::from_validation_errors(&errors, Some("zip_code")));
+
+        assert_eq!(
+            serde_json::to_value(&problem)?,
+            serde_json::json!({
+                "type": "urn:address-propensity:problem:invalid-parameter",
+                "title": "Invalid parameter",
+                "status": 400,
+                "invalid_params": [{ "name": "zip_code", "reason": "only 5 digit US zip codes are supported" }],
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_params_from_serde_message() {
+        assert_eq!(
+            invalid_params_from_serde_message("Query deserialize error: missing field `zip_code`"),
+            vec![InvalidParam::new("zip_code", "required parameter is missing")]
+        );
+        assert!(invalid_params_from_serde_message("invalid digit found in string").is_empty());
+    }
+}
@@ -0,0 +1,453 @@
+use actix_web::http::header::{Accept, HeaderValue, VARY};
+use actix_web::HttpResponse;
+use anyhow::Context;
+use bigdecimal::BigDecimal;
+use serde_json::{json, Value};
+
+use crate::core::domain::property::{PropertyField, PropertyFields};
+use crate::core::domain::GeoCoordinate;
+use crate::server::routes::{PropensityRouteError, PropensitySearchItem};
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+pub const GEO_JSON_CONTENT_TYPE: &str = "application/geo+json";
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Representation of a propensity search response, chosen by the `format` query parameter or else
+/// negotiated from the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    Csv,
+    GeoJson,
+}
+
+impl ResponseFormat {
+    /// Chooses the response format. An explicit `format` parameter takes precedence and must name a
+    /// supported format; otherwise the most preferred supported media type in the `Accept` header is
+    /// used, falling back to JSON.
+    pub fn negotiate(format: Option<&str>, accept: Option<&Accept>) -> Result<Self, PropensityRouteError> {
+        if let Some(format) = format {
+            return Self::from_param(format).ok_or_else(|| PropensityRouteError::UnsupportedFormat(format.to_string()));
+        }
+
+        let preferred = accept.and_then(|accept| {
+            accept
+                .mime_precedence()
+                .iter()
+                .find_map(|mime| Self::from_media_type(mime.essence_str()))
+        });
+        Ok(preferred.unwrap_or(Self::Json))
+    }
+
+    fn from_param(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "geojson" => Some(Self::GeoJson),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "text/csv" => Some(Self::Csv),
+            "application/geo+json" => Some(Self::GeoJson),
+            _ => None,
+        }
+    }
+
+    /// Whether this format presents property locations, which must then be selected regardless of the
+    /// requested property fields.
+    pub fn needs_location(&self) -> bool {
+        !matches!(self, Self::Json)
+    }
+
+    /// Whether this format cannot be rendered without property locations.
+    pub fn requires_location(&self) -> bool {
+        matches!(self, Self::GeoJson)
+    }
+
+    /// Renders scored properties, with their locations, in this format. Tabular formats have a column
+    /// for each of the requested property fields, and latitude and longitude columns only
+    /// `with_location`.
+    pub fn respond(
+        &self, items: Vec<(PropensitySearchItem, Option<GeoCoordinate>)>, fields: &PropertyFields, with_location: bool,
+    ) -> Result<HttpResponse, PropensityRouteError> {
+        let mut response = match self {
+            Self::Json => {
+                let items: Vec<PropensitySearchItem> = items.into_iter().map(|(item, _)| item).collect();
+                HttpResponse::Ok().json(items)
+            }
+            Self::Csv => HttpResponse::Ok()
+                .content_type(CSV_CONTENT_TYPE)
+                .body(to_csv(&items, fields, with_location)?),
+            Self::GeoJson => HttpResponse::Ok()
+                .content_type(GEO_JSON_CONTENT_TYPE)
+                .body(to_feature_collection(&items).to_string()),
+        };
+
+        response.headers_mut().insert(VARY, HeaderValue::from_static("Accept"));
+        Ok(response)
+    }
+}
+
+/// Representation of a streamed export, chosen by the `format` query parameter or else negotiated
+/// from the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    NdJson,
+    Csv,
+}
+
+impl ExportFormat {
+    /// Chooses the export format as `ResponseFormat::negotiate` does, falling back to NDJSON.
+    pub fn negotiate(format: Option<&str>, accept: Option<&Accept>) -> Result<Self, PropensityRouteError> {
+        if let Some(format) = format {
+            return match format.trim().to_lowercase().as_str() {
+                "ndjson" => Ok(Self::NdJson),
+                "csv" => Ok(Self::Csv),
+                _ => Err(PropensityRouteError::UnsupportedFormat(format.to_string())),
+            };
+        }
+
+        let preferred = accept.and_then(|accept| {
+            accept.mime_precedence().iter().find_map(|mime| match mime.essence_str() {
+                "application/x-ndjson" | "*/*" => Some(Self::NdJson),
+                "text/csv" => Some(Self::Csv),
+                _ => None,
+            })
+        });
+        Ok(preferred.unwrap_or(Self::NdJson))
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::NdJson => NDJSON_CONTENT_TYPE,
+            Self::Csv => CSV_CONTENT_TYPE,
+        }
+    }
+
+    /// Whether this format presents property locations, which must then be selected regardless of the
+    /// requested property fields.
+    pub fn needs_location(&self) -> bool {
+        matches!(self, Self::Csv)
+    }
+
+    /// Encoded content preceding the exported rows; i.e., the CSV headers.
+    pub fn preamble(&self, fields: &PropertyFields, with_location: bool) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::NdJson => Ok(vec![]),
+            Self::Csv => write_csv(&[], fields, with_location, true),
+        }
+    }
+
+    /// Encodes a chunk of exported rows.
+    pub fn encode(
+        &self, items: &[(PropensitySearchItem, Option<GeoCoordinate>)], fields: &PropertyFields, with_location: bool,
+    ) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::NdJson => {
+                let mut chunk = vec![];
+                for (item, _) in items {
+                    serde_json::to_writer(&mut chunk, item).context("Failed to write propensity results as JSON.")?;
+                    chunk.push(b'\n');
+                }
+                Ok(chunk)
+            }
+            Self::Csv => write_csv(items, fields, with_location, false),
+        }
+    }
+}
+
+const CSV_HEADERS: [&str; 7] = [
+    "apn",
+    "score",
+    "tier",
+    "address_line",
+    "city",
+    "state_or_region",
+    "zip_code",
+];
+
+const CSV_LOCATION_HEADERS: [&str; 2] = ["latitude", "longitude"];
+
+/// Requested property fields given their own CSV columns, following the standard columns. Coordinates
+/// are already presented by the latitude and longitude columns.
+fn csv_fields(fields: &PropertyFields) -> impl Iterator<Item = PropertyField> + '_ {
+    fields.iter().filter(|field| *field != PropertyField::Coordinates)
+}
+
+fn csv_record(
+    item: &PropensitySearchItem, location: Option<&GeoCoordinate>, fields: &PropertyFields, with_location: bool,
+) -> Vec<String> {
+    let address = item.address.as_ref();
+    let mut record = vec![
+        Some(item.apn.to_string()),
+        Some(item.propensity_score.score.to_string()),
+        item.tier.clone(),
+        address.map(|a| match &a.secondary_address_line {
+            Some(secondary) => format!("{} {}", a.address_line, secondary),
+            None => a.address_line.to_string(),
+        }),
+        address.map(|a| a.city.to_string()),
+        address.map(|a| a.state_or_region.to_string()),
+        address
+            .map(|a| a.zip_or_postal_code.to_string())
+            .or_else(|| item.zip_code.clone()),
+    ];
+    if with_location {
+        record.push(location.map(|l| l.latitude.to_string()));
+        record.push(location.map(|l| l.longitude.to_string()));
+    }
+    record.extend(csv_fields(fields).map(|field| item.attributes.value_of(field)));
+    record.into_iter().map(Option::unwrap_or_default).collect()
+}
+
+fn to_csv(
+    items: &[(PropensitySearchItem, Option<GeoCoordinate>)], fields: &PropertyFields, with_location: bool,
+) -> anyhow::Result<Vec<u8>> {
+    write_csv(items, fields, with_location, true)
+}
+
+fn write_csv(
+    items: &[(PropensitySearchItem, Option<GeoCoordinate>)], fields: &PropertyFields, with_location: bool,
+    with_headers: bool,
+) -> anyhow::Result<Vec<u8>> {
+    // headers are written explicitly so that an empty result still has them
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
+    if with_headers {
+        let location_headers = if with_location { &CSV_LOCATION_HEADERS[..] } else { &[] };
+        let headers = CSV_HEADERS
+            .iter()
+            .chain(location_headers)
+            .copied()
+            .chain(csv_fields(fields).map(|field| field.as_str()));
+        writer
+            .write_record(headers)
+            .context("Failed to write propensity results as CSV.")?;
+    }
+    for (item, location) in items {
+        writer
+            .write_record(csv_record(item, location.as_ref(), fields, with_location))
+            .context("Failed to write propensity results as CSV.")?;
+    }
+    writer
+        .into_inner()
+        .map_err(|err| anyhow::anyhow!("Failed to write propensity results as CSV: {}", err))
+}
+
+/// Converts a stored coordinate to the nearest `f64` by way of its decimal representation, since
+/// `BigDecimal::to_f64` accumulates rounding error (e.g., 47.61413 becomes 47.614129999999996).
+fn to_degrees(coordinate: &BigDecimal) -> Option<f64> {
+    coordinate.to_string().parse().ok()
+}
+
+/// GeoJSON FeatureCollection of scored properties, each a Point at the property's location (or
+/// without geometry if its location is unknown) with its APN, score and address as properties.
+pub(crate) fn to_feature_collection(items: &[(PropensitySearchItem, Option<GeoCoordinate>)]) -> Value {
+    let features: Vec<Value> = items
+        .iter()
+        .map(|(item, location)| {
+            let geometry = location
+                .as_ref()
+                .and_then(|l| to_degrees(&l.longitude).zip(to_degrees(&l.latitude)))
+                .map(|(longitude, latitude)| json!({ "type": "Point", "coordinates": [longitude, latitude] }))
+                .unwrap_or(Value::Null);
+
+            json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": item,
+            })
+        })
+        .collect();
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::property::PropertyAttributes;
+    use crate::core::domain::{
+        Address, AddressLine, AssessorParcelNumber, City, PropensityScore, StateOrRegion, StreetDirection,
+        ZipOrPostalCode,
+    };
+    use actix_web::http::header::Header;
+    use actix_web::test::TestRequest;
+    use claim::{assert_err, assert_ok};
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    fn accept(value: &str) -> Accept {
+        let request = TestRequest::default().insert_header(("Accept", value)).to_http_request();
+        assert_ok!(Accept::parse(&request))
+    }
+
+    fn items() -> anyhow::Result<Vec<(PropensitySearchItem, Option<GeoCoordinate>)>> {
+        let address = Address::new_in_usa(
+            AddressLine::new("583", "BATTERY", "ST", StreetDirection::None),
+            None,
+            City::new("SEATTLE"),
+            StateOrRegion::new("WA"),
+            ZipOrPostalCode::new("98121")?,
+        );
+        Ok(vec![
+            (
+                PropensitySearchItem {
+                    apn: assert_ok!(AssessorParcelNumber::new("6633050420")),
+                    propensity_score: assert_ok!(PropensityScore::new(259)),
+                    tier: Some("Low".to_string()),
+                    matched: None,
+                    zip_code: None,
+                    address: Some(address),
+                    attributes: PropertyAttributes {
+                        bedrooms: Some(2),
+                        county: Some("KING".to_string()),
+                        ..PropertyAttributes::default()
+                    },
+                },
+                Some(GeoCoordinate::new(
+                    bigdecimal::BigDecimal::from_str("47.614130")?,
+                    bigdecimal::BigDecimal::from_str("-122.348070")?,
+                )),
+            ),
+            (
+                PropensitySearchItem {
+                    apn: assert_ok!(AssessorParcelNumber::new("7656901080")),
+                    propensity_score: assert_ok!(PropensityScore::new(112)),
+                    tier: None,
+                    matched: Some(false),
+                    zip_code: Some("98121".to_string()),
+                    address: None,
+                    attributes: PropertyAttributes::default(),
+                },
+                None,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_response_format_negotiation() {
+        assert_eq!(assert_ok!(ResponseFormat::negotiate(None, None)), ResponseFormat::Json);
+        assert_eq!(
+            assert_ok!(ResponseFormat::negotiate(
+                Some("CSV"),
+                Some(&accept("application/json"))
+            )),
+            ResponseFormat::Csv
+        );
+        assert_err!(ResponseFormat::negotiate(Some("xml"), None));
+        assert_eq!(
+            assert_ok!(ResponseFormat::negotiate(None, Some(&accept("text/csv")))),
+            ResponseFormat::Csv
+        );
+        assert_eq!(
+            assert_ok!(ResponseFormat::negotiate(
+                None,
+                Some(&accept("text/html, application/geo+json;q=0.9, */*;q=0.1"))
+            )),
+            ResponseFormat::GeoJson
+        );
+        assert_eq!(
+            assert_ok!(ResponseFormat::negotiate(None, Some(&accept("image/png")))),
+            ResponseFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_export_encoding() -> anyhow::Result<()> {
+        assert_eq!(assert_ok!(ExportFormat::negotiate(None, None)), ExportFormat::NdJson);
+        assert_eq!(
+            assert_ok!(ExportFormat::negotiate(None, Some(&accept("text/csv")))),
+            ExportFormat::Csv
+        );
+        assert_err!(ExportFormat::negotiate(Some("geojson"), None));
+
+        let items = items()?;
+        let ndjson = String::from_utf8(assert_ok!(ExportFormat::NdJson.encode(
+            &items,
+            &PropertyFields::default(),
+            true
+        )))?;
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"{"apn":"00007656901080","score":112,"matched":false,"zip_code":"98121"}"#
+        );
+        assert!(ndjson.ends_with('\n'));
+
+        let csv = assert_ok!(ExportFormat::Csv.preamble(&PropertyFields::default(), true));
+        let rows = assert_ok!(ExportFormat::Csv.encode(&items[1..], &PropertyFields::default(), true));
+        assert_eq!(
+            String::from_utf8([csv, rows].concat())?,
+            "apn,score,tier,address_line,city,state_or_region,zip_code,latitude,longitude\n00007656901080,112,,,,,98121,,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_requested_fields_rendering() -> anyhow::Result<()> {
+        let items = items()?;
+        let fields: PropertyFields = assert_ok!("county,coordinates,bedrooms".parse());
+        let csv = String::from_utf8(assert_ok!(to_csv(&items, &fields, true)))?;
+        assert_eq!(
+            csv,
+            "apn,score,tier,address_line,city,state_or_region,zip_code,latitude,longitude,bedrooms,county\n\
+             00006633050420,259,Low,583 BATTERY ST,SEATTLE,WA,98121,47.614130,-122.348070,2,KING\n\
+             00007656901080,112,,,,,98121,,,,\n"
+        );
+
+        let ndjson = String::from_utf8(assert_ok!(ExportFormat::NdJson.encode(&items[..1], &fields, true)))?;
+        let line: Value = serde_json::from_str(ndjson.trim_end())?;
+        assert_eq!(line["bedrooms"], 2);
+        assert_eq!(line["county"], "KING");
+        assert_eq!(line["address"]["city"], "SEATTLE");
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_and_geojson_rendering() -> anyhow::Result<()> {
+        let items = items()?;
+        let csv = String::from_utf8(assert_ok!(to_csv(&items, &PropertyFields::default(), true)))?;
+        assert_eq!(
+            csv,
+            "apn,score,tier,address_line,city,state_or_region,zip_code,latitude,longitude\n\
+             00006633050420,259,Low,583 BATTERY ST,SEATTLE,WA,98121,47.614130,-122.348070\n\
+             00007656901080,112,,,,,98121,,\n"
+        );
+
+        let features = to_feature_collection(&items);
+        assert_eq!(features["type"], "FeatureCollection");
+        assert_eq!(
+            features["features"][0]["geometry"],
+            json!({ "type": "Point", "coordinates": [-122.34807, 47.61413] })
+        );
+        assert_eq!(features["features"][0]["properties"]["apn"], "00006633050420");
+        assert_eq!(features["features"][0]["properties"]["score"], 259);
+        assert_eq!(features["features"][0]["properties"]["tier"], "Low");
+        assert_eq!(features["features"][0]["properties"]["address"]["city"], "SEATTLE");
+        assert_eq!(features["features"][1]["geometry"], Value::Null);
+        assert_eq!(
+            features["features"][1]["properties"],
+            json!({ "apn": "00007656901080", "score": 112, "matched": false, "zip_code": "98121" })
+        );
+
+        let fields: PropertyFields = assert_ok!("coordinates,county".parse());
+        let unlocated = String::from_utf8(assert_ok!(to_csv(&items[..1], &fields, false)))?;
+        assert_eq!(
+            unlocated,
+            "apn,score,tier,address_line,city,state_or_region,zip_code,county\n\
+             00006633050420,259,Low,583 BATTERY ST,SEATTLE,WA,98121,KING\n"
+        );
+
+        let empty = String::from_utf8(assert_ok!(to_csv(&[], &PropertyFields::default(), true)))?;
+        assert_eq!(
+            empty,
+            "apn,score,tier,address_line,city,state_or_region,zip_code,latitude,longitude\n"
+        );
+        Ok(())
+    }
+}
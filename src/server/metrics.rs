@@ -0,0 +1,129 @@
+use std::future::Future;
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{web, Error, HttpResponse};
+use lazy_static::lazy_static;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use sqlx::PgPool;
+
+/// Route label for requests not matching any registered route, so arbitrary paths do not each create
+/// a new time series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+lazy_static! {
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "HTTP request latency by method, route pattern and response status.",
+        &["method", "route", "status"]
+    )
+    .expect("failed to register http_request_duration_seconds metric");
+    static ref REPOSITORY_QUERY_DURATION: HistogramVec = register_histogram_vec!(
+        "repository_query_duration_seconds",
+        "Database repository query latency by query.",
+        &["query"]
+    )
+    .expect("failed to register repository_query_duration_seconds metric");
+    static ref REPOSITORY_RESULT_SIZE: HistogramVec = register_histogram_vec!(
+        "repository_result_size",
+        "Number of rows returned by database repository queries.",
+        &["query"],
+        exponential_buckets(1.0, 4.0, 8).expect("invalid result size buckets")
+    )
+    .expect("failed to register repository_result_size metric");
+    static ref DB_POOL_SIZE: IntGauge = register_int_gauge!(
+        "db_pool_connections",
+        "Connections currently held by the database pool."
+    )
+    .expect("failed to register db_pool_connections metric");
+    static ref DB_POOL_IDLE: IntGauge =
+        register_int_gauge!("db_pool_idle_connections", "Idle connections in the database pool.")
+            .expect("failed to register db_pool_idle_connections metric");
+    static ref RESPONSE_CACHE_LOOKUPS: IntCounterVec = register_int_counter_vec!(
+        "response_cache_lookups_total",
+        "Response cache lookups by cache and outcome (hit or miss).",
+        &["cache", "outcome"]
+    )
+    .expect("failed to register response_cache_lookups_total metric");
+    static ref RESPONSE_CACHE_ENTRIES: IntGaugeVec = register_int_gauge_vec!(
+        "response_cache_entries",
+        "Responses currently held by each response cache.",
+        &["cache"]
+    )
+    .expect("failed to register response_cache_entries metric");
+}
+
+/// Middleware function recording the latency of every request, labeled by its route pattern (e.g.,
+/// `/propensity/batch`) rather than its path. Registered on the app so routes are covered as they
+/// are added.
+pub fn track_request<S, B>(
+    req: ServiceRequest, srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>> + 'static
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    let method = req.method().to_string();
+    let route = req.match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let start = Instant::now();
+    let response = srv.call(req);
+
+    async move {
+        let response = response.await;
+        let status = match &response {
+            Ok(response) => response.status(),
+            Err(error) => error.as_response_error().status_code(),
+        };
+
+        HTTP_REQUEST_DURATION
+            .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+            .observe(start.elapsed().as_secs_f64());
+        response
+    }
+}
+
+/// Records the latency and number of rows of a repository query.
+pub async fn track_query<T, E, F>(query: &'static str, rows: F) -> Result<Vec<T>, E>
+where
+    F: Future<Output = Result<Vec<T>, E>>,
+{
+    let timer = REPOSITORY_QUERY_DURATION.with_label_values(&[query]).start_timer();
+    let rows = rows.await;
+    timer.observe_duration();
+
+    if let Ok(rows) = &rows {
+        REPOSITORY_RESULT_SIZE
+            .with_label_values(&[query])
+            .observe(rows.len() as f64);
+    }
+    rows
+}
+
+pub fn record_cache_lookup(cache: &str, hit: bool) {
+    let outcome = if hit { "hit" } else { "miss" };
+    RESPONSE_CACHE_LOOKUPS.with_label_values(&[cache, outcome]).inc();
+}
+
+pub fn set_cache_size(cache: &str, nr_entries: usize) {
+    RESPONSE_CACHE_ENTRIES.with_label_values(&[cache]).set(nr_entries as i64);
+}
+
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn metrics(pool: web::Data<PgPool>) -> HttpResponse {
+    DB_POOL_SIZE.set(pool.size() as i64);
+    DB_POOL_IDLE.set(pool.num_idle() as i64);
+
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(()) => HttpResponse::Ok().content_type(encoder.format_type()).body(buffer),
+        Err(error) => {
+            tracing::error!(?error, "failed to encode prometheus metrics");
+            super::errors::ProblemDetails::for_status(StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
@@ -1,13 +1,25 @@
 use std::net::TcpListener;
+use std::time::Duration;
 
-use actix_web::dev::Server;
+use actix_web::dev::{Server, Service};
 use actix_web::web::{self, Data};
-use actix_web::{App, HttpServer};
+use actix_web::{App, HttpMessage, HttpServer};
+use address_index::AddressIndexHandle;
+use auth::ApiKeyAuth;
+use cache::{ZipScoresCache, ZIP_SCORES_CACHE};
+use rate_limit::RateLimit;
 use settings::Settings;
 use sqlx::PgPool;
-use tracing_actix_web::TracingLogger;
+use tracing_actix_web::{RequestId, TracingLogger};
 
+pub mod address_index;
+pub mod auth;
+pub mod cache;
 pub mod errors;
+pub mod formats;
+pub mod metrics;
+pub mod openapi;
+pub mod rate_limit;
 pub mod routes;
 pub mod settings;
 
@@ -26,10 +38,25 @@ impl Application {
             .await
             .expect("Failed to connect to Postgres database.");
 
+        let address_index = AddressIndexHandle::default();
+        address_index.clone().spawn_refresh(
+            connection_pool.clone(),
+            Duration::from_secs(settings.address_index.refresh_interval_secs),
+        );
+
+        let zip_scores_cache = ZipScoresCache::new(ZIP_SCORES_CACHE, settings.response_cache.clone());
+        cache::spawn_invalidation_listener(zip_scores_cache.clone(), address_index.clone(), connection_pool.clone());
+
         let address = format!("{}:{}", settings.application.host, settings.application.port);
         let listener = TcpListener::bind(&address)?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(listener, connection_pool /*settings.application.base_url*/)?;
+        let server = run(
+            listener,
+            connection_pool,
+            address_index,
+            zip_scores_cache,
+            settings, /*settings.application.base_url*/
+        )?;
         Ok(Self { port, server })
     }
 
@@ -42,15 +69,42 @@ impl Application {
     }
 }
 
-fn run(listener: TcpListener, db_pool: PgPool /*base_url: String*/) -> Result<Server, std::io::Error> {
+fn run(
+    listener: TcpListener, db_pool: PgPool, address_index: AddressIndexHandle, zip_scores_cache: ZipScoresCache,
+    settings: Settings,
+    /*base_url: String*/
+) -> Result<Server, std::io::Error> {
+    let api_key_auth = ApiKeyAuth::new(db_pool.clone(), settings.auth);
+    let ip_rate_limit = RateLimit::per_ip(&settings.rate_limit);
+    let rate_limit = RateLimit::new(settings.rate_limit);
     let db_pool = Data::new(db_pool);
+    let address_index = Data::new(address_index);
+    let zip_scores_cache = Data::new(zip_scores_cache);
+    let address_index_settings = Data::new(settings.address_index);
+    let export_settings = Data::new(settings.export);
+    let docs_settings = Data::new(settings.docs);
     // let base_url = Data::new(ApplicationBaseUrl(base_url));
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(rate_limit.clone())
+            .wrap(api_key_auth.clone())
+            .wrap(ip_rate_limit.clone())
+            .wrap_fn(|req, srv| {
+                let request_id = req.extensions().get::<RequestId>().map(|id| id.to_string());
+                errors::with_request_id(request_id, srv.call(req))
+            })
+            .wrap_fn(metrics::track_request)
             .wrap(TracingLogger::default())
-            .route("/propensity", web::get().to(routes::propensity_search))
-            .route("/health_check", web::get().to(routes::health_check))
+            .app_data(web::QueryConfig::default().error_handler(errors::query_error_handler))
+            .app_data(web::JsonConfig::default().error_handler(errors::json_error_handler))
+            .configure(openapi::configure_api_routes)
+            .default_service(web::route().to(errors::not_found))
             .app_data(db_pool.clone())
+            .app_data(address_index.clone())
+            .app_data(zip_scores_cache.clone())
+            .app_data(address_index_settings.clone())
+            .app_data(export_settings.clone())
+            .app_data(docs_settings.clone())
         // .app_data(base_url.clone())
     })
     .listen(listener)?
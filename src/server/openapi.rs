@@ -0,0 +1,1337 @@
+use actix_web::{web, HttpResponse};
+use serde_json::{json, Map, Value};
+
+use crate::server::settings::DocsSettings;
+use crate::server::{metrics, routes};
+
+/// An API operation: the route serving it along with its OpenAPI description. Routes are registered
+/// from this table, so every served route is documented; the tests verify that each documented
+/// path and method is routed and that the documented schemas match the serialized types.
+pub struct ApiOperation {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub register: fn(&mut web::ServiceConfig),
+    pub describe: fn() -> Value,
+}
+
+pub fn api_operations() -> Vec<ApiOperation> {
+    vec![
+        ApiOperation {
+            method: "get",
+            path: "/propensity",
+            register: |cfg| {
+                cfg.route("/propensity", web::get().to(routes::propensity_search));
+            },
+            describe: || {
+                json!({
+                    "summary": "Top propensity scores in a zip code",
+                    "description": "Properties in the zip code sorted from the model's best propensity score. Addresses and locations are included only for API keys with the addresses:read scope, which GeoJSON results require.",
+                    "operationId": "propensitySearch",
+                    "security": [{ "apiKey": ["scores:read"] }, { "bearer": ["scores:read"] }],
+                    "parameters": [
+                        query_param("zip_code", true, json!({ "type": "string", "pattern": "^\\d{5}$" }), "5 digit US zip code; also accepted as `zip` or `zipcode`."),
+                        query_param("model", false, json!({ "type": "string", "default": "heloc" }), "Registered model whose scores are searched."),
+                        query_param("tier", false, json!({ "type": "string" }), "Tier band of the model's scores to search; e.g., `High`. Tier names are matched ignoring case."),
+                        query_param("limit", false, json!({ "type": "integer", "minimum": 0, "default": 10 }), "Maximum number of results."),
+                        query_param("format", false, json!({ "type": "string", "enum": ["json", "csv", "geojson"] }), "Response format, overriding the Accept header."),
+                        query_param("include", false, json!({ "type": "string" }), "Comma separated property fields to include in each result: land_use, bedrooms, bathrooms, area_sq_ft, total_area_sq_ft, coordinates or county; also accepted as `fields`."),
+                        query_param("include_unmatched", false, json!({ "type": "boolean", "default": false }), "Include scores whose property is not loaded, flagged as unmatched and without an address."),
+                        query_param("zip_source", false, json!({ "type": "string", "enum": ["propensity", "property"], "default": "propensity" }), "Whether the zip code is matched against the zip code supplied with each score or that of its property."),
+                    ],
+                    "responses": with_problems(
+                        json!({
+                            "200": {
+                                "description": "Scored properties, as JSON, CSV or a GeoJSON FeatureCollection per the `format` parameter or Accept header",
+                                "content": {
+                                    "application/json": { "schema": { "type": "array", "items": schema_ref("PropensitySearchItem") } },
+                                    "text/csv": { "schema": { "type": "string" } },
+                                    "application/geo+json": { "schema": schema_ref("PropensityFeatureCollection") },
+                                },
+                            },
+                        }),
+                        &["400", "401", "403", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/propensity/top",
+            register: |cfg| {
+                cfg.route("/propensity/top", web::get().to(routes::propensity_top));
+            },
+            describe: || {
+                json!({
+                    "summary": "Top propensity scores in a state, county or city",
+                    "description": "The highest scored properties in a state, optionally narrowed to a county and city, each with its rank and percentile among the scored properties in its zip code. Addresses are included only for API keys with the addresses:read scope.",
+                    "operationId": "propensityTop",
+                    "security": [{ "apiKey": ["scores:read"] }, { "bearer": ["scores:read"] }],
+                    "parameters": [
+                        query_param("state", true, json!({ "type": "string" }), "State to rank; e.g., WA."),
+                        query_param("county", false, json!({ "type": "string" }), "County within the state; e.g., KING."),
+                        query_param("city", false, json!({ "type": "string" }), "City within the state; e.g., SEATTLE."),
+                        query_param("n", false, json!({ "type": "integer", "minimum": 0, "maximum": 1000, "default": 10 }), "Number of properties to return; also accepted as `limit`."),
+                        query_param("model", false, json!({ "type": "string", "default": "heloc" }), "Registered model whose scores are ranked."),
+                    ],
+                    "responses": with_problems(
+                        json!({ "200": json_response("Ranked scored properties by descending score", json!({ "type": "array", "items": schema_ref("RankedSearchItem") })) }),
+                        &["400", "401", "403", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/propensity/{apn}/history",
+            register: |cfg| {
+                cfg.route("/propensity/{apn}/history", web::get().to(routes::propensity_history));
+            },
+            describe: || {
+                json!({
+                    "summary": "History of a property's propensity score",
+                    "description": "Every version of the property's score in the order they took effect, along with the version in effect on the `as_of` date. Versions are distinguished by the date they took effect and their source.",
+                    "operationId": "propensityHistory",
+                    "security": [{ "apiKey": ["scores:read"] }, { "bearer": ["scores:read"] }],
+                    "parameters": [
+                        path_param("apn", json!({ "type": "string" }), "Assessor parcel number of the property."),
+                        query_param("as_of", false, json!({ "type": "string", "format": "date" }), "Date (YYYY-MM-DD) on which to report the score in effect; defaults to the current score."),
+                        query_param("model", false, json!({ "type": "string", "default": "heloc" }), "Registered model whose scores are reported."),
+                    ],
+                    "responses": with_problems(
+                        json!({ "200": json_response("Score versions", schema_ref("PropensityHistory")) }),
+                        &["400", "401", "403", "404", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "post",
+            path: "/propensity/batch",
+            register: |cfg| {
+                cfg.route("/propensity/batch", web::post().to(routes::propensity_batch));
+            },
+            describe: || {
+                json!({
+                    "summary": "Batch lookup of propensity scores",
+                    "description": "Looks up the score of each property identified by APN or structured address. Results are returned in request order with a per-item status.",
+                    "operationId": "propensityBatch",
+                    "security": [{ "apiKey": ["scores:read"] }, { "bearer": ["scores:read"] }],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": schema_ref("PropensityBatchRequest") } },
+                    },
+                    "responses": with_problems(
+                        json!({ "200": json_response("Batch results", json!({ "type": "array", "items": schema_ref("PropensityBatchItem") })) }),
+                        &["400", "401", "403", "413", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/propensity/address",
+            register: |cfg| {
+                cfg.route("/propensity/address", web::get().to(routes::propensity_address_lookup));
+            },
+            describe: || {
+                json!({
+                    "summary": "Look up a free-text address",
+                    "description": "Parses a single-line address and returns the matching properties with their scores, exact matches first.",
+                    "operationId": "propensityAddressLookup",
+                    "security": [{ "apiKey": ["scores:read"] }, { "bearer": ["scores:read"] }],
+                    "parameters": [
+                        query_param("q", true, json!({ "type": "string" }), "Single-line address; also accepted as `address`."),
+                        query_param("limit", false, json!({ "type": "integer", "minimum": 0, "default": 5 }), "Maximum number of candidates."),
+                        query_param("min_confidence", false, json!({ "type": "number", "minimum": 0.0, "maximum": 1.0, "default": 0.6 }), "Minimum confidence of fuzzy matches."),
+                        query_param("model", false, json!({ "type": "string", "default": "heloc" }), "Registered model whose scores are reported."),
+                    ],
+                    "responses": with_problems(
+                        json!({ "200": json_response("Address candidates", schema_ref("AddressLookupResponse")) }),
+                        &["400", "401", "403", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/export/propensity",
+            register: |cfg| {
+                cfg.route("/export/propensity", web::get().to(routes::export_propensity));
+            },
+            describe: || {
+                json!({
+                    "summary": "Export the propensity scores in a region",
+                    "description": "Streams every scored property in a state, or a county within it, in APN order. Regions with more rows than the server allows, or than remain in the client's daily quota, are refused; addresses and locations are included only for API keys with the addresses:read scope. An export exceeding the server's time limit is aborted, leaving an incomplete body.",
+                    "operationId": "exportPropensity",
+                    "security": [{ "apiKey": ["scores:read"] }, { "bearer": ["scores:read"] }],
+                    "parameters": [
+                        query_param("state", true, json!({ "type": "string" }), "State to export; e.g., WA."),
+                        query_param("county", false, json!({ "type": "string" }), "County within the state to export; e.g., KING."),
+                        query_param("model", false, json!({ "type": "string", "default": "heloc" }), "Registered model whose scores are exported."),
+                        query_param("format", false, json!({ "type": "string", "enum": ["ndjson", "csv"] }), "Export format, overriding the Accept header."),
+                        query_param("include", false, json!({ "type": "string" }), "Comma separated property fields to include in each result: land_use, bedrooms, bathrooms, area_sq_ft, total_area_sq_ft, coordinates or county; also accepted as `fields`."),
+                    ],
+                    "responses": with_problems(
+                        json!({
+                            "200": {
+                                "description": "Scored properties, one per line",
+                                "content": {
+                                    "application/x-ndjson": { "schema": schema_ref("PropensitySearchItem") },
+                                    "text/csv": { "schema": { "type": "string" } },
+                                },
+                            },
+                        }),
+                        &["400", "401", "403", "413", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/addresses/suggest",
+            register: |cfg| {
+                cfg.route("/addresses/suggest", web::get().to(routes::address_suggest));
+            },
+            describe: || {
+                json!({
+                    "summary": "Address autocomplete",
+                    "operationId": "addressSuggest",
+                    "security": [{ "apiKey": ["addresses:read"] }, { "bearer": ["addresses:read"] }],
+                    "parameters": [
+                        query_param("prefix", true, json!({ "type": "string" }), "Beginning of the address typed so far."),
+                        query_param("zip_code", false, json!({ "type": "string", "pattern": "^\\d{5}$" }), "Restrict suggestions to a zip code; also accepted as `zip` or `zipcode`."),
+                        query_param("limit", false, json!({ "type": "integer", "minimum": 0, "default": 10 }), "Maximum number of suggestions."),
+                    ],
+                    "responses": with_problems(
+                        json!({ "200": json_response("Address suggestions", json!({ "type": "array", "items": schema_ref("AddressSuggestion") })) }),
+                        &["400", "401", "403", "429", "500", "503"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "post",
+            path: "/admin/cache/flush",
+            register: |cfg| {
+                cfg.route("/admin/cache/flush", web::post().to(routes::flush_response_cache));
+            },
+            describe: || {
+                json!({
+                    "summary": "Flush the response cache",
+                    "description": "Discards cached propensity search responses. The cache is also flushed automatically whenever the loader completes a load.",
+                    "operationId": "flushResponseCache",
+                    "security": [{ "apiKey": ["admin"] }, { "bearer": ["admin"] }],
+                    "responses": with_problems(
+                        json!({ "200": json_response("Number of responses flushed", schema_ref("CacheFlushReport")) }),
+                        &["401", "403", "429", "500"],
+                    ),
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/health_check",
+            register: |cfg| {
+                cfg.route("/health_check", web::get().to(routes::health_check));
+            },
+            describe: || {
+                json!({
+                    "summary": "Basic health check",
+                    "description": "Retained alias of the liveness check.",
+                    "operationId": "healthCheck",
+                    "security": [],
+                    "responses": { "200": { "description": "Server is accepting requests" } },
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/health/live",
+            register: |cfg| {
+                cfg.route("/health/live", web::get().to(routes::health_live));
+            },
+            describe: || {
+                json!({
+                    "summary": "Liveness check",
+                    "operationId": "healthLive",
+                    "security": [],
+                    "responses": { "200": json_response("Server is live", schema_ref("LivenessReport")) },
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/health/ready",
+            register: |cfg| {
+                cfg.route("/health/ready", web::get().to(routes::health_ready));
+            },
+            describe: || {
+                json!({
+                    "summary": "Readiness check",
+                    "operationId": "healthReady",
+                    "security": [],
+                    "responses": {
+                        "200": json_response("Server is ready", schema_ref("ReadinessReport")),
+                        "503": json_response("Server is not ready", schema_ref("ReadinessReport")),
+                    },
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/metrics",
+            register: |cfg| {
+                cfg.route("/metrics", web::get().to(metrics::metrics));
+            },
+            describe: || {
+                json!({
+                    "summary": "Prometheus metrics",
+                    "operationId": "metrics",
+                    "security": [],
+                    "responses": {
+                        "200": {
+                            "description": "Metrics in the Prometheus text format",
+                            "content": { "text/plain": { "schema": { "type": "string" } } },
+                        },
+                    },
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/openapi.json",
+            register: |cfg| {
+                cfg.route("/openapi.json", web::get().to(openapi_json));
+            },
+            describe: || {
+                json!({
+                    "summary": "This OpenAPI document",
+                    "operationId": "openapi",
+                    "security": [],
+                    "responses": { "200": json_response("OpenAPI 3 document", json!({ "type": "object" })) },
+                })
+            },
+        },
+        ApiOperation {
+            method: "get",
+            path: "/docs",
+            register: |cfg| {
+                cfg.route("/docs", web::get().to(docs));
+            },
+            describe: || {
+                json!({
+                    "summary": "API documentation page",
+                    "operationId": "docs",
+                    "security": [],
+                    "responses": {
+                        "200": {
+                            "description": "Interactive API documentation",
+                            "content": { "text/html": { "schema": { "type": "string" } } },
+                        },
+                    },
+                })
+            },
+        },
+    ]
+}
+
+/// Registers the route of every API operation.
+pub fn configure_api_routes(cfg: &mut web::ServiceConfig) {
+    for operation in api_operations() {
+        (operation.register)(cfg);
+    }
+}
+
+pub fn openapi_document() -> Value {
+    let mut paths = Map::new();
+    for operation in api_operations() {
+        let path_item = paths.entry(operation.path).or_insert_with(|| json!({}));
+        path_item[operation.method] = (operation.describe)();
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Address Propensity API",
+            "description": "Propensity scores of properties by zip code, APN and address.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "security": [{ "apiKey": [] }, { "bearer": [] }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+            "responses": problem_responses(),
+            "schemas": schemas(),
+        },
+    })
+}
+
+#[tracing::instrument(level = "debug")]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(openapi_document())
+}
+
+/// Documentation page rendering the OpenAPI description with swagger-ui, whose assets are loaded by
+/// the browser from the configured `docs.swagger_ui_url`.
+#[tracing::instrument(level = "debug", skip(settings))]
+pub async fn docs(settings: web::Data<DocsSettings>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(docs_page(&settings.swagger_ui_url))
+}
+
+fn docs_page(swagger_ui_url: &str) -> String {
+    let swagger_ui_url = swagger_ui_url.trim_end_matches('/');
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>Address Propensity API</title>
+  <link rel="stylesheet" href="{swagger_ui_url}/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="{swagger_ui_url}/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = function () {{
+      window.ui = SwaggerUIBundle({{ url: "/openapi.json", dom_id: "#swagger-ui" }});
+    }};
+  </script>
+</body>
+</html>
+"##,
+        swagger_ui_url = swagger_ui_url
+    )
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn query_param(name: &str, required: bool, schema: Value, description: &str) -> Value {
+    json!({ "name": name, "in": "query", "required": required, "schema": schema, "description": description })
+}
+
+fn path_param(name: &str, schema: Value, description: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": schema, "description": description })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+}
+
+fn with_problems(mut responses: Value, statuses: &[&str]) -> Value {
+    for status in statuses {
+        responses[*status] = json!({ "$ref": format!("#/components/responses/Problem{}", status) });
+    }
+    responses
+}
+
+fn problem_responses() -> Value {
+    let statuses = [
+        ("400", "Invalid request parameters or body"),
+        ("401", "Missing, unrecognized or revoked API key"),
+        ("403", "API key lacks a required scope"),
+        ("404", "Resource not found"),
+        ("413", "Batch or export too large"),
+        ("429", "Rate limit or daily quota exceeded; see the Retry-After header"),
+        ("500", "Unexpected server error"),
+        ("503", "Service temporarily unavailable"),
+    ];
+
+    let mut responses = Map::new();
+    for (status, description) in statuses.iter() {
+        responses.insert(
+            format!("Problem{}", status),
+            json!({
+                "description": description,
+                "content": { "application/problem+json": { "schema": schema_ref("ProblemDetails") } },
+            }),
+        );
+    }
+    Value::Object(responses)
+}
+
+fn schemas() -> Value {
+    json!({
+        "AssessorParcelNumber": {
+            "type": "string",
+            "description": "Assessor parcel number, zero padded to 14 digits.",
+            "pattern": "^[\\d-]+$",
+        },
+        "PropensityScore": {
+            "type": "object",
+            "required": ["score"],
+            "properties": {
+                "score": { "type": "integer", "minimum": 0 },
+            },
+        },
+        "Address": {
+            "type": "object",
+            "required": ["address_line", "city", "state_or_region", "zip_or_postal_code", "locale"],
+            "properties": {
+                "address_line": schema_ref("AddressLine"),
+                "secondary_address_line": schema_ref("SecondaryAddressLine"),
+                "city": { "type": "string" },
+                "state_or_region": { "type": "string" },
+                "zip_or_postal_code": schema_ref("ZipOrPostalCode"),
+                "locale": schema_ref("CountryCode"),
+            },
+        },
+        "AddressLine": {
+            "type": "object",
+            "required": ["street_number", "street_name", "street_suffix", "street_direction"],
+            "properties": {
+                "street_number": { "type": "string" },
+                "street_name": { "type": "string" },
+                "street_suffix": { "type": "string" },
+                "street_direction": schema_ref("StreetDirection"),
+            },
+        },
+        "StreetDirection": {
+            "description": "Directional preceding and/or following the street name.",
+            "oneOf": [
+                { "type": "string", "enum": ["None"] },
+                {
+                    "type": "object",
+                    "required": ["Prefix"],
+                    "properties": { "Prefix": { "type": "string" } },
+                },
+                {
+                    "type": "object",
+                    "required": ["Suffix"],
+                    "properties": { "Suffix": { "type": "string" } },
+                },
+                {
+                    "type": "object",
+                    "required": ["Both"],
+                    "properties": { "Both": { "type": "array", "items": { "type": "string" }, "minItems": 2, "maxItems": 2 } },
+                },
+            ],
+        },
+        "SecondaryAddressLine": {
+            "type": "object",
+            "required": ["designator", "number"],
+            "properties": {
+                "designator": { "type": "string" },
+                "number": { "type": "string" },
+            },
+        },
+        "ZipOrPostalCode": {
+            "type": "object",
+            "required": ["code"],
+            "properties": {
+                "code": { "type": "string", "pattern": "^\\d{5}$" },
+            },
+        },
+        "CountryCode": {
+            "type": "object",
+            "required": ["iso_3166_alpha_3", "official_name"],
+            "properties": {
+                "iso_3166_alpha_3": { "type": "string", "minLength": 3, "maxLength": 3 },
+                "official_name": { "type": "string" },
+            },
+        },
+        "PropensitySearchItem": {
+            "allOf": [
+                schema_ref("PropensityScore"),
+                {
+                    "type": "object",
+                    "required": ["apn"],
+                    "properties": {
+                        "apn": schema_ref("AssessorParcelNumber"),
+                        "tier": { "type": "string", "description": "Tier band of the model containing the score; omitted if the model defines no band containing it." },
+                        "matched": { "type": "boolean", "description": "Whether the score's property is loaded; reported only when unmatched scores are requested." },
+                        "zip_code": { "type": "string", "description": "Zip code supplied with the score; reported only when unmatched scores are requested." },
+                        "address": schema_ref("Address"),
+                    },
+                },
+                schema_ref("PropertyAttributes"),
+            ],
+        },
+        "RankedSearchItem": {
+            "allOf": [
+                schema_ref("PropensitySearchItem"),
+                {
+                    "type": "object",
+                    "required": ["zip_rank", "zip_percentile"],
+                    "properties": {
+                        "zip_rank": { "type": "integer", "minimum": 1, "description": "Rank by descending score within the property's zip code; equal scores share a rank." },
+                        "zip_percentile": { "type": "number", "minimum": 0, "maximum": 100, "description": "Percentage of the other scored properties in the zip code with lower scores." },
+                    },
+                },
+            ],
+        },
+        "PropensityHistory": {
+            "type": "object",
+            "required": ["apn", "model", "versions"],
+            "properties": {
+                "apn": schema_ref("AssessorParcelNumber"),
+                "model": { "type": "string" },
+                "effective": schema_ref("PropensityScoreVersion"),
+                "versions": { "type": "array", "items": schema_ref("PropensityScoreVersion") },
+            },
+        },
+        "PropensityScoreVersion": {
+            "allOf": [
+                schema_ref("PropensityScore"),
+                {
+                    "type": "object",
+                    "required": ["effective_on", "source", "loaded_on"],
+                    "properties": {
+                        "zip_code": { "type": "string" },
+                        "effective_on": { "type": "string", "format": "date" },
+                        "source": { "type": "string", "description": "Source or model that produced the score." },
+                        "loaded_on": { "type": "string", "format": "date-time" },
+                    },
+                },
+            ],
+        },
+        "PropertyAttributes": {
+            "type": "object",
+            "description": "Property fields requested by the `include` parameter; omitted when not requested or unknown.",
+            "properties": {
+                "land_use": {
+                    "type": "string",
+                    "description": "Canonical code of the land use taxonomy; e.g., SingleFamilyResidential, Townhouse, RetailStore or VacantLand. Unknown if the property's land use matched no land use of the taxonomy.",
+                },
+                "bedrooms": { "type": "integer", "minimum": 0 },
+                "bathrooms": { "type": "string", "description": "Decimal number of bathrooms; e.g., 2.5." },
+                "area_sq_ft": { "type": "integer", "minimum": 0 },
+                "total_area_sq_ft": { "type": "integer", "minimum": 0 },
+                "coordinates": schema_ref("GeoCoordinate"),
+                "county": { "type": "string" },
+            },
+        },
+        "GeoCoordinate": {
+            "type": "object",
+            "required": ["latitude", "longitude"],
+            "properties": {
+                "latitude": { "type": "string", "description": "Decimal degrees." },
+                "longitude": { "type": "string", "description": "Decimal degrees." },
+            },
+        },
+        "PropensityFeatureCollection": {
+            "type": "object",
+            "required": ["type", "features"],
+            "properties": {
+                "type": { "type": "string", "enum": ["FeatureCollection"] },
+                "features": { "type": "array", "items": schema_ref("PropensityFeature") },
+            },
+        },
+        "PropensityFeature": {
+            "type": "object",
+            "description": "A scored property located at a Point, or without geometry if its location is unknown.",
+            "required": ["type", "geometry", "properties"],
+            "properties": {
+                "type": { "type": "string", "enum": ["Feature"] },
+                "geometry": {
+                    "type": "object",
+                    "nullable": true,
+                    "required": ["type", "coordinates"],
+                    "properties": {
+                        "type": { "type": "string", "enum": ["Point"] },
+                        "coordinates": { "type": "array", "items": { "type": "number" }, "minItems": 2, "maxItems": 2 },
+                    },
+                },
+                "properties": schema_ref("PropensitySearchItem"),
+            },
+        },
+        "PropensityBatchRequest": {
+            "type": "object",
+            "required": ["items"],
+            "properties": {
+                "model": { "type": "string", "default": "heloc", "description": "Registered model whose scores are looked up." },
+                "items": { "type": "array", "maxItems": 1000, "items": schema_ref("PropensityBatchQuery") },
+            },
+        },
+        "PropensityBatchQuery": {
+            "oneOf": [
+                {
+                    "type": "object",
+                    "required": ["apn"],
+                    "properties": { "apn": { "type": "string" } },
+                },
+                {
+                    "type": "object",
+                    "required": ["address"],
+                    "properties": { "address": schema_ref("BatchAddressQuery") },
+                },
+            ],
+        },
+        "BatchAddressQuery": {
+            "type": "object",
+            "required": ["street_number", "street_name", "zip_code"],
+            "properties": {
+                "street_number": { "type": "string" },
+                "street_name": { "type": "string" },
+                "street_suffix": { "type": "string" },
+                "secondary_number": { "type": "string" },
+                "zip_code": { "type": "string" },
+            },
+        },
+        "PropensityBatchItem": {
+            "type": "object",
+            "required": ["query", "status"],
+            "properties": {
+                "query": schema_ref("PropensityBatchQuery"),
+                "status": {
+                    "type": "string",
+                    "enum": ["found", "invalid_apn", "invalid_address", "ambiguous_address", "not_found"],
+                },
+                "apn": schema_ref("AssessorParcelNumber"),
+                "score": { "type": "integer", "minimum": 0 },
+                "address": schema_ref("Address"),
+                "error": { "type": "string" },
+            },
+        },
+        "AddressLookupResponse": {
+            "type": "object",
+            "required": ["normalized", "candidates"],
+            "properties": {
+                "normalized": { "type": "string" },
+                "candidates": { "type": "array", "items": schema_ref("AddressMatchItem") },
+            },
+        },
+        "AddressMatchItem": {
+            "type": "object",
+            "required": ["apn", "match_type", "confidence"],
+            "properties": {
+                "apn": schema_ref("AssessorParcelNumber"),
+                "match_type": { "type": "string", "enum": ["exact", "fuzzy"] },
+                "confidence": { "type": "number", "minimum": 0.0, "maximum": 1.0 },
+                "score": { "type": "integer", "minimum": 0 },
+                "address": schema_ref("Address"),
+            },
+        },
+        "AddressSuggestion": {
+            "type": "object",
+            "required": ["apn", "display", "address"],
+            "properties": {
+                "apn": schema_ref("AssessorParcelNumber"),
+                "display": { "type": "string" },
+                "address": schema_ref("Address"),
+            },
+        },
+        "CacheFlushReport": {
+            "type": "object",
+            "required": ["nr_flushed"],
+            "properties": {
+                "nr_flushed": { "type": "integer", "minimum": 0 },
+            },
+        },
+        "ProblemDetails": {
+            "type": "object",
+            "description": "Problem details for HTTP APIs (RFC 7807).",
+            "required": ["type", "title", "status"],
+            "properties": {
+                "type": { "type": "string" },
+                "title": { "type": "string" },
+                "status": { "type": "integer" },
+                "detail": { "type": "string" },
+                "invalid_params": { "type": "array", "items": schema_ref("InvalidParam") },
+                "request_id": { "type": "string" },
+            },
+        },
+        "InvalidParam": {
+            "type": "object",
+            "required": ["name", "reason"],
+            "properties": {
+                "name": { "type": "string" },
+                "reason": { "type": "string" },
+            },
+        },
+        "LivenessReport": {
+            "type": "object",
+            "required": ["status"],
+            "properties": {
+                "status": { "type": "string", "enum": ["live"] },
+            },
+        },
+        "ReadinessReport": {
+            "type": "object",
+            "required": ["status", "database", "migrations", "pool", "freshness"],
+            "properties": {
+                "status": { "type": "string", "enum": ["ready", "not_ready"] },
+                "database": {
+                    "type": "object",
+                    "required": ["reachable", "latency_millis"],
+                    "properties": {
+                        "reachable": { "type": "boolean" },
+                        "latency_millis": { "type": "integer" },
+                        "error": { "type": "string", "enum": ["unreachable", "timed out"] },
+                    },
+                },
+                "migrations": {
+                    "type": "object",
+                    "required": ["up_to_date", "expected_version", "applied_version"],
+                    "properties": {
+                        "up_to_date": { "type": "boolean" },
+                        "expected_version": { "type": "integer", "nullable": true },
+                        "applied_version": { "type": "integer", "nullable": true },
+                    },
+                },
+                "pool": {
+                    "type": "object",
+                    "required": ["size", "idle", "max_connections", "saturation"],
+                    "properties": {
+                        "size": { "type": "integer" },
+                        "idle": { "type": "integer" },
+                        "max_connections": { "type": "integer" },
+                        "saturation": { "type": "number" },
+                    },
+                },
+                "freshness": {
+                    "type": "object",
+                    "properties": {
+                        "properties": schema_ref("TableFreshness"),
+                        "propensities": schema_ref("TableFreshness"),
+                    },
+                },
+            },
+        },
+        "TableFreshness": {
+            "type": "object",
+            "required": ["nr_rows", "last_updated_on"],
+            "properties": {
+                "nr_rows": { "type": "integer" },
+                "last_updated_on": { "type": "string", "format": "date-time", "nullable": true },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::property::PropertyAttributes;
+    use crate::core::domain::{
+        Address, AddressLine, AssessorParcelNumber, City, GeoCoordinate, LandUseType, PropensityScore,
+        SecondaryAddressLine, StateOrRegion, StreetDirection, ZipOrPostalCode, ZipRanking,
+    };
+    use crate::core::TableFreshness;
+    use crate::server::address_index::AddressSuggestion;
+    use crate::server::errors::{InvalidParam, ProblemDetails};
+    use crate::server::routes::{
+        AddressLookupParameters, AddressLookupResponse, AddressMatchItem, AddressSuggestParameters, BatchAddressQuery,
+        BatchItemStatus, CacheFlushReport, DatabaseCheck, ExportParameters, FreshnessCheck, HealthStatus,
+        LivenessReport, MatchType, MigrationsCheck, PoolCheck, PropensityBatchItem, PropensityBatchQuery,
+        PropensityBatchRequest, PropensityHistoryParameters, PropensityHistoryResponse, PropensityScoresParameters,
+        PropensitySearchItem, RankedSearchItem, ReadinessReport, ScoreVersionItem, TopScoresParameters,
+    };
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::{test, App};
+    use bigdecimal::BigDecimal;
+    use claim::assert_ok;
+    use pretty_assertions::assert_eq;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    /// Merges the properties of object schemas combined by `allOf`, including nested combinations.
+    fn merge_all_of(parts: &[Value], schemas: &Value, merged: &mut Value) {
+        for part in parts {
+            let part = match part.get("$ref").and_then(Value::as_str) {
+                Some(reference) => &schemas[reference.trim_start_matches("#/components/schemas/")],
+                None => part,
+            };
+            if let Some(nested) = part.get("allOf").and_then(Value::as_array) {
+                merge_all_of(nested, schemas, merged);
+            }
+            for (name, property) in part["properties"].as_object().into_iter().flatten() {
+                merged["properties"][name] = property.clone();
+            }
+            for required in part["required"].as_array().into_iter().flatten() {
+                merged["required"].as_array_mut().unwrap().push(required.clone());
+            }
+        }
+    }
+
+    /// Checks a JSON value against the subset of JSON Schema used by the document. Objects must
+    /// carry every required property and no undocumented ones.
+    fn check(value: &Value, schema: &Value, schemas: &Value, at: &str) -> Result<(), String> {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            let schema = schemas
+                .get(name)
+                .ok_or_else(|| format!("{}: unknown schema {}", at, reference))?;
+            return check(value, schema, schemas, at);
+        }
+
+        if value.is_null() && schema.get("nullable") == Some(&Value::Bool(true)) {
+            return Ok(());
+        }
+
+        if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+            let nr_matches = alternatives
+                .iter()
+                .filter(|alternative| check(value, alternative, schemas, at).is_ok())
+                .count();
+            return if nr_matches == 1 {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{}: {} matches {} of oneOf alternatives",
+                    at, value, nr_matches
+                ))
+            };
+        }
+
+        if let Some(parts) = schema.get("allOf").and_then(Value::as_array) {
+            let mut merged = json!({ "type": "object", "required": [], "properties": {} });
+            merge_all_of(parts, schemas, &mut merged);
+            return check(value, &merged, schemas, at);
+        }
+
+        match schema.get("type").and_then(Value::as_str) {
+            Some("object") => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| format!("{}: {} is not an object", at, value))?;
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for required in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                    let required = required.as_str().unwrap_or_default();
+                    if !object.contains_key(required) {
+                        return Err(format!("{}: missing required property {}", at, required));
+                    }
+                }
+                for (name, property_value) in object {
+                    let property_schema = properties
+                        .and_then(|p| p.get(name))
+                        .ok_or_else(|| format!("{}: undocumented property {}", at, name))?;
+                    check(property_value, property_schema, schemas, &format!("{}.{}", at, name))?;
+                }
+                Ok(())
+            }
+            Some("array") => {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| format!("{}: {} is not an array", at, value))?;
+                for (idx, item) in items.iter().enumerate() {
+                    check(item, &schema["items"], schemas, &format!("{}[{}]", at, idx))?;
+                }
+                Ok(())
+            }
+            Some("string") => {
+                let string = value
+                    .as_str()
+                    .ok_or_else(|| format!("{}: {} is not a string", at, value))?;
+                match schema.get("enum").and_then(Value::as_array) {
+                    Some(values) if !values.iter().any(|v| v == string) => {
+                        Err(format!("{}: {} is not one of {:?}", at, string, values))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Some("integer") if value.is_i64() || value.is_u64() => Ok(()),
+            Some("number") if value.is_number() => Ok(()),
+            Some("boolean") if value.is_boolean() => Ok(()),
+            Some(expected) => Err(format!("{}: {} is not {}", at, value, expected)),
+            None => Ok(()),
+        }
+    }
+
+    fn assert_conforms(value: &impl Serialize, schema_name: &str) -> anyhow::Result<()> {
+        let document = openapi_document();
+        let schemas = &document["components"]["schemas"];
+        let value = serde_json::to_value(value)?;
+        if let Err(error) = check(&value, &schema_ref(schema_name), schemas, schema_name) {
+            panic!(
+                "serialized {} diverges from its documented schema: {}",
+                schema_name, error
+            );
+        }
+        Ok(())
+    }
+
+    fn address(direction: StreetDirection, unit: Option<&str>) -> anyhow::Result<Address> {
+        Ok(Address::new_in_usa(
+            AddressLine::new("583", "BATTERY", "ST", direction),
+            unit.map(|number| SecondaryAddressLine::new("UNIT", number)),
+            City::new("SEATTLE"),
+            StateOrRegion::new("WA"),
+            ZipOrPostalCode::new("98121")?,
+        ))
+    }
+
+    fn search_item(address: Option<Address>) -> anyhow::Result<PropensitySearchItem> {
+        Ok(PropensitySearchItem {
+            apn: assert_ok!(AssessorParcelNumber::new("6633050420")),
+            propensity_score: assert_ok!(PropensityScore::new(259)),
+            tier: None,
+            matched: None,
+            zip_code: None,
+            address,
+            attributes: PropertyAttributes::default(),
+        })
+    }
+
+    #[test]
+    fn test_openapi_schemas_match_serialized_types() -> anyhow::Result<()> {
+        assert_conforms(
+            &search_item(Some(address(StreetDirection::None, Some("509"))?))?,
+            "PropensitySearchItem",
+        )?;
+        assert_conforms(&search_item(None)?, "PropensitySearchItem")?;
+        assert_conforms(
+            &RankedSearchItem {
+                item: search_item(Some(address(StreetDirection::None, None)?))?,
+                ranking: ZipRanking { zip_rank: 2, zip_percentile: 87.5 },
+            },
+            "RankedSearchItem",
+        )?;
+        assert_conforms(
+            &PropensitySearchItem {
+                tier: Some("Very High".to_string()),
+                ..search_item(None)?
+            },
+            "PropensitySearchItem",
+        )?;
+        assert_conforms(
+            &PropensitySearchItem {
+                matched: Some(false),
+                zip_code: Some("98121".to_string()),
+                ..search_item(None)?
+            },
+            "PropensitySearchItem",
+        )?;
+        assert_conforms(
+            &PropensitySearchItem {
+                attributes: PropertyAttributes {
+                    land_use: Some(LandUseType::SingleFamilyResidential),
+                    bedrooms: Some(3),
+                    bathrooms: Some(BigDecimal::from_str("2.5")?),
+                    area_sq_ft: Some(1_850),
+                    total_area_sq_ft: Some(6_000),
+                    coordinates: Some(GeoCoordinate::new(47.into(), (-122).into())),
+                    county: Some("KING".to_string()),
+                },
+                ..search_item(None)?
+            },
+            "PropensitySearchItem",
+        )?;
+        assert_conforms(
+            &crate::server::formats::to_feature_collection(&[
+                (
+                    search_item(Some(address(StreetDirection::None, None)?))?,
+                    Some(GeoCoordinate::new(47.into(), (-122).into())),
+                ),
+                (search_item(None)?, None),
+            ]),
+            "PropensityFeatureCollection",
+        )?;
+        let version = |score: u16, effective_on: &str| -> anyhow::Result<ScoreVersionItem> {
+            Ok(ScoreVersionItem {
+                propensity_score: assert_ok!(PropensityScore::new(score)),
+                zip_code: Some("98121".to_string()),
+                effective_on: chrono::NaiveDate::from_str(effective_on)?,
+                source: "scores_2021_q3".to_string(),
+                loaded_on: chrono::Utc::now(),
+            })
+        };
+        assert_conforms(
+            &PropensityHistoryResponse {
+                apn: assert_ok!(AssessorParcelNumber::new("6633050420")),
+                model: "heloc".to_string(),
+                effective: Some(version(259, "2021-07-01")?),
+                versions: vec![version(212, "2021-04-01")?, version(259, "2021-07-01")?],
+            },
+            "PropensityHistory",
+        )?;
+        assert_conforms(
+            &PropensityHistoryResponse {
+                apn: assert_ok!(AssessorParcelNumber::new("6633050420")),
+                model: "heloc".to_string(),
+                effective: None,
+                versions: vec![ScoreVersionItem { zip_code: None, ..version(212, "2021-04-01")? }],
+            },
+            "PropensityHistory",
+        )?;
+        let directions = [
+            StreetDirection::for_prefix("N"),
+            StreetDirection::for_suffix("E"),
+            StreetDirection::new(Some("N"), Some("E")),
+        ];
+        for direction in directions.iter() {
+            assert_conforms(&address(direction.clone(), None)?, "Address")?;
+        }
+
+        let address_query = PropensityBatchQuery::Address {
+            address: BatchAddressQuery {
+                street_number: "583".to_string(),
+                street_name: "BATTERY".to_string(),
+                street_suffix: Some("ST".to_string()),
+                secondary_number: None,
+                zip_code: "98121".to_string(),
+            },
+        };
+        let batch = vec![
+            PropensityBatchItem {
+                query: PropensityBatchQuery::Apn { apn: "6633050420".to_string() },
+                status: BatchItemStatus::Found,
+                result: Some(search_item(Some(address(StreetDirection::None, None)?))?),
+                error: None,
+            },
+            PropensityBatchItem {
+                query: address_query.clone(),
+                status: BatchItemStatus::AmbiguousAddress,
+                result: None,
+                error: Some("address matches 2 properties".to_string()),
+            },
+        ];
+        for item in batch.iter() {
+            assert_conforms(item, "PropensityBatchItem")?;
+        }
+
+        let request =
+            json!({ "model": "heloc", "items": [{ "apn": "6633050420" }, serde_json::to_value(&address_query)?] });
+        assert_conforms(&request, "PropensityBatchRequest")?;
+        assert_ok!(serde_json::from_value::<PropensityBatchRequest>(request));
+
+        assert_conforms(
+            &AddressLookupResponse {
+                normalized: "583 BATTERY ST, SEATTLE, WA 98121".to_string(),
+                candidates: vec![
+                    AddressMatchItem {
+                        apn: assert_ok!(AssessorParcelNumber::new("6633050420")),
+                        match_type: MatchType::Exact,
+                        confidence: 1.0,
+                        propensity_score: Some(assert_ok!(PropensityScore::new(259))),
+                        address: Some(address(StreetDirection::None, None)?),
+                    },
+                    AddressMatchItem {
+                        apn: assert_ok!(AssessorParcelNumber::new("7656901080")),
+                        match_type: MatchType::Fuzzy,
+                        confidence: 0.75,
+                        propensity_score: None,
+                        address: None,
+                    },
+                ],
+            },
+            "AddressLookupResponse",
+        )?;
+
+        let suggested = address(StreetDirection::None, None)?;
+        assert_conforms(
+            &AddressSuggestion {
+                apn: assert_ok!(AssessorParcelNumber::new("6633050420")),
+                display: suggested.to_string(),
+                address: suggested,
+            },
+            "AddressSuggestion",
+        )?;
+
+        assert_conforms(
+            &ProblemDetails::new(
+                StatusCode::BAD_REQUEST,
+                "invalid-parameter",
+                "Invalid request parameter",
+            )
+            .with_detail("detail")
+            .with_invalid_params(vec![InvalidParam::new("zip_code", "required parameter is missing")]),
+            "ProblemDetails",
+        )?;
+
+        assert_conforms(&CacheFlushReport { nr_flushed: 3 }, "CacheFlushReport")?;
+        assert_conforms(&LivenessReport { status: HealthStatus::Live }, "LivenessReport")?;
+        assert_conforms(
+            &ReadinessReport {
+                status: HealthStatus::NotReady,
+                database: DatabaseCheck {
+                    reachable: false,
+                    latency_millis: 2000,
+                    error: Some("timed out".to_string()),
+                },
+                migrations: MigrationsCheck {
+                    up_to_date: false,
+                    expected_version: Some(20211004184512),
+                    applied_version: None,
+                },
+                pool: PoolCheck {
+                    size: 2,
+                    idle: 1,
+                    max_connections: 10,
+                    saturation: 0.1,
+                },
+                freshness: FreshnessCheck {
+                    properties: Some(TableFreshness {
+                        nr_rows: 2,
+                        last_updated_on: Some(chrono::Utc::now()),
+                    }),
+                    propensities: Some(TableFreshness { nr_rows: 0, last_updated_on: None }),
+                },
+            },
+            "ReadinessReport",
+        )?;
+        Ok(())
+    }
+
+    /// Deserializer that only records the field names a struct declares to serde, which include their
+    /// aliases in recent serde versions.
+    struct FieldNames(Vec<&'static str>);
+
+    impl<'de> serde::Deserializer<'de> for &mut FieldNames {
+        type Error = serde::de::value::Error;
+
+        fn deserialize_any<V: serde::de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(serde::de::Error::custom("only struct field names are recorded"))
+        }
+
+        fn deserialize_struct<V: serde::de::Visitor<'de>>(
+            self, _name: &'static str, fields: &'static [&'static str], _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            self.0.extend_from_slice(fields);
+            Err(serde::de::Error::custom("struct field names recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit unit_struct
+            newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    fn parameter_fields<T: DeserializeOwned>() -> HashSet<&'static str> {
+        let mut names = FieldNames(vec![]);
+        let _ = T::deserialize(&mut names);
+        names.0.into_iter().collect()
+    }
+
+    #[test]
+    fn test_openapi_query_parameters_match_parameter_types() {
+        let parameter_types = vec![
+            ("/propensity", parameter_fields::<PropensityScoresParameters>()),
+            ("/propensity/top", parameter_fields::<TopScoresParameters>()),
+            (
+                "/propensity/{apn}/history",
+                parameter_fields::<PropensityHistoryParameters>(),
+            ),
+            ("/propensity/address", parameter_fields::<AddressLookupParameters>()),
+            ("/export/propensity", parameter_fields::<ExportParameters>()),
+            ("/addresses/suggest", parameter_fields::<AddressSuggestParameters>()),
+        ];
+
+        let document = openapi_document();
+        let query_parameters = |path: &str| -> Vec<Value> {
+            document["paths"][path]["get"]["parameters"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|parameter| parameter["in"] == "query")
+                .cloned()
+                .collect()
+        };
+
+        let with_query_parameters: HashSet<&str> = api_operations()
+            .into_iter()
+            .map(|operation| operation.path)
+            .filter(|path| !query_parameters(*path).is_empty())
+            .collect();
+        let with_parameter_types: HashSet<&str> = parameter_types.iter().map(|(path, _)| *path).collect();
+        assert_eq!(with_query_parameters, with_parameter_types);
+
+        for (path, fields) in parameter_types {
+            assert!(!fields.is_empty(), "no fields recorded for the parameters of {}", path);
+            let documented = query_parameters(path);
+            let names: HashSet<&str> = documented.iter().filter_map(|p| p["name"].as_str()).collect();
+            for name in names.iter() {
+                assert!(
+                    fields.contains(name),
+                    "{} documents query parameter {} that its parameters do not accept",
+                    path,
+                    name
+                );
+            }
+
+            // fields not documented by name must be aliases noted in a parameter's description
+            for field in fields.iter().filter(|field| !names.contains(*field)) {
+                let alias = format!("`{}`", field);
+                assert!(
+                    documented
+                        .iter()
+                        .any(|p| p["description"].as_str().map(|d| d.contains(&alias)).unwrap_or(false)),
+                    "{} does not document its {} query parameter",
+                    path,
+                    field
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_docs_page_loads_configured_swagger_ui() {
+        let page = docs_page("https://assets.example.com/swagger-ui/");
+        assert!(page.contains(r#"href="https://assets.example.com/swagger-ui/swagger-ui.css""#));
+        assert!(page.contains(r#"src="https://assets.example.com/swagger-ui/swagger-ui-bundle.js""#));
+        assert!(page.contains(r##"SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });"##));
+    }
+
+    #[test]
+    fn test_openapi_references_resolve() {
+        fn collect_refs(value: &Value, refs: &mut HashSet<String>) {
+            match value {
+                Value::Object(object) => {
+                    if let Some(Value::String(reference)) = object.get("$ref") {
+                        refs.insert(reference.clone());
+                    }
+                    object.values().for_each(|v| collect_refs(v, refs));
+                }
+                Value::Array(values) => values.iter().for_each(|v| collect_refs(v, refs)),
+                _ => {}
+            }
+        }
+
+        let document = openapi_document();
+        let mut refs = HashSet::new();
+        collect_refs(&document, &mut refs);
+        for reference in refs {
+            let pointer = reference.trim_start_matches('#');
+            assert!(
+                document.pointer(pointer).is_some(),
+                "unresolved reference: {}",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_openapi_paths_are_routed() {
+        let operations = api_operations();
+        let document = openapi_document();
+        let nr_documented: usize = document["paths"]
+            .as_object()
+            .map(|paths| paths.values().map(|p| p.as_object().map(|o| o.len()).unwrap_or(0)).sum())
+            .unwrap_or(0);
+        assert_eq!(nr_documented, operations.len());
+
+        actix_web::rt::System::new().block_on(async {
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(DocsSettings::default()))
+                    .configure(configure_api_routes)
+                    .default_service(web::route().to(crate::server::errors::not_found)),
+            )
+            .await;
+
+            for operation in operations {
+                let method = assert_ok!(Method::from_bytes(operation.method.to_uppercase().as_bytes()));
+                let request = test::TestRequest::default()
+                    .method(method)
+                    .uri(&operation.path.replace("{apn}", "6633050420"))
+                    .to_request();
+                let response = test::call_service(&app, request).await;
+                assert!(
+                    response.status() != StatusCode::NOT_FOUND && response.status() != StatusCode::METHOD_NOT_ALLOWED,
+                    "documented operation {} {} is not routed: {}",
+                    operation.method,
+                    operation.path,
+                    response.status()
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage};
+use chrono::{NaiveDate, Utc};
+
+use crate::server::auth::{ApiCaller, PUBLIC_PATHS};
+use crate::server::errors::ProblemDetails;
+use crate::server::settings::{ClientLimits, IpLimits, RateLimitSettings};
+
+/// Number of tracked clients above which idle clients are forgotten.
+const NR_CLIENTS_PRUNE_THRESHOLD: usize = 10_000;
+
+/// Token bucket admitting requests at a sustained rate with bursts up to its capacity.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn full(limits: &ClientLimits, now: Instant) -> Self {
+        Self { tokens: limits.burst as f64, last_refill: now }
+    }
+
+    fn refill(&mut self, limits: &ClientLimits, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limits.requests_per_second).min(limits.burst as f64);
+        self.last_refill = now;
+    }
+
+    /// Takes a token for a request, or returns how long until a token is available.
+    fn try_acquire(&mut self, limits: &ClientLimits, now: Instant) -> Result<(), Duration> {
+        self.refill(limits, now);
+        if 1.0 <= self.tokens {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if limits.requests_per_second <= 0.0 {
+            Err(until_next_day())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limits.requests_per_second,
+            ))
+        }
+    }
+
+    fn is_full(&self, limits: &ClientLimits, now: Instant) -> bool {
+        let mut bucket = self.clone();
+        bucket.refill(limits, now);
+        limits.burst as f64 <= bucket.tokens
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DailyUsage {
+    day: NaiveDate,
+    nr_rows: u64,
+}
+
+#[derive(Debug)]
+struct ClientState {
+    bucket: TokenBucket,
+    usage: DailyUsage,
+}
+
+/// Why a request was refused, along with how long the client should wait before retrying.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Refusal {
+    RateExceeded(Duration),
+    QuotaExhausted(Duration),
+}
+
+/// Request rate limits and daily row quotas per client, where a client is identified by its API key
+/// or, for unauthenticated requests, by its IP address. State is kept in memory, so limits apply
+/// per server instance.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    settings: Arc<RateLimitSettings>,
+    clients: Arc<Mutex<HashMap<String, ClientState>>>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            settings: Arc::new(settings),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Admits a request if the client has a token available and has not exhausted its daily quota.
+    pub fn admit(
+        &self, client: &str, api_key_name: Option<&str>, now: Instant, today: NaiveDate,
+    ) -> Result<(), Refusal> {
+        let limits = self.settings.limits_for(api_key_name);
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        if NR_CLIENTS_PRUNE_THRESHOLD < clients.len() {
+            clients.retain(|_, state| !state.bucket.is_full(limits, now) || state.usage.day == today);
+        }
+
+        let state = clients.entry(client.to_string()).or_insert_with(|| ClientState {
+            bucket: TokenBucket::full(limits, now),
+            usage: DailyUsage { day: today, nr_rows: 0 },
+        });
+
+        if state.usage.day != today {
+            state.usage = DailyUsage { day: today, nr_rows: 0 };
+        }
+        if limits.daily_row_quota <= state.usage.nr_rows {
+            return Err(Refusal::QuotaExhausted(until_next_day()));
+        }
+
+        state.bucket.try_acquire(limits, now).map_err(Refusal::RateExceeded)
+    }
+
+    /// Number of result items the client may still be returned today.
+    pub fn remaining_rows(&self, client: &str, api_key_name: Option<&str>, today: NaiveDate) -> u64 {
+        let limits = self.settings.limits_for(api_key_name);
+        let clients = self.clients.lock().expect("rate limiter lock poisoned");
+        let nr_used = clients
+            .get(client)
+            .filter(|state| state.usage.day == today)
+            .map_or(0, |state| state.usage.nr_rows);
+        limits.daily_row_quota.saturating_sub(nr_used)
+    }
+
+    /// Counts result items returned to the client against its daily quota.
+    pub fn record_rows(&self, client: &str, nr_rows: usize, today: NaiveDate) {
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        if let Some(state) = clients.get_mut(client) {
+            if state.usage.day != today {
+                state.usage = DailyUsage { day: today, nr_rows: 0 };
+            }
+            state.usage.nr_rows += nr_rows as u64;
+        }
+    }
+}
+
+fn today() -> NaiveDate {
+    Utc::now().naive_utc().date()
+}
+
+pub(crate) fn until_next_day() -> Duration {
+    let now = Utc::now().naive_utc();
+    now.date()
+        .succ_opt()
+        .and_then(|tomorrow| tomorrow.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| (midnight - now).to_std().ok())
+        .unwrap_or_else(|| Duration::from_secs(0))
+}
+
+/// Handle on the daily row quota of the client making a request. Handlers extract it via
+/// `web::ReqData<RowQuota>` to record the number of items they return.
+#[derive(Clone)]
+pub struct RowQuota {
+    limiter: Option<RateLimiter>,
+    client: String,
+    api_key_name: Option<String>,
+}
+
+impl fmt::Debug for RowQuota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RowQuota")
+            .field("client", &self.client)
+            .field("limited", &self.limiter.is_some())
+            .finish()
+    }
+}
+
+impl RowQuota {
+    /// Quota for requests not subject to rate limiting.
+    pub fn unlimited() -> Self {
+        Self {
+            limiter: None,
+            client: String::new(),
+            api_key_name: None,
+        }
+    }
+
+    /// Number of result items the client may still be returned today, or `None` if the client's
+    /// requests are not limited.
+    pub fn remaining(&self) -> Option<u64> {
+        self.limiter
+            .as_ref()
+            .map(|limiter| limiter.remaining_rows(self.client.as_str(), self.api_key_name.as_deref(), today()))
+    }
+
+    pub fn record(&self, nr_rows: usize) {
+        if let Some(limiter) = &self.limiter {
+            limiter.record_rows(self.client.as_str(), nr_rows, today());
+        }
+    }
+}
+
+/// Which client a rate limit applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LimitedClient {
+    /// The authenticated caller, by API key, or its IP address for unauthenticated requests.
+    Caller,
+
+    /// The IP address, whether or not the request is authenticated.
+    IpAddress,
+}
+
+/// Middleware refusing requests over a client's rate limit or daily quota with `429 Too Many
+/// Requests` and a `Retry-After` header. The limit per caller must run after `ApiKeyAuth` so the
+/// caller is known; the limit per IP address runs before it.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    limiter: Option<RateLimiter>,
+    limited: LimitedClient,
+}
+
+impl RateLimit {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        let limiter = if settings.enabled { Some(RateLimiter::new(settings)) } else { None };
+        Self { limiter, limited: LimitedClient::Caller }
+    }
+
+    /// Limits the request rate of each IP address before its API key is authenticated, so requests
+    /// with missing or invalid keys, each costing a key lookup, are throttled too. No daily quota
+    /// applies.
+    pub fn per_ip(settings: &RateLimitSettings) -> Self {
+        let IpLimits { requests_per_second, burst } = settings.per_ip;
+        let ip_settings = RateLimitSettings {
+            enabled: settings.enabled,
+            limits: ClientLimits {
+                requests_per_second,
+                burst,
+                daily_row_quota: u64::MAX,
+            },
+            clients: HashMap::new(),
+            per_ip: settings.per_ip.clone(),
+        };
+        let limiter = if settings.enabled { Some(RateLimiter::new(ip_settings)) } else { None };
+        Self { limiter, limited: LimitedClient::IpAddress }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+            limited: self.limited,
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: Option<RateLimiter>,
+    limited: LimitedClient,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if self.limited == LimitedClient::IpAddress {
+            return self.call_per_ip(req);
+        }
+
+        let limiter = match &self.limiter {
+            Some(limiter) if !PUBLIC_PATHS.contains(&req.path()) => limiter.clone(),
+            _ => {
+                req.extensions_mut().insert(RowQuota::unlimited());
+                return Box::pin(self.service.call(req));
+            }
+        };
+
+        let caller = req.extensions().get::<ApiCaller>().cloned();
+        let api_key_name = caller.as_ref().and_then(|c| c.key_id.map(|_| c.name.clone()));
+        let client = match caller.as_ref().and_then(|c| c.key_id) {
+            Some(key_id) => format!("key:{}", key_id),
+            None => ip_client(&req),
+        };
+
+        if let Err(refusal) = limiter.admit(client.as_str(), api_key_name.as_deref(), Instant::now(), today()) {
+            tracing::warn!(%client, ?refusal, path=%req.path(), "request refused by rate limiter");
+            return Box::pin(async move { Err(too_many_requests(refusal)) });
+        }
+
+        req.extensions_mut()
+            .insert(RowQuota { limiter: Some(limiter), client, api_key_name });
+        Box::pin(self.service.call(req))
+    }
+}
+
+impl<S, B> RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    fn call_per_ip(&self, req: ServiceRequest) -> Pin<Box<dyn Future<Output = Result<ServiceResponse<B>, Error>>>> {
+        if let Some(limiter) = self.limiter.as_ref().filter(|_| !PUBLIC_PATHS.contains(&req.path())) {
+            let client = ip_client(&req);
+            if let Err(refusal) = limiter.admit(client.as_str(), None, Instant::now(), today()) {
+                tracing::warn!(%client, ?refusal, path=%req.path(), "request refused by IP rate limiter");
+                return Box::pin(async move { Err(too_many_requests(refusal)) });
+            }
+        }
+        Box::pin(self.service.call(req))
+    }
+}
+
+fn ip_client(req: &ServiceRequest) -> String {
+    format!(
+        "ip:{}",
+        req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default()
+    )
+}
+
+fn too_many_requests(refusal: Refusal) -> Error {
+    let (retry_after, detail) = match refusal {
+        Refusal::RateExceeded(wait) => (wait, "Request rate limit exceeded."),
+        Refusal::QuotaExhausted(wait) => (wait, "Daily result quota exhausted."),
+    };
+    let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    let problem = ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "rate-limited", "Too many requests")
+        .with_detail(format!("{} Retry after {} seconds.", detail, retry_after_secs));
+    let mut response = problem.into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    InternalError::from_response(detail, response).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_none, assert_ok, assert_some};
+    use pretty_assertions::assert_eq;
+
+    fn settings() -> RateLimitSettings {
+        let mut settings = RateLimitSettings::default();
+        settings.limits = ClientLimits {
+            requests_per_second: 2.0,
+            burst: 3,
+            daily_row_quota: 10,
+        };
+        settings
+    }
+
+    #[test]
+    fn test_rate_limiter_token_bucket() {
+        let limiter = RateLimiter::new(settings());
+        let start = Instant::now();
+        let today = today();
+
+        for _ in 0..3 {
+            assert_ok!(limiter.admit("ip:10.0.0.1", None, start, today));
+        }
+        let refusal = assert_err!(limiter.admit("ip:10.0.0.1", None, start, today));
+        assert_eq!(refusal, Refusal::RateExceeded(Duration::from_millis(500)));
+
+        // other clients are unaffected
+        assert_ok!(limiter.admit("ip:10.0.0.2", None, start, today));
+
+        // tokens refill at the sustained rate
+        assert_ok!(limiter.admit("ip:10.0.0.1", None, start + Duration::from_millis(500), today));
+        assert_err!(limiter.admit("ip:10.0.0.1", None, start + Duration::from_millis(600), today));
+        assert_ok!(limiter.admit("ip:10.0.0.1", None, start + Duration::from_secs(10), today));
+    }
+
+    #[test]
+    fn test_rate_limiter_daily_quota() {
+        let limiter = RateLimiter::new(settings());
+        let start = Instant::now();
+        let today = today();
+
+        assert_ok!(limiter.admit("key:1", Some("partner"), start, today));
+        assert_eq!(limiter.remaining_rows("key:1", Some("partner"), today), 10);
+        limiter.record_rows("key:1", 7, today);
+        assert_eq!(limiter.remaining_rows("key:1", Some("partner"), today), 3);
+        assert_ok!(limiter.admit("key:1", Some("partner"), start + Duration::from_secs(1), today));
+        limiter.record_rows("key:1", 3, today);
+        assert_eq!(limiter.remaining_rows("key:1", Some("partner"), today), 0);
+        let refusal = assert_err!(limiter.admit("key:1", Some("partner"), start + Duration::from_secs(5), today));
+        assert!(matches!(refusal, Refusal::QuotaExhausted(_)));
+
+        // quota resets the next day
+        assert_ok!(limiter.admit(
+            "key:1",
+            Some("partner"),
+            start + Duration::from_secs(10),
+            today.succ_opt().expect("tomorrow")
+        ));
+    }
+
+    #[test]
+    fn test_ip_rate_limit() {
+        let mut settings = settings();
+        settings.per_ip = IpLimits { requests_per_second: 1.0, burst: 2 };
+        let limiter = assert_some!(RateLimit::per_ip(&settings).limiter);
+        let start = Instant::now();
+        let today = today();
+
+        for _ in 0..2 {
+            assert_ok!(limiter.admit("ip:10.0.0.1", None, start, today));
+        }
+        let refusal = assert_err!(limiter.admit("ip:10.0.0.1", None, start, today));
+        assert_eq!(refusal, Refusal::RateExceeded(Duration::from_secs(1)));
+
+        // only the request rate is limited, not the number of result items
+        limiter.record_rows("ip:10.0.0.1", 1_000, today);
+        assert_ok!(limiter.admit("ip:10.0.0.1", None, start + Duration::from_secs(10), today));
+
+        settings.enabled = false;
+        assert_none!(RateLimit::per_ip(&settings).limiter);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::{
+    Address, AssessorParcelNumber, ParsedAddress, PropensityScore, PropertyPropensityScoreRepository, ZipOrPostalCode,
+};
+use crate::server::address_index::{AddressIndexHandle, AddressSuggestion};
+use crate::server::auth::ApiCaller;
+use crate::server::metrics;
+use crate::server::rate_limit::RowQuota;
+use crate::server::routes::{find_model, PropensityRouteError};
+use crate::server::settings::AddressIndexSettings;
+use actix_web::web;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::convert::TryInto;
+use std::time::{Duration, Instant};
+
+const LIMIT_DEFAULT: u16 = 5;
+const MIN_CONFIDENCE_DEFAULT: f64 = 0.6;
+const NR_CANDIDATES_MAX: u16 = 200;
+const NR_SUGGESTIONS_DEFAULT: usize = 10;
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct AddressLookupParameters {
+    #[serde(alias = "address")]
+    pub q: String,
+
+    #[serde(default)]
+    pub limit: Option<u16>,
+
+    #[serde(default)]
+    pub min_confidence: Option<f64>,
+
+    /// Registered model whose scores are reported; defaults to `heloc`.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchType {
+    Exact,
+    Fuzzy,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddressMatchItem {
+    pub apn: AssessorParcelNumber,
+    pub match_type: MatchType,
+    pub confidence: f64,
+    #[serde(flatten)]
+    pub propensity_score: Option<PropensityScore>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddressLookupResponse {
+    pub normalized: String,
+    pub candidates: Vec<AddressMatchItem>,
+}
+
+/// Resolves a single-line address to the properties it most likely refers to. Properties matching
+/// every recognized component of the address are returned if there are any; otherwise candidates
+/// sharing the street number are ranked by match confidence.
+#[tracing::instrument(level = "info", skip(pool))]
+pub async fn propensity_address_lookup(
+    parameters: web::Query<AddressLookupParameters>, pool: web::Data<PgPool>, caller: web::ReqData<ApiCaller>,
+    quota: web::ReqData<RowQuota>,
+) -> Result<web::Json<AddressLookupResponse>, PropensityRouteError> {
+    caller.require_scope(Scope::ScoresRead)?;
+    let address = ParsedAddress::parse(parameters.q.as_str())?;
+    let limit = parameters.limit.unwrap_or(LIMIT_DEFAULT) as usize;
+    let min_confidence = parameters.min_confidence.unwrap_or(MIN_CONFIDENCE_DEFAULT);
+    let model = find_model(parameters.model.as_deref(), &pool).await?;
+
+    let candidates = metrics::track_query(
+        "find_address_score_candidates",
+        PropertyPropensityScoreRepository::find_address_score_candidates(
+            &address,
+            &model.name,
+            NR_CANDIDATES_MAX,
+            &pool,
+        ),
+    )
+    .await
+    .context(format!("Failed to find properties matching address, {}", address));
+    if let Err(ref error) = candidates {
+        tracing::error!(?error, "failed to search repository for address candidates.");
+    }
+    let candidates = candidates?;
+
+    let (exact, fuzzy): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|(_, candidate, _)| address.is_exact_match(candidate));
+
+    let mut matches: Vec<AddressMatchItem> = if !exact.is_empty() {
+        exact
+            .into_iter()
+            .map(|(apn, candidate, propensity_score)| AddressMatchItem {
+                apn,
+                match_type: MatchType::Exact,
+                confidence: 1.0,
+                propensity_score,
+                address: Some(candidate),
+            })
+            .collect()
+    } else {
+        fuzzy
+            .into_iter()
+            .map(|(apn, candidate, propensity_score)| AddressMatchItem {
+                apn,
+                match_type: MatchType::Fuzzy,
+                confidence: address.match_confidence(&candidate),
+                propensity_score,
+                address: Some(candidate),
+            })
+            .filter(|item| min_confidence <= item.confidence)
+            .collect()
+    };
+
+    matches.sort_by(|lhs, rhs| {
+        rhs.confidence
+            .partial_cmp(&lhs.confidence)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    matches.truncate(limit);
+    if !caller.has_scope(Scope::AddressesRead) {
+        matches.iter_mut().for_each(|item| item.address = None);
+    }
+    quota.record(matches.len());
+
+    Ok(web::Json(AddressLookupResponse {
+        normalized: address.to_string(),
+        candidates: matches,
+    }))
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct AddressSuggestParameters {
+    pub prefix: String,
+
+    #[serde(default)]
+    #[serde(alias = "zip")]
+    #[serde(alias = "zipcode")]
+    pub zip_code: Option<String>,
+
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Suggests addresses beginning with the typed prefix from the in-memory address index.
+#[tracing::instrument(level = "info", skip(address_index, settings))]
+pub async fn address_suggest(
+    parameters: web::Query<AddressSuggestParameters>, address_index: web::Data<AddressIndexHandle>,
+    settings: web::Data<AddressIndexSettings>, caller: web::ReqData<ApiCaller>, quota: web::ReqData<RowQuota>,
+) -> Result<web::Json<Vec<AddressSuggestion>>, PropensityRouteError> {
+    caller.require_scope(Scope::AddressesRead)?;
+    let deadline = Instant::now() + Duration::from_millis(settings.latency_budget_millis);
+    let zip_code: Option<ZipOrPostalCode> = parameters.zip_code.clone().map(|zip| zip.try_into()).transpose()?;
+    let limit = parameters
+        .limit
+        .unwrap_or(NR_SUGGESTIONS_DEFAULT)
+        .min(settings.max_suggestions);
+
+    let index = address_index.current().ok_or(PropensityRouteError::AddressIndexWarming)?;
+    let suggestions = index.suggest(parameters.prefix.as_str(), zip_code.as_ref(), limit, deadline);
+    quota.record(suggestions.len());
+    Ok(web::Json(suggestions))
+}
@@ -0,0 +1,22 @@
+use crate::core::domain::api_key::Scope;
+use crate::server::auth::ApiCaller;
+use crate::server::cache::ZipScoresCache;
+use crate::server::routes::PropensityRouteError;
+use actix_web::web;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct CacheFlushReport {
+    pub nr_flushed: usize,
+}
+
+/// Discards every cached response so that subsequent requests are answered from the database.
+#[tracing::instrument(level = "info", skip(cache))]
+pub async fn flush_response_cache(
+    cache: web::Data<ZipScoresCache>, caller: web::ReqData<ApiCaller>,
+) -> Result<web::Json<CacheFlushReport>, PropensityRouteError> {
+    caller.require_scope(Scope::Admin)?;
+    let nr_flushed = cache.flush();
+    tracing::info!(target: "audit", api_key_name=%caller.name, %nr_flushed, "flushed response cache");
+    Ok(web::Json(CacheFlushReport { nr_flushed }))
+}
@@ -0,0 +1,221 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::property::{AddressCriteria, PropertyAttributes, PropertyRecordRepository};
+use crate::core::domain::{AssessorParcelNumber, PropensityScore, PropertyPropensityScoreRepository, ZipOrPostalCode};
+use crate::server::auth::ApiCaller;
+use crate::server::metrics;
+use crate::server::rate_limit::RowQuota;
+use crate::server::routes::{find_model, tier_name, PropensityRouteError, PropensitySearchItem};
+use actix_web::web;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+const BATCH_SIZE_MAX: usize = 1_000;
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct PropensityBatchRequest {
+    /// Registered model whose scores are looked up; defaults to `heloc`.
+    #[serde(default)]
+    pub model: Option<String>,
+
+    pub items: Vec<PropensityBatchQuery>,
+}
+
+/// A single lookup in a batch, identifying the property either by its APN or by its structured
+/// address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PropensityBatchQuery {
+    Apn { apn: String },
+    Address { address: BatchAddressQuery },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchAddressQuery {
+    pub street_number: String,
+    pub street_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street_suffix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_number: Option<String>,
+    #[serde(alias = "zip")]
+    #[serde(alias = "zipcode")]
+    pub zip_code: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    Found,
+    InvalidApn,
+    InvalidAddress,
+    AmbiguousAddress,
+    NotFound,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PropensityBatchItem {
+    pub query: PropensityBatchQuery,
+    pub status: BatchItemStatus,
+    #[serde(flatten)]
+    pub result: Option<PropensitySearchItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl PropensityBatchItem {
+    fn found(query: PropensityBatchQuery, item: PropensitySearchItem) -> Self {
+        Self {
+            query,
+            status: BatchItemStatus::Found,
+            result: Some(item),
+            error: None,
+        }
+    }
+
+    fn failed(query: PropensityBatchQuery, status: BatchItemStatus, error: impl Into<String>) -> Self {
+        Self {
+            query,
+            status,
+            result: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Progress of a single batch item as it is resolved to an APN and then to its score.
+enum Resolution {
+    Apn(AssessorParcelNumber),
+    Failed(BatchItemStatus, String),
+}
+
+#[tracing::instrument(level = "info", skip(request, pool), fields(nr_items = request.items.len()))]
+pub async fn propensity_batch(
+    request: web::Json<PropensityBatchRequest>, pool: web::Data<PgPool>, caller: web::ReqData<ApiCaller>,
+    quota: web::ReqData<RowQuota>,
+) -> Result<web::Json<Vec<PropensityBatchItem>>, PropensityRouteError> {
+    caller.require_scope(Scope::ScoresRead)?;
+    let include_address = caller.has_scope(Scope::AddressesRead);
+    let PropensityBatchRequest { model, items: queries } = request.into_inner();
+    if BATCH_SIZE_MAX < queries.len() {
+        return Err(PropensityRouteError::BatchTooLarge { size: queries.len(), max: BATCH_SIZE_MAX });
+    }
+    let model = find_model(model.as_deref(), &pool).await?;
+
+    let mut resolutions: Vec<Option<Resolution>> = Vec::with_capacity(queries.len());
+    let mut address_positions = vec![];
+    let mut address_criteria = vec![];
+    for (position, query) in queries.iter().enumerate() {
+        match query {
+            PropensityBatchQuery::Apn { apn } => {
+                let resolution = match AssessorParcelNumber::new(apn.as_str()) {
+                    Ok(apn) => Resolution::Apn(apn),
+                    Err(err) => Resolution::Failed(BatchItemStatus::InvalidApn, err.to_string()),
+                };
+                resolutions.push(Some(resolution));
+            }
+            PropensityBatchQuery::Address { address } => match ZipOrPostalCode::new(address.zip_code.as_str()) {
+                Ok(zip_code) => {
+                    address_positions.push(position);
+                    address_criteria.push(AddressCriteria::new(
+                        address.street_number.as_str(),
+                        address.street_name.as_str(),
+                        address.street_suffix.as_deref(),
+                        address.secondary_number.as_deref(),
+                        zip_code,
+                    ));
+                    resolutions.push(None);
+                }
+                Err(err) => resolutions.push(Some(Resolution::Failed(
+                    BatchItemStatus::InvalidAddress,
+                    err.to_string(),
+                ))),
+            },
+        }
+    }
+
+    let address_matches = metrics::track_query(
+        "find_apns_for_addresses",
+        PropertyRecordRepository::find_apns_for_addresses(&address_criteria, &pool),
+    )
+    .await
+    .context("Failed to resolve batch addresses to APNs")?;
+    let mut matches_by_position: HashMap<usize, Vec<AssessorParcelNumber>> = HashMap::new();
+    for (criteria_idx, apn) in address_matches {
+        matches_by_position
+            .entry(address_positions[criteria_idx])
+            .or_insert_with(Vec::new)
+            .push(apn);
+    }
+    for position in address_positions {
+        let resolution = match matches_by_position.remove(&position) {
+            None => Resolution::Failed(BatchItemStatus::NotFound, "no property found at address".to_string()),
+            Some(mut apns) if apns.len() == 1 => Resolution::Apn(apns.remove(0)),
+            Some(apns) => Resolution::Failed(
+                BatchItemStatus::AmbiguousAddress,
+                format!(
+                    "address matches {} properties; include street suffix or secondary number",
+                    apns.len()
+                ),
+            ),
+        };
+        resolutions[position] = Some(resolution);
+    }
+
+    let apns: Vec<AssessorParcelNumber> = resolutions
+        .iter()
+        .filter_map(|r| match r {
+            Some(Resolution::Apn(apn)) => Some(apn.clone()),
+            _ => None,
+        })
+        .collect();
+    let scores = metrics::track_query(
+        "find_address_scores_for_apns",
+        PropertyPropensityScoreRepository::find_address_scores_for_apns(&apns, &model.name, &pool),
+    )
+    .await
+    .context("Failed to find propensity scores for batch of APNs");
+    if let Err(ref error) = scores {
+        tracing::error!(
+            ?error,
+            "failed to search repository for {} batch propensity scores.",
+            apns.len()
+        );
+    }
+    let scores: HashMap<String, (PropensityScore, _)> = scores?
+        .into_iter()
+        .map(|(score, address)| (score.apn.to_string(), (score.score, address)))
+        .collect();
+
+    let report: Vec<PropensityBatchItem> = queries
+        .into_iter()
+        .zip(resolutions)
+        .map(|(query, resolution)| match resolution {
+            Some(Resolution::Apn(apn)) => match scores.get(apn.as_ref()).cloned() {
+                Some((propensity_score, address)) => PropensityBatchItem::found(
+                    query,
+                    PropensitySearchItem {
+                        apn,
+                        propensity_score,
+                        tier: tier_name(&model, &propensity_score),
+                        matched: None,
+                        zip_code: None,
+                        address: address.filter(|_| include_address),
+                        attributes: PropertyAttributes::default(),
+                    },
+                ),
+                None => PropensityBatchItem::failed(
+                    query,
+                    BatchItemStatus::NotFound,
+                    format!("no propensity score found for APN {}", apn),
+                ),
+            },
+            Some(Resolution::Failed(status, error)) => PropensityBatchItem::failed(query, status, error),
+            None => PropensityBatchItem::failed(query, BatchItemStatus::NotFound, "lookup was not resolved"),
+        })
+        .collect();
+
+    quota.record(report.iter().filter(|item| item.result.is_some()).count());
+    Ok(web::Json(report))
+}
@@ -0,0 +1,205 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::propensity_model::PropensityModel;
+use crate::core::domain::property::{PropertyField, PropertyFields};
+use crate::core::domain::{GeoCoordinate, PropertyPropensityScoreRepository, RegionScoresQuery};
+use crate::server::auth::ApiCaller;
+use crate::server::formats::ExportFormat;
+use crate::server::rate_limit::RowQuota;
+use crate::server::routes::{find_model, permitted_fields, tier_name, PropensityRouteError, PropensitySearchItem};
+use crate::server::settings::ExportSettings;
+use actix_web::http::header::Accept;
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use anyhow::Context;
+use futures::TryStreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::io;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Number of rows encoded into each chunk of the response body.
+const EXPORT_CHUNK_ROWS: usize = 500;
+
+/// Number of encoded chunks buffered ahead of a slow client before reading from the database pauses.
+const EXPORT_BUFFER_CHUNKS: usize = 4;
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ExportParameters {
+    pub state: String,
+
+    #[serde(default)]
+    pub county: Option<String>,
+
+    /// Registered model whose scores are exported; defaults to `heloc`.
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Export format (`ndjson` or `csv`), overriding the `Accept` header.
+    #[serde(default)]
+    pub format: Option<String>,
+
+    /// Comma separated property fields to include in each row; e.g., `land_use,bedrooms`.
+    #[serde(default, alias = "fields")]
+    pub include: Option<String>,
+}
+
+/// Streams every scored property in a state, or a county within it, as newline-delimited JSON or CSV.
+/// Rows are read from the database, encoded and sent in chunks as the client consumes them, so memory
+/// use does not grow with the size of the region. Regions with more rows than allowed are refused up
+/// front; an export exceeding its time limit is aborted, leaving the client with an incomplete body.
+#[tracing::instrument(level = "info", skip(pool, settings))]
+pub async fn export_propensity(
+    parameters: web::Query<ExportParameters>, accept: Option<web::Header<Accept>>, pool: web::Data<PgPool>,
+    settings: web::Data<ExportSettings>, caller: web::ReqData<ApiCaller>, quota: web::ReqData<RowQuota>,
+) -> Result<HttpResponse, PropensityRouteError> {
+    caller.require_scope(Scope::ScoresRead)?;
+    let include_address = caller.has_scope(Scope::AddressesRead);
+    let format = ExportFormat::negotiate(parameters.format.as_deref(), accept.as_deref())?;
+    let state = parameters.state.trim().to_uppercase();
+    let county = parameters.county.as_ref().map(|c| c.trim().to_uppercase());
+    let model = find_model(parameters.model.as_deref(), &pool).await?;
+    let fields = permitted_fields(parameters.include.as_deref().unwrap_or_default().parse()?, &caller);
+    let selected = if format.needs_location() && include_address {
+        fields.clone().with(PropertyField::Coordinates)
+    } else {
+        fields.clone()
+    };
+
+    let nr_rows = PropertyPropensityScoreRepository::count_for_region(&state, county.as_deref(), &model.name, &pool)
+        .await
+        .context(format!("Failed to count propensity scores to export in {}", state))?;
+    if settings.max_rows < nr_rows as u64 {
+        return Err(PropensityRouteError::ExportTooLarge { size: nr_rows as u64, max: settings.max_rows });
+    }
+
+    // quota is charged as rows are sent, so an export is refused up front if the client's remaining
+    // quota cannot cover it, and capped at that quota in case concurrent requests consume it meanwhile
+    let remaining = quota.remaining();
+    if let Some(remaining) = remaining {
+        if remaining < nr_rows as u64 {
+            return Err(PropensityRouteError::ExportExceedsQuota { size: nr_rows as u64, remaining });
+        }
+    }
+    let max_rows = remaining.map_or(settings.max_rows, |remaining| remaining.min(settings.max_rows));
+
+    let (sender, receiver) = mpsc::channel::<Result<Bytes, io::Error>>(EXPORT_BUFFER_CHUNKS);
+    let export = Export {
+        format,
+        query: RegionScoresQuery::new(selected),
+        fields,
+        state,
+        county,
+        model,
+        include_address,
+        max_rows: max_rows as i64,
+        timeout: Duration::from_secs(settings.timeout_secs),
+    };
+    tokio::spawn(export.run(pool.get_ref().clone(), sender, quota.into_inner()));
+
+    let body = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .streaming(Box::pin(body)))
+}
+
+#[derive(Debug)]
+struct Export {
+    format: ExportFormat,
+    query: RegionScoresQuery,
+    fields: PropertyFields,
+    state: String,
+    county: Option<String>,
+    model: PropensityModel,
+    /// Whether the caller may see properties' addresses and locations.
+    include_address: bool,
+    max_rows: i64,
+    timeout: Duration,
+}
+
+impl Export {
+    #[tracing::instrument(
+        level = "info",
+        skip(self, pool, sender, quota),
+        fields(state = %self.state, county = ?self.county, model = %self.model.name, format = ?self.format)
+    )]
+    async fn run(self, pool: PgPool, sender: mpsc::Sender<Result<Bytes, io::Error>>, quota: RowQuota) {
+        let mut nr_exported = 0;
+        let outcome = tokio::time::timeout(self.timeout, self.send_rows(&pool, &sender, &mut nr_exported)).await;
+        quota.record(nr_exported);
+
+        let error = match outcome {
+            Ok(Ok(())) => {
+                tracing::info!(%nr_exported, "propensity export completed");
+                return;
+            }
+            Ok(Err(error)) => {
+                tracing::error!(?error, %nr_exported, "propensity export failed");
+                io::Error::new(io::ErrorKind::Other, "export failed")
+            }
+            Err(_) => {
+                tracing::warn!(%nr_exported, timeout=?self.timeout, "propensity export timed out");
+                io::Error::new(io::ErrorKind::TimedOut, "export timed out")
+            }
+        };
+
+        // aborts the response so the client does not mistake a partial export for a complete one
+        let _ = sender.send(Err(error)).await;
+    }
+
+    async fn send_rows(
+        &self, pool: &PgPool, sender: &mpsc::Sender<Result<Bytes, io::Error>>, nr_exported: &mut usize,
+    ) -> anyhow::Result<()> {
+        self.send(sender, self.format.preamble(&self.fields, self.include_address)?)
+            .await?;
+
+        let mut rows = PropertyPropensityScoreRepository::stream_for_region(
+            &self.query,
+            &self.state,
+            self.county.as_deref(),
+            &self.model.name,
+            self.max_rows,
+            pool,
+        );
+        let mut chunk: Vec<(PropensitySearchItem, Option<GeoCoordinate>)> = Vec::with_capacity(EXPORT_CHUNK_ROWS);
+        while let Some((score, address, attributes)) = rows.try_next().await? {
+            let item = PropensitySearchItem {
+                apn: score.apn,
+                propensity_score: score.score,
+                tier: tier_name(&self.model, &score.score),
+                matched: None,
+                zip_code: None,
+                address: address.filter(|_| self.include_address),
+                attributes: attributes.retain(&self.fields),
+            };
+            chunk.push((item, attributes.coordinates.filter(|_| self.include_address)));
+
+            if EXPORT_CHUNK_ROWS <= chunk.len() {
+                self.send(sender, self.format.encode(&chunk, &self.fields, self.include_address)?)
+                    .await?;
+                *nr_exported += chunk.len();
+                chunk.clear();
+            }
+        }
+
+        if !chunk.is_empty() {
+            self.send(sender, self.format.encode(&chunk, &self.fields, self.include_address)?)
+                .await?;
+            *nr_exported += chunk.len();
+        }
+        Ok(())
+    }
+
+    async fn send(&self, sender: &mpsc::Sender<Result<Bytes, io::Error>>, encoded: Vec<u8>) -> anyhow::Result<()> {
+        if encoded.is_empty() {
+            return Ok(());
+        }
+
+        sender
+            .send(Ok(Bytes::from(encoded)))
+            .await
+            .map_err(|_| anyhow::anyhow!("client disconnected during export"))
+    }
+}
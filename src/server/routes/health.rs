@@ -1,7 +1,180 @@
-use actix_web::HttpResponse;
+use crate::core::domain::propensity::PropertyPropensityScoreRepository;
+use crate::core::domain::property::PropertyRecordRepository;
+use crate::core::{TableFreshness, POOL_MAX_CONNECTIONS};
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Time each database query of the readiness check is given to answer, so a struggling database
+/// cannot hold up readiness probes.
+const DATABASE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[tracing::instrument(level = "info")]
 pub async fn health_check() -> HttpResponse {
-    //todo!("check database");
     HttpResponse::Ok().finish()
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Live,
+    Ready,
+    NotReady,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LivenessReport {
+    pub status: HealthStatus,
+}
+
+/// Liveness only reports that the server is accepting requests; it deliberately does not depend on
+/// the database so that an orchestrator does not restart servers during a database outage.
+#[tracing::instrument(level = "info")]
+pub async fn health_live() -> HttpResponse {
+    HttpResponse::Ok().json(LivenessReport { status: HealthStatus::Live })
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    pub status: HealthStatus,
+    pub database: DatabaseCheck,
+    pub migrations: MigrationsCheck,
+    pub pool: PoolCheck,
+    pub freshness: FreshnessCheck,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseCheck {
+    pub reachable: bool,
+    pub latency_millis: u128,
+
+    /// Generic reason the database is not reachable, `unreachable` or `timed out`; the underlying
+    /// error is logged rather than reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationsCheck {
+    pub up_to_date: bool,
+    pub expected_version: Option<i64>,
+    pub applied_version: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolCheck {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+    pub saturation: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FreshnessCheck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<TableFreshness>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub propensities: Option<TableFreshness>,
+}
+
+/// Readiness verifies the server can actually serve scores: the database must answer a ping within
+/// a short timeout and its schema must be migrated to the version this build expects. Pool
+/// saturation and data freshness are reported for operators but do not affect readiness; freshness
+/// uses estimated row counts so probes stay cheap on large tables.
+#[tracing::instrument(level = "info", skip(pool))]
+pub async fn health_ready(pool: web::Data<PgPool>) -> HttpResponse {
+    let database = ping_database(&pool).await;
+
+    let mut migrations = MigrationsCheck {
+        up_to_date: false,
+        expected_version: crate::core::expected_migration_version(),
+        applied_version: None,
+    };
+    let mut freshness = FreshnessCheck::default();
+
+    if database.reachable {
+        if let Ok(applied) = within_check_timeout(
+            "applied schema migrations",
+            crate::core::find_applied_migration_version(&pool),
+        )
+        .await
+        {
+            migrations.applied_version = applied;
+            migrations.up_to_date = migrations.expected_version <= applied;
+        }
+
+        let (properties, propensities) = futures::join!(
+            within_check_timeout(
+                "property freshness",
+                PropertyRecordRepository::estimate_freshness(&pool)
+            ),
+            within_check_timeout(
+                "propensity freshness",
+                PropertyPropensityScoreRepository::estimate_freshness(&pool)
+            ),
+        );
+        freshness.properties = properties.ok();
+        freshness.propensities = propensities.ok();
+    }
+
+    let size = pool.size();
+    let idle = pool.num_idle();
+    let pool_check = PoolCheck {
+        size,
+        idle,
+        max_connections: POOL_MAX_CONNECTIONS,
+        saturation: size.saturating_sub(idle as u32) as f64 / POOL_MAX_CONNECTIONS as f64,
+    };
+
+    let ready = database.reachable && migrations.up_to_date;
+    let report = ReadinessReport {
+        status: if ready { HealthStatus::Ready } else { HealthStatus::NotReady },
+        database,
+        migrations,
+        pool: pool_check,
+        freshness,
+    };
+
+    if ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        tracing::warn!(?report, "server is not ready");
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+async fn ping_database(pool: &PgPool) -> DatabaseCheck {
+    let start = Instant::now();
+    let error = within_check_timeout("database ping", sqlx::query("SELECT 1").execute(pool))
+        .await
+        .err()
+        .map(|reason| reason.to_string());
+
+    DatabaseCheck {
+        reachable: error.is_none(),
+        latency_millis: start.elapsed().as_millis(),
+        error,
+    }
+}
+
+/// Awaits a database query of the readiness check for at most `DATABASE_CHECK_TIMEOUT`. Since the
+/// check is public, why the query failed is only logged; the caller is given a generic reason.
+async fn within_check_timeout<T, E: fmt::Debug>(
+    check: &str, query: impl Future<Output = Result<T, E>>,
+) -> Result<T, &'static str> {
+    match tokio::time::timeout(DATABASE_CHECK_TIMEOUT, query).await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(error)) => {
+            tracing::warn!(?error, %check, "readiness check query failed");
+            Err("unreachable")
+        }
+        Err(_elapsed) => {
+            tracing::warn!(timeout=?DATABASE_CHECK_TIMEOUT, %check, "readiness check query timed out");
+            Err("timed out")
+        }
+    }
+}
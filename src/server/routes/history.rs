@@ -0,0 +1,98 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::{
+    AssessorParcelNumber, PropensityScore, PropensityScoreVersion, PropertyPropensityScoreRepository,
+};
+use crate::server::auth::ApiCaller;
+use crate::server::metrics;
+use crate::server::rate_limit::RowQuota;
+use crate::server::routes::{find_model, PropensityRouteError};
+use actix_web::web;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct PropensityHistoryParameters {
+    /// Date on which to report the score in effect (YYYY-MM-DD); defaults to the current score.
+    #[serde(default)]
+    pub as_of: Option<NaiveDate>,
+
+    /// Registered model whose scores are reported; defaults to `heloc`.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreVersionItem {
+    #[serde(flatten)]
+    pub propensity_score: PropensityScore,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zip_code: Option<String>,
+    pub effective_on: NaiveDate,
+    pub source: String,
+    pub loaded_on: DateTime<Utc>,
+}
+
+impl From<PropensityScoreVersion> for ScoreVersionItem {
+    fn from(version: PropensityScoreVersion) -> Self {
+        Self {
+            propensity_score: version.score,
+            zip_code: version.zip_or_postal_code.map(|z| z.as_ref().to_string()),
+            effective_on: version.effective_on,
+            source: version.source,
+            loaded_on: version.loaded_on,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PropensityHistoryResponse {
+    pub apn: AssessorParcelNumber,
+    pub model: String,
+
+    /// Version in effect on the `as_of` date, or currently; absent if the first version took effect
+    /// after the `as_of` date.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective: Option<ScoreVersionItem>,
+
+    /// Every version of the score, in the order they took effect.
+    pub versions: Vec<ScoreVersionItem>,
+}
+
+/// Reports how a property's propensity score has changed over the versions loaded for it, along
+/// with the version in effect on a given date.
+#[tracing::instrument(level = "info", skip(pool))]
+pub async fn propensity_history(
+    apn: web::Path<String>, parameters: web::Query<PropensityHistoryParameters>, pool: web::Data<PgPool>,
+    caller: web::ReqData<ApiCaller>, quota: web::ReqData<RowQuota>,
+) -> Result<web::Json<PropensityHistoryResponse>, PropensityRouteError> {
+    caller.require_scope(Scope::ScoresRead)?;
+    let apn = AssessorParcelNumber::new(apn.into_inner()).map_err(PropensityRouteError::InvalidApn)?;
+    let model = find_model(parameters.model.as_deref(), &pool).await?;
+
+    let versions = metrics::track_query(
+        "find_history",
+        PropertyPropensityScoreRepository::find_history(&apn, &model.name, &pool),
+    )
+    .await
+    .context(format!("Failed to find propensity score history for APN {}", apn))?;
+    if versions.is_empty() {
+        return Err(PropensityRouteError::NotFound(format!(
+            "No {} propensity score has been loaded for APN {}",
+            model.name, apn
+        )));
+    }
+
+    let effective = PropertyPropensityScoreRepository::find_version(&apn, &model.name, parameters.as_of, &pool)
+        .await
+        .context(format!("Failed to find propensity score in effect for APN {}", apn))?;
+
+    quota.record(versions.len());
+    Ok(web::Json(PropensityHistoryResponse {
+        apn,
+        model: model.name,
+        effective: effective.map(ScoreVersionItem::from),
+        versions: versions.into_iter().map(ScoreVersionItem::from).collect(),
+    }))
+}
@@ -1,8 +1,20 @@
+pub mod address;
+pub mod admin;
+pub mod batch;
+pub mod export;
 pub mod health;
+pub mod history;
 pub mod propensity;
+pub mod top;
 
+pub use address::*;
+pub use admin::*;
+pub use batch::*;
+pub use export::*;
 pub use health::*;
+pub use history::*;
 pub use propensity::*;
+pub use top::*;
 
 pub fn error_chain_fmt(e: &impl std::error::Error, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     writeln!(f, "{}\n", e)?;
@@ -1,14 +1,27 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::propensity_model::{PropensityModel, PropensityModelRepository, DEFAULT_MODEL};
+use crate::core::domain::property::{PropertyAttributes, PropertyField, PropertyFields};
 use crate::core::domain::{
-    Address, AssessorParcelNumber, DomainError, PropensityScore, PropertyPropensityScoreRepository,
+    Address, AssessorParcelNumber, DomainError, GeoCoordinate, PropensityScore, PropertyPropensityScoreRepository,
+    ZipCodeSource, ZipSearchOptions,
 };
+use crate::server::auth::ApiCaller;
+use crate::server::cache::{ZipScoresCache, ZipScoresKey};
+use crate::server::errors::{InvalidParam, ProblemDetails};
+use crate::server::formats::ResponseFormat;
+use crate::server::metrics;
+use crate::server::rate_limit::{self, RowQuota};
 use crate::server::routes::error_chain_fmt;
 // use crate::server::ApplicationBaseUrl;
+use actix_web::http::header::{Accept, HeaderValue, RETRY_AFTER};
 use actix_web::http::StatusCode;
-use actix_web::{web, ResponseError};
+use actix_web::{web, HttpResponse, ResponseError};
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::convert::TryInto;
+use std::sync::Arc;
+use validator::ValidationErrors;
 
 #[derive(Debug, PartialEq, Deserialize)]
 pub struct PropensityScoresParameters {
@@ -18,6 +31,31 @@ pub struct PropensityScoresParameters {
     #[serde(alias = "zip")]
     #[serde(alias = "zipcode")]
     pub zip_code: String,
+
+    /// Registered model whose scores are searched; defaults to `heloc`.
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Tier band of the model's scores to search; e.g., `High`.
+    #[serde(default)]
+    pub tier: Option<String>,
+
+    /// Response format (`json`, `csv` or `geojson`), overriding the `Accept` header.
+    #[serde(default)]
+    pub format: Option<String>,
+
+    /// Comma separated property fields to include in each result; e.g., `land_use,bedrooms,county`.
+    #[serde(default, alias = "fields")]
+    pub include: Option<String>,
+
+    /// Whether to include scores whose property is not loaded.
+    #[serde(default)]
+    pub include_unmatched: bool,
+
+    /// Zip code matched by the search (`propensity` or `property`); defaults to the zip code supplied
+    /// with the propensity score.
+    #[serde(default)]
+    pub zip_source: Option<String>,
 }
 
 #[derive(thiserror::Error)]
@@ -25,8 +63,32 @@ pub enum PropensityRouteError {
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 
-    #[error("User supplied invalid zip code: {0}")]
-    InvalidZipCode(#[from] DomainError),
+    #[error("User supplied invalid parameter: {0}")]
+    InvalidParameter(#[from] DomainError),
+
+    #[error("Batch of {size} lookups exceeds the maximum of {max}")]
+    BatchTooLarge { size: usize, max: usize },
+
+    #[error("Export of {size} rows exceeds the maximum of {max}; export a smaller region, such as a county")]
+    ExportTooLarge { size: u64, max: u64 },
+
+    #[error("Export of {size} rows exceeds the {remaining} rows remaining in today's quota; export a smaller region, such as a county")]
+    ExportExceedsQuota { size: u64, remaining: u64 },
+
+    #[error("Address index is warming up; try again shortly")]
+    AddressIndexWarming,
+
+    #[error("API key lacks the {0} scope required for this request")]
+    MissingScope(Scope),
+
+    #[error("Unsupported response format: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("Invalid APN: {0}")]
+    InvalidApn(#[source] ValidationErrors),
+
+    #[error("{0}")]
+    NotFound(String),
 }
 
 impl std::fmt::Debug for PropensityRouteError {
@@ -39,35 +101,206 @@ impl ResponseError for PropensityRouteError {
     fn status_code(&self) -> StatusCode {
         match self {
             Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::InvalidZipCode(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+            Self::BatchTooLarge { .. } | Self::ExportTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::ExportExceedsQuota { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::AddressIndexWarming => StatusCode::SERVICE_UNAVAILABLE,
+            Self::MissingScope(_) => StatusCode::FORBIDDEN,
+            Self::UnsupportedFormat(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidApn(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let problem = match self {
+            Self::UnexpectedError(_) => {
+                tracing::error!(error=?self, "unexpected error while handling request");
+                ProblemDetails::for_status(status)
+                    .with_detail("An unexpected error occurred while handling the request.")
+            }
+            Self::InvalidParameter(DomainError::InvalidZipOrPostalCode(errors)) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter")
+                    .with_invalid_params(InvalidParam::from_validation_errors(errors, Some("zip_code")))
+            }
+            Self::InvalidParameter(DomainError::UnparseableAddress(reason)) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter")
+                    .with_invalid_params(vec![InvalidParam::new("q", reason.as_str())])
+            }
+            Self::InvalidParameter(DomainError::UnrecognizedPropertyField(field)) => {
+                let known: Vec<&str> = PropertyField::ALL.iter().map(|f| f.as_str()).collect();
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter").with_invalid_params(vec![
+                    InvalidParam::new("include", format!("{} is not one of {}", field, known.join(", "))),
+                ])
+            }
+            Self::InvalidParameter(DomainError::UnrecognizedZipCodeSource(source)) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter").with_invalid_params(vec![
+                    InvalidParam::new("zip_source", format!("{} is not one of propensity or property", source)),
+                ])
+            }
+            Self::InvalidParameter(DomainError::UnrecognizedPropensityModel(model)) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter").with_invalid_params(vec![
+                    InvalidParam::new("model", format!("{} is not a registered model", model)),
+                ])
+            }
+            Self::InvalidParameter(DomainError::UnrecognizedTier { model, tier }) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter").with_invalid_params(vec![
+                    InvalidParam::new("tier", format!("{} is not a tier of model {}", tier, model)),
+                ])
+            }
+            Self::InvalidParameter(error) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter")
+                    .with_detail(error.to_string())
+            }
+            Self::BatchTooLarge { .. } => {
+                ProblemDetails::new(status, "batch-too-large", "Batch too large").with_detail(self.to_string())
+            }
+            Self::ExportTooLarge { .. } => {
+                ProblemDetails::new(status, "export-too-large", "Export too large").with_detail(self.to_string())
+            }
+            Self::ExportExceedsQuota { .. } => {
+                let retry_after = rate_limit::until_next_day();
+                let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                let mut response = ProblemDetails::new(status, "rate-limited", "Too many requests")
+                    .with_detail(self.to_string())
+                    .into_response();
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+                return response;
+            }
+            Self::AddressIndexWarming => {
+                ProblemDetails::new(status, "address-index-warming", "Address index unavailable")
+                    .with_detail(self.to_string())
+            }
+            Self::MissingScope(_) => {
+                ProblemDetails::new(status, "insufficient-scope", "Insufficient scope").with_detail(self.to_string())
+            }
+            Self::UnsupportedFormat(format) => {
+                ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter").with_invalid_params(vec![
+                    InvalidParam::new("format", format!("{} is not one of json, csv or geojson", format)),
+                ])
+            }
+            Self::InvalidApn(errors) => ProblemDetails::new(status, "invalid-parameter", "Invalid request parameter")
+                .with_invalid_params(InvalidParam::from_validation_errors(errors, Some("apn"))),
+            Self::NotFound(detail) => ProblemDetails::for_status(status).with_detail(detail.as_str()),
+        };
+
+        problem.into_response()
+    }
 }
 
 const LIMIT_DEFAULT: u16 = 10;
 
+/// Finds the registered model whose scores a request names, or the default model if it names none.
+pub(crate) async fn find_model(model: Option<&str>, pool: &PgPool) -> Result<PropensityModel, PropensityRouteError> {
+    let name = model.unwrap_or(DEFAULT_MODEL);
+    PropensityModelRepository::find(name, pool)
+        .await
+        .context(format!("Failed to find propensity model {}", name))?
+        .ok_or_else(|| DomainError::UnrecognizedPropensityModel(name.to_string()).into())
+}
+
+/// Property fields of a request that may be sent to the caller. A property's coordinates locate it
+/// as precisely as its address, so they are withheld from callers without the `addresses:read` scope.
+pub(crate) fn permitted_fields(fields: PropertyFields, caller: &ApiCaller) -> PropertyFields {
+    if caller.has_scope(Scope::AddressesRead) {
+        fields
+    } else {
+        fields.without(PropertyField::Coordinates)
+    }
+}
+
+/// Name of the model's tier band containing a score, if any.
+pub(crate) fn tier_name(model: &PropensityModel, score: &PropensityScore) -> Option<String> {
+    model.tier_of(score.score).map(|tier| tier.name.clone())
+}
+
 #[derive(Debug, Serialize)]
 pub struct PropensitySearchItem {
     pub apn: AssessorParcelNumber,
     #[serde(flatten)]
     pub propensity_score: PropensityScore,
+    /// Tier band of the model containing the score; omitted if the model defines no band containing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    /// Whether the score's property is loaded; reported only when unmatched scores are requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched: Option<bool>,
+    /// Zip code supplied with the score; reported only when unmatched scores are requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zip_code: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub address: Option<Address>,
+    #[serde(flatten)]
+    pub attributes: PropertyAttributes,
 }
 
-#[tracing::instrument(level = "info")]
+#[tracing::instrument(level = "info", skip(cache))]
 pub async fn propensity_search(
-    parameters: web::Query<PropensityScoresParameters>, pool: web::Data<PgPool>,
-) -> Result<web::Json<Vec<PropensitySearchItem>>, PropensityRouteError> {
+    parameters: web::Query<PropensityScoresParameters>, accept: Option<web::Header<Accept>>, pool: web::Data<PgPool>,
+    cache: web::Data<ZipScoresCache>, caller: web::ReqData<ApiCaller>, quota: web::ReqData<RowQuota>,
+) -> Result<HttpResponse, PropensityRouteError> {
+    caller.require_scope(Scope::ScoresRead)?;
+    let format = ResponseFormat::negotiate(parameters.format.as_deref(), accept.as_deref())?;
+    let include_address = caller.has_scope(Scope::AddressesRead);
+    if format.requires_location() {
+        caller.require_scope(Scope::AddressesRead)?;
+    }
     let zip_code = parameters.zip_code.clone().try_into()?;
+    let model = find_model(parameters.model.as_deref(), &pool).await?;
     let limit = parameters.limit.unwrap_or(LIMIT_DEFAULT);
-    let top_propensity_addresses =
-        PropertyPropensityScoreRepository::find_address_scores_for_zip_code(&zip_code, limit, &pool)
-            .await
-            .context(format!(
-                "Failed to find addresses with top propensity scores in zip code, {}",
-                zip_code
-            ));
+    let fields = permitted_fields(parameters.include.as_deref().unwrap_or_default().parse()?, &caller);
+    let options = ZipSearchOptions {
+        zip_source: parameters
+            .zip_source
+            .as_deref()
+            .map(str::parse::<ZipCodeSource>)
+            .transpose()?
+            .unwrap_or_default(),
+        include_unmatched: parameters.include_unmatched,
+        direction: model.scale.direction,
+        score_band: parameters
+            .tier
+            .as_deref()
+            .map(|tier| {
+                model
+                    .find_tier(tier)
+                    .map(|band| (band.min_score, band.max_score))
+                    .ok_or_else(|| DomainError::UnrecognizedTier { model: model.name.clone(), tier: tier.to_string() })
+            })
+            .transpose()?,
+    };
+    let selected = if format.needs_location() && include_address {
+        fields.clone().with(PropertyField::Coordinates)
+    } else {
+        fields.clone()
+    };
+    let top_propensity_addresses = cache
+        .get_or_try_load(
+            ZipScoresKey::new(&zip_code, &model.name, limit, &selected, options),
+            async {
+                metrics::track_query(
+                    "find_address_scores_for_zip_code",
+                    PropertyPropensityScoreRepository::find_address_scores_for_zip_code(
+                        &zip_code,
+                        &model.name,
+                        limit,
+                        &selected,
+                        options,
+                        &pool,
+                    ),
+                )
+                .await
+                .map(Arc::new)
+            },
+        )
+        .await
+        .context(format!(
+            "Failed to find addresses with top propensity scores in zip code, {}",
+            zip_code
+        ));
     if let Err(ref error) = top_propensity_addresses {
         tracing::error!(
             ?error,
@@ -76,13 +309,25 @@ pub async fn propensity_search(
         );
     }
 
-    let report = top_propensity_addresses?
-        .into_iter()
-        .map(|(score, address)| PropensitySearchItem {
-            apn: score.apn,
-            propensity_score: score.score,
-            address,
+    let report: Vec<(PropensitySearchItem, Option<GeoCoordinate>)> = top_propensity_addresses?
+        .iter()
+        .map(|(score, address, attributes)| {
+            let item = PropensitySearchItem {
+                apn: score.apn.clone(),
+                propensity_score: score.score,
+                tier: tier_name(&model, &score.score),
+                matched: Some(address.is_some()).filter(|_| options.include_unmatched),
+                zip_code: score
+                    .zip_or_postal_code
+                    .as_ref()
+                    .map(|z| z.as_ref().to_string())
+                    .filter(|_| options.include_unmatched),
+                address: address.clone().filter(|_| include_address),
+                attributes: attributes.retain(&fields),
+            };
+            (item, attributes.coordinates.clone().filter(|_| include_address))
         })
         .collect();
-    Ok(web::Json(report))
+    quota.record(report.len());
+    format.respond(report, &fields, include_address)
 }
@@ -0,0 +1,91 @@
+use crate::core::domain::api_key::Scope;
+use crate::core::domain::property::PropertyAttributes;
+use crate::core::domain::{PropertyPropensityScoreRepository, ZipRanking};
+use crate::server::auth::ApiCaller;
+use crate::server::metrics;
+use crate::server::rate_limit::RowQuota;
+use crate::server::routes::{find_model, tier_name, PropensityRouteError, PropensitySearchItem};
+use actix_web::web;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+const NR_TOP_DEFAULT: u16 = 10;
+const NR_TOP_MAX: u16 = 1_000;
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct TopScoresParameters {
+    pub state: String,
+
+    #[serde(default)]
+    pub county: Option<String>,
+
+    #[serde(default)]
+    pub city: Option<String>,
+
+    /// Number of properties to return; at most 1,000.
+    #[serde(default, alias = "limit")]
+    pub n: Option<u16>,
+
+    /// Registered model whose scores are ranked; defaults to `heloc`.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RankedSearchItem {
+    #[serde(flatten)]
+    pub item: PropensitySearchItem,
+    #[serde(flatten)]
+    pub ranking: ZipRanking,
+}
+
+/// Finds the best scored properties in a state, or a county or city within it, so a territory's
+/// best prospects can be seen without searching each of its zip codes. Each property is reported
+/// with its rank and percentile among the scored properties in its zip code.
+#[tracing::instrument(level = "info", skip(pool))]
+pub async fn propensity_top(
+    parameters: web::Query<TopScoresParameters>, pool: web::Data<PgPool>, caller: web::ReqData<ApiCaller>,
+    quota: web::ReqData<RowQuota>,
+) -> Result<web::Json<Vec<RankedSearchItem>>, PropensityRouteError> {
+    caller.require_scope(Scope::ScoresRead)?;
+    let include_address = caller.has_scope(Scope::AddressesRead);
+    let state = parameters.state.trim().to_uppercase();
+    let county = parameters.county.as_ref().map(|c| c.trim().to_uppercase());
+    let city = parameters.city.as_ref().map(|c| c.trim().to_uppercase());
+    let n = parameters.n.unwrap_or(NR_TOP_DEFAULT).min(NR_TOP_MAX);
+    let model = find_model(parameters.model.as_deref(), &pool).await?;
+
+    let ranked = metrics::track_query(
+        "find_top_scores_for_region",
+        PropertyPropensityScoreRepository::find_top_scores_for_region(
+            &state,
+            county.as_deref(),
+            city.as_deref(),
+            &model.name,
+            model.scale.direction,
+            n,
+            &pool,
+        ),
+    )
+    .await
+    .context(format!("Failed to find top propensity scores in {}", state))?;
+
+    let report: Vec<RankedSearchItem> = ranked
+        .into_iter()
+        .map(|(score, address, ranking)| RankedSearchItem {
+            item: PropensitySearchItem {
+                apn: score.apn,
+                propensity_score: score.score,
+                tier: tier_name(&model, &score.score),
+                matched: None,
+                zip_code: None,
+                address: address.filter(|_| include_address),
+                attributes: PropertyAttributes::default(),
+            },
+            ranking,
+        })
+        .collect();
+    quota.record(report.len());
+    Ok(web::Json(report))
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use clap::{AppSettings, Clap};
@@ -11,6 +12,178 @@ use settings_loader::{LoadingOptions, SettingsError, SettingsLoader};
 pub struct Settings {
     pub application: HttpServerSettings,
     pub database: DatabaseSettings,
+    #[serde(default)]
+    pub address_index: AddressIndexSettings,
+    #[serde(default)]
+    pub auth: AuthSettings,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    #[serde(default)]
+    pub response_cache: ResponseCacheSettings,
+    #[serde(default)]
+    pub export: ExportSettings,
+    #[serde(default)]
+    pub docs: DocsSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressIndexSettings {
+    /// How often to check whether properties were loaded and the address index should be rebuilt.
+    pub refresh_interval_secs: u64,
+
+    /// Upper bound on the number of address suggestions returned for a prefix.
+    pub max_suggestions: usize,
+
+    /// Time allowed to gather address suggestions before returning those found so far.
+    pub latency_budget_millis: u64,
+}
+
+impl Default for AddressIndexSettings {
+    fn default() -> Self {
+        Self {
+            refresh_interval_secs: 300,
+            max_suggestions: 25,
+            latency_budget_millis: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Whether requests must present an API key. Disable only for local development.
+    pub enabled: bool,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+
+    /// Limits applied to each client unless overridden in `clients`.
+    pub limits: ClientLimits,
+
+    /// Limits for specific clients, by API key name.
+    pub clients: HashMap<String, ClientLimits>,
+
+    /// Request rate of each IP address, applied before its API key is authenticated so requests with
+    /// missing or invalid keys are throttled as well.
+    pub per_ip: IpLimits,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            limits: ClientLimits::default(),
+            clients: HashMap::new(),
+            per_ip: IpLimits::default(),
+        }
+    }
+}
+
+impl RateLimitSettings {
+    pub fn limits_for(&self, api_key_name: Option<&str>) -> &ClientLimits {
+        api_key_name
+            .and_then(|name| self.clients.get(name))
+            .unwrap_or(&self.limits)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientLimits {
+    /// Sustained rate at which requests are admitted.
+    pub requests_per_second: f64,
+
+    /// Number of requests admitted in a burst above the sustained rate.
+    pub burst: u32,
+
+    /// Number of result items a client may retrieve per (UTC) day.
+    pub daily_row_quota: u64,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 10.0,
+            burst: 20,
+            daily_row_quota: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpLimits {
+    /// Sustained rate at which requests from an IP address are admitted.
+    pub requests_per_second: f64,
+
+    /// Number of requests from an IP address admitted in a burst above the sustained rate.
+    pub burst: u32,
+}
+
+impl Default for IpLimits {
+    fn default() -> Self {
+        Self { requests_per_second: 20.0, burst: 40 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheSettings {
+    pub enabled: bool,
+
+    /// Maximum number of cached responses; the least recently used response is evicted beyond it.
+    pub capacity: usize,
+
+    /// How long a cached response is served before it is reloaded.
+    pub ttl_secs: u64,
+}
+
+impl Default for ResponseCacheSettings {
+    fn default() -> Self {
+        Self { enabled: true, capacity: 1_000, ttl_secs: 300 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    /// Maximum number of rows in an export; larger regions must be exported in parts (e.g., by county).
+    pub max_rows: u64,
+
+    /// Time allowed for an export to complete before it is aborted.
+    pub timeout_secs: u64,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self { max_rows: 1_000_000, timeout_secs: 300 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocsSettings {
+    /// Base URL of the swagger-ui-dist assets the documentation page loads; point it at a mirror or
+    /// a path served alongside the server where the public CDN is not reachable.
+    pub swagger_ui_url: String,
+}
+
+impl Default for DocsSettings {
+    fn default() -> Self {
+        Self {
+            swagger_ui_url: "https://unpkg.com/swagger-ui-dist@3".to_string(),
+        }
+    }
 }
 
 impl SettingsLoader for Settings {
@@ -95,6 +268,12 @@ database:
                     database_name: "propensity".to_string(),
                     require_ssl: true,
                 },
+                address_index: AddressIndexSettings::default(),
+                auth: AuthSettings::default(),
+                rate_limit: RateLimitSettings::default(),
+                response_cache: ResponseCacheSettings::default(),
+                export: ExportSettings::default(),
+                docs: DocsSettings::default(),
             }
         );
         Ok(())
@@ -130,6 +309,12 @@ database:
                 database_name: "propensity".to_string(),
                 require_ssl: false,
             },
+            address_index: AddressIndexSettings::default(),
+            auth: AuthSettings::default(),
+            rate_limit: RateLimitSettings::default(),
+            response_cache: ResponseCacheSettings::default(),
+            export: ExportSettings::default(),
+            docs: DocsSettings::default(),
         };
 
         assert_eq!(actual, expected);